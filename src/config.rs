@@ -1,17 +1,83 @@
 //! # Configuration Management
 //!
-//! This module handles loading and validating application configuration from environment variables.
+//! This module handles loading and validating application configuration from environment
+//! variables, optionally layered over a TOML config file.
 //! It uses `serde` for deserialization and provides defaults where appropriate.
 //!
 //! Key components:
 //! - `Config`: The main configuration struct.
-//! - `ScheduleMode`: Enum defining how tests are scheduled (Hourly, Interval, Cron).
+//! - `ScheduleMode`: Enum defining how tests are scheduled (Hourly, Interval, Cron, Adaptive).
 //! - `SpeedtestConfig`: Configuration specific to the speedtest command.
 //!
 //! Note: The speedtest command and arguments are hardcoded to ensure compatibility
 //! with the Ookla Speedtest CLI installed in the Docker container.
-use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::env;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Reasons configuration fails to load.
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Failed to read config file {}: {source}", path.display())]
+    ReadFile {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("Invalid config file {}: {message}", path.display())]
+    InvalidFile { path: PathBuf, message: String },
+    /// A variable whose value cannot be parsed (not a number, unknown option...).
+    #[error("Invalid {field}: {message}")]
+    ParseError {
+        field: &'static str,
+        message: String,
+    },
+    #[error("Invalid schedule mode: {0}")]
+    InvalidScheduleMode(String),
+    #[error("Invalid timezone: {0}")]
+    InvalidTimezone(String),
+    /// One of the speedtest timeouts is 0.
+    #[error("{0} must be greater than 0")]
+    ZeroTimeout(&'static str),
+    /// A parsable value outside its allowed range.
+    #[error("{field} must be {expected}")]
+    OutOfRange {
+        field: &'static str,
+        expected: &'static str,
+    },
+    /// A variable set in a configuration it can't work with.
+    #[error("{field} requires {requirement}")]
+    Requires {
+        field: &'static str,
+        requirement: &'static str,
+    },
+    /// Schedule variables that have no effect in the schedule mode, with
+    /// `NETSPEED_STRICT_CONFIG` enabled.
+    #[error("{} set but ignored in {mode:?} schedule mode", vars.join(", "))]
+    IgnoredScheduleVars {
+        vars: Vec<&'static str>,
+        mode: ScheduleMode,
+    },
+}
+
+impl ConfigError {
+    /// Returns a `map_err` adapter turning a parse error of `field` into a `ParseError`.
+    fn parse<E: std::fmt::Display>(field: &'static str) -> impl FnOnce(E) -> Self {
+        move |e| ConfigError::ParseError {
+            field,
+            message: e.to_string(),
+        }
+    }
+
+    fn out_of_range(field: &'static str, expected: &'static str) -> Self {
+        ConfigError::OutOfRange { field, expected }
+    }
+
+    fn requires(field: &'static str, requirement: &'static str) -> Self {
+        ConfigError::Requires { field, requirement }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -20,12 +86,41 @@ pub struct Config {
     pub speedtest: SpeedtestConfig,
     pub ntfy: Option<NtfyConfig>,
     pub notify_on: NotifyOn,
+    pub notify_success_streak: u64,
+    pub server_failover_threshold: u64,
+    pub single_connection: bool,
+    pub failure_verbose: bool,
+    pub notify_on_start: bool,
     pub resource_interval_seconds: u64,
+    pub cpu_smoothing_alpha: f64,
+    pub notify_on_high_cpu: Option<f64>,
+    pub notify_on_high_memory: Option<u64>,
+    pub high_usage_samples: u32,
+    pub high_usage_cooldown_seconds: u64,
+    pub suppress_initial_gauges: bool,
+    pub expose_external_ip: bool,
+    pub expose_interface: bool,
+    pub success_criterion_cmd: Option<String>,
+    pub source_label: Option<String>,
+    pub textfile_path: Option<PathBuf>,
+    pub db_path: Option<PathBuf>,
+    pub health_webhook_url: Option<String>,
+    pub max_jitter_ms: Option<f64>,
+    pub anomaly_sigma: f64,
+    pub anomaly_notify: bool,
+    pub debug_capture_count: usize,
 }
 
 #[derive(Debug, Clone)]
 pub struct ServerConfig {
     pub bind_address: String,
+    pub init_grace_seconds: u64,
+    pub bind_retries: u32,
+    pub max_rss_bytes: Option<u64>,
+    pub json_pretty: bool,
+    pub metrics_token: Option<String>,
+    pub admin_token: Option<String>,
+    pub stale_after_seconds: u64,
 }
 
 #[derive(Debug, Clone)]
@@ -35,13 +130,21 @@ pub struct ScheduleConfig {
     pub cron_expression: Option<String>,
     pub timezone: String,
     pub allow_overlap: bool,
+    pub max_concurrent_runs: usize,
+    pub min_gap_seconds: u64,
+    pub jitter_seconds: u64,
+    pub adaptive_min_seconds: u64,
+    pub adaptive_max_seconds: u64,
+    pub adaptive_tolerance: f64,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ScheduleMode {
     HourlyAligned,
     Interval,
     Cron,
+    Adaptive,
 }
 
 #[derive(Debug, Clone)]
@@ -49,6 +152,37 @@ pub struct SpeedtestConfig {
     pub command: String,
     pub args: Vec<String>,
     pub timeout_seconds: u64,
+    pub first_output_timeout_seconds: Option<u64>,
+    pub selection_timeout_seconds: Option<u64>,
+    /// Attempts per run; failures worth retrying are retried until this many
+    /// attempts have been made.
+    pub max_attempts: u32,
+    pub allow_partial: bool,
+    pub parse_last_line: bool,
+    pub max_latency_ms: Option<f64>,
+    pub output_stream: OutputStream,
+    pub output_format: OutputFormat,
+}
+
+/// Which speed test CLI produces the JSON result.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OutputFormat {
+    /// Ookla Speedtest CLI (`speedtest --format=json`).
+    #[default]
+    Ookla,
+    /// librespeed-cli (`librespeed-cli --json`).
+    Librespeed,
+}
+
+/// Which of the speedtest command's output streams carries the JSON result.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OutputStream {
+    #[default]
+    Stdout,
+    /// For wrappers that print the result on stderr.
+    Stderr,
+    /// Parse stdout, falling back to stderr if that fails.
+    Both,
 }
 
 #[derive(Debug, Clone)]
@@ -59,6 +193,24 @@ pub struct NtfyConfig {
     pub tags: String,
     pub priority: u8,
     pub click_url: Option<String>,
+    pub markdown: bool,
+    pub speed_unit: SpeedUnit,
+    pub quality_tags: bool,
+    /// Retries after a failed send (network error or 5xx), with exponential backoff.
+    pub max_retries: u32,
+    /// Longest message body sent, in characters; longer messages are truncated
+    /// with an ellipsis.
+    pub max_chars: Option<usize>,
+}
+
+/// Unit used to display download/upload speeds in notifications.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SpeedUnit {
+    /// Megabits per second (bps / 1e6).
+    #[default]
+    Mbps,
+    /// Megabytes per second (bps / 8e6), as some ISPs advertise.
+    MBytePerSec,
 }
 
 #[derive(Debug, Clone)]
@@ -67,34 +219,99 @@ pub struct NotifyOn {
     pub failure: bool,
 }
 
+impl NotifyOn {
+    /// Never notifies; used when no notification channel is configured.
+    pub fn none() -> Self {
+        Self {
+            success: false,
+            failure: false,
+        }
+    }
+}
+
 impl Config {
     /// Loads configuration from environment variables.
     ///
+    /// When `NETSPEED_CONFIG_FILE` is set, the file it names is loaded first and
+    /// environment variables override its values (see `from_file`).
+    ///
     /// # Environment Variables
     ///
+    /// - `NETSPEED_CONFIG_FILE`: TOML file to load before applying environment variables (optional)
     /// - `NETSPEED_BIND`: Server bind address (default: "0.0.0.0:9109")
-    /// - `NETSPEED_SCHEDULE_MODE`: Schedule mode - "hourly_aligned", "interval", or "cron" (default: "hourly_aligned")
-    /// - `NETSPEED_INTERVAL_SECONDS`: Interval between runs in seconds (default: 3600)
+    /// - `NETSPEED_BIND_RETRIES`: Extra attempts to bind the server address before giving up (default: 5)
+    /// - `NETSPEED_MAX_RSS_BYTES`: Report `/healthz` as degraded when process memory exceeds this (optional)
+    /// - `NETSPEED_JSON_PRETTY`: Pretty-print JSON responses by default (default: false)
+    /// - `NETSPEED_METRICS_TOKEN`: Require an `Authorization: Bearer <value>` header or `?token=<value>` to scrape metrics (optional)
+    /// - `NETSPEED_ADMIN_TOKEN`: Bearer token enabling the `/admin/*` and `/debug/*` endpoints (optional; disabled when unset)
+    /// - `NETSPEED_STALE_AFTER_SECONDS`: Age after which `/healthz` flags the last run as `stale` (default: 7200)
+    /// - `NETSPEED_INIT_GRACE_SECONDS`: Startup grace period during which `/healthz` reports "initializing" with 200 (default: 0)
+    /// - `NETSPEED_SCHEDULE_MODE`: Schedule mode - "hourly_aligned", "interval", "cron", or "adaptive" (default: "hourly_aligned")
+    /// - `NETSPEED_INTERVAL_SECONDS`: Interval between runs in seconds (at least 1 in interval and adaptive modes), and the starting interval in adaptive mode (default: 3600)
+    /// - `NETSPEED_ADAPTIVE_MIN_SECONDS`: Shortest interval in adaptive mode (default: 900)
+    /// - `NETSPEED_ADAPTIVE_MAX_SECONDS`: Longest interval in adaptive mode (default: 14400)
+    /// - `NETSPEED_ADAPTIVE_TOLERANCE`: Relative download change still considered stable in adaptive mode (default: 0.1)
     /// - `NETSPEED_SCHEDULE`: Cron expression for cron mode
-    /// - `NETSPEED_TIMEZONE`: Timezone for scheduling (default: "Europe/Brussels")
+    /// - `NETSPEED_TIMEZONE`: Timezone for scheduling (default: system timezone, or "UTC" if undetectable)
     /// - `NETSPEED_ALLOW_OVERLAP`: Allow overlapping test runs (default: false)
+    /// - `NETSPEED_MAX_CONCURRENT_RUNS`: Maximum runs in flight at once when overlap is allowed (default: 1)
+    /// - `NETSPEED_MIN_GAP_SECONDS`: Minimum gap between the end of one run and the start of the next (default: 0)
+    /// - `NETSPEED_SCHEDULE_JITTER_SECONDS`: Random delay of up to this many seconds added to each interval run, to spread out instances started together (default: 0)
+    /// - `NETSPEED_STRICT_CONFIG`: Fail instead of warning when schedule variables are set that the chosen mode ignores (default: false)
+    /// - `NETSPEED_OOKLA_PROGRESS`: Set to false to pass `--progress=no` to the Ookla CLI (default: true)
+    /// - `NETSPEED_SERVER_ID`: Pin the Ookla server by passing `--server-id` (optional)
+    /// - `NETSPEED_SINGLE_CONNECTION`: Set to true to pass `--single` (one connection per direction) to the Ookla CLI (default: false)
+    /// - `NETSPEED_SERVER_FAILOVER_THRESHOLD`: Consecutive failures after which a pinned server is dropped until the next success, 0 disables (default: 0)
     /// - `NETSPEED_TIMEOUT_SECONDS`: Speedtest command timeout (default: 120)
+    /// - `NETSPEED_FIRST_OUTPUT_TIMEOUT_SECONDS`: Deadline for the command's first output byte on the `NETSPEED_OUTPUT_STREAM` stream (optional)
+    /// - `NETSPEED_SELECTION_TIMEOUT_SECONDS`: Deadline for the CLI to finish selecting a server; passes `--progress=yes` and parses the last output line (optional)
+    /// - `NETSPEED_MAX_ATTEMPTS`: Attempts per run, retrying timeouts, command failures and internal errors (default: 1)
+    /// - `NETSPEED_OUTPUT_FORMAT`: Speed test CLI to run and parse - "ookla" or "librespeed" (default: "ookla")
+    /// - `NETSPEED_OUTPUT_STREAM`: Stream holding the JSON result - "stdout", "stderr" or "both" (default: "stdout")
+    /// - `NETSPEED_ALLOW_PARTIAL`: Accept results missing one direction (default: false)
+    /// - `NETSPEED_PARSE_LAST_LINE`: Parse the last output line when progress lines precede the result (default: false)
+    /// - `NETSPEED_MAX_LATENCY_MS`: Reject results with a higher latency as broken measurements (optional)
     /// - `NETSPEED_NTFY_URL`: ntfy.sh notification URL (optional)
     /// - `NETSPEED_NTFY_TOKEN`: ntfy.sh authentication token (optional)
     /// - `NETSPEED_NTFY_TITLE`: Notification title (default: "netspeed-lite")
     /// - `NETSPEED_NTFY_TAGS`: Notification tags (default: "speedtest,isp")
     /// - `NETSPEED_NTFY_PRIORITY`: Notification priority 1-5 (default: 3)
     /// - `NETSPEED_NTFY_CLICK`: Click URL for notifications (optional)
+    /// - `NETSPEED_NTFY_MARKDOWN`: Send Markdown-formatted messages (default: false)
+    /// - `NETSPEED_NTFY_QUALITY_TAGS`: Append a `green`/`yellow`/`red` result quality tag to run notifications (default: false)
+    /// - `NETSPEED_NTFY_MAX_RETRIES`: Retries of a notification failing with a network error or 5xx, 1s apart and doubling (default: 3)
+    /// - `NETSPEED_NOTIFY_MAX_CHARS`: Truncate notification messages longer than this many characters, ending them with an ellipsis (optional)
+    /// - `NETSPEED_SPEED_UNIT`: Speed unit in notifications - "mbps" or "mbyteps" (default: "mbps")
     /// - `NETSPEED_NOTIFY_ON`: When to notify - "success", "failure", or "success,failure" (default: "success,failure")
+    /// - `NETSPEED_FAILURE_VERBOSE`: Include the full error, command and timestamp in failure notifications (default: false)
+    /// - `NETSPEED_NOTIFY_ON_START`: Send a notification with the version when the process starts (default: false)
+    /// - `NETSPEED_HEALTH_WEBHOOK_URL`: URL receiving a JSON POST on every health state transition (optional)
+    /// - `NETSPEED_NOTIFY_SUCCESS_STREAK`: Notify after every N consecutive successful runs, 0 disables (default: 0)
     /// - `NETSPEED_RESOURCE_INTERVAL_SECONDS`: Resource monitoring interval (default: 15)
+    /// - `NETSPEED_CPU_SMOOTHING_ALPHA`: Exponential smoothing factor for `netspeed_process_cpu_usage`, in (0, 1]; 1 disables smoothing (default: 1)
+    /// - `NETSPEED_NOTIFY_ON_HIGH_CPU`: Notify when process CPU usage stays above this percentage (optional)
+    /// - `NETSPEED_NOTIFY_ON_HIGH_MEMORY`: Notify when process memory stays above this many bytes (optional)
+    /// - `NETSPEED_HIGH_USAGE_SAMPLES`: Consecutive resource samples above a threshold before notifying (default: 3)
+    /// - `NETSPEED_HIGH_USAGE_COOLDOWN_SECONDS`: Minimum time between high usage notifications for the same resource (default: 3600)
+    /// - `NETSPEED_SUCCESS_CRITERION_CMD`: Shell command given each parsed result as JSON on stdin; a nonzero exit fails the run (optional)
+    /// - `NETSPEED_SUPPRESS_INITIAL_GAUGES`: Hide measurement gauges until the first successful run (default: false)
+    /// - `NETSPEED_MAX_JITTER_MS`: Flag runs with higher jitter via `netspeed_jitter_exceeded` and a `degraded` `/healthz` (optional)
+    /// - `NETSPEED_EXPOSE_EXTERNAL_IP`: Export the external IP as `netspeed_external_ip_info` (default: false)
+    /// - `NETSPEED_EXPOSE_INTERFACE`: Export the network interface name as `netspeed_interface_info`; the MAC address is never exported (default: false)
+    /// - `NETSPEED_SOURCE_LABEL`: Constant `source` label added to every metric, for grouping in federated setups (optional)
+    /// - `NETSPEED_TEXTFILE_PATH`: Write the metrics to this file after each run, for node_exporter's textfile collector (optional)
+    /// - `NETSPEED_DB_PATH`: Record every run in this SQLite database, served by `/history`; needs the `history` feature (optional)
+    /// - `NETSPEED_ANOMALY_SIGMA`: Flag results this many standard deviations from the moving mean via `netspeed_anomaly`, 0 disables (default: 3)
+    /// - `NETSPEED_ANOMALY_NOTIFY`: Send a notification when a result is flagged as anomalous (default: false)
+    /// - `NETSPEED_DEBUG_CAPTURE_COUNT`: Keep the raw output of this many recent runs for `/debug/outputs` and `/debug/last-output`, 0 disables (default: 0)
     ///
     /// # Returns
     ///
-    /// Returns `Ok(Config)` if all required variables are valid, or `Err` if:
-    /// - Timezone is invalid
-    /// - Timeout is 0
-    /// - Schedule mode is invalid
-    /// - Any numeric value cannot be parsed
+    /// Returns `Ok(Config)` if all required variables are valid, or a `ConfigError` if:
+    /// - Timezone is invalid (`InvalidTimezone`)
+    /// - A timeout is 0 (`ZeroTimeout`)
+    /// - Schedule mode is invalid (`InvalidScheduleMode`)
+    /// - Any value cannot be parsed (`ParseError`) or is out of range (`OutOfRange`)
     ///
     /// # Examples
     ///
@@ -104,100 +321,866 @@ impl Config {
     /// let config = Config::from_env().expect("Failed to load config");
     /// println!("Bind address: {}", config.server.bind_address);
     /// ```
-    pub fn from_env() -> Result<Self> {
-        let bind_address = env::var("NETSPEED_BIND").unwrap_or_else(|_| "0.0.0.0:9109".to_string());
+    pub fn from_env() -> Result<Self, ConfigError> {
+        match env::var_os("NETSPEED_CONFIG_FILE") {
+            Some(path) => Self::from_file(Path::new(&path)),
+            None => Self::load(&ConfigSource::default()),
+        }
+    }
+
+    /// Loads configuration from a TOML file, with environment variables
+    /// overriding values from the file.
+    ///
+    /// File keys are the environment variable names without the `NETSPEED_`
+    /// prefix, in lowercase. Keys inside a table are prefixed with the table
+    /// name, so `[ntfy]` followed by `url = "..."` sets `NETSPEED_NTFY_URL`.
+    /// Values go through exactly the same parsing and validation as
+    /// environment variables.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the TOML configuration file
+    ///
+    /// # Returns
+    ///
+    /// Returns `ConfigError::ReadFile` if the file cannot be read,
+    /// `ConfigError::InvalidFile` if it is not valid TOML, or any of the errors
+    /// `from_env` returns.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use netspeed_lite::config::Config;
+    /// use std::path::Path;
+    ///
+    /// let config = Config::from_file(Path::new("/etc/netspeed-lite.toml")).unwrap();
+    /// println!("Schedule mode: {:?}", config.schedule.mode);
+    /// ```
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let content = std::fs::read_to_string(path).map_err(|source| ConfigError::ReadFile {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let file = parse_config_file(&content).map_err(|message| ConfigError::InvalidFile {
+            path: path.to_path_buf(),
+            message,
+        })?;
 
-        let schedule_mode = match env::var("NETSPEED_SCHEDULE_MODE")
+        Self::load(&ConfigSource { file })
+    }
+
+    fn load(source: &ConfigSource) -> Result<Self, ConfigError> {
+        let bind_address = source
+            .var("NETSPEED_BIND")
+            .unwrap_or_else(|_| "0.0.0.0:9109".to_string());
+
+        let bind_retries = source
+            .var("NETSPEED_BIND_RETRIES")
+            .unwrap_or_else(|_| "5".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_BIND_RETRIES"))?;
+
+        let init_grace_seconds = source
+            .var("NETSPEED_INIT_GRACE_SECONDS")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_INIT_GRACE_SECONDS"))?;
+
+        let stale_after_seconds = source
+            .var("NETSPEED_STALE_AFTER_SECONDS")
+            .unwrap_or_else(|_| "7200".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_STALE_AFTER_SECONDS"))?;
+
+        let max_rss_bytes = source
+            .var("NETSPEED_MAX_RSS_BYTES")
+            .ok()
+            .map(|v| v.parse())
+            .transpose()
+            .map_err(ConfigError::parse("NETSPEED_MAX_RSS_BYTES"))?;
+
+        let metrics_token = source
+            .var("NETSPEED_METRICS_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty());
+
+        let admin_token = source
+            .var("NETSPEED_ADMIN_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty());
+
+        let json_pretty = source
+            .var("NETSPEED_JSON_PRETTY")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_JSON_PRETTY"))?;
+
+        let schedule_mode = match source
+            .var("NETSPEED_SCHEDULE_MODE")
             .unwrap_or_else(|_| "hourly_aligned".to_string())
             .as_str()
         {
             "hourly_aligned" => ScheduleMode::HourlyAligned,
             "interval" => ScheduleMode::Interval,
             "cron" => ScheduleMode::Cron,
-            other => anyhow::bail!("Invalid schedule mode: {}", other),
+            "adaptive" => ScheduleMode::Adaptive,
+            other => return Err(ConfigError::InvalidScheduleMode(other.to_string())),
         };
 
-        let interval_seconds = env::var("NETSPEED_INTERVAL_SECONDS")
+        let interval_seconds = source
+            .var("NETSPEED_INTERVAL_SECONDS")
             .unwrap_or_else(|_| "3600".to_string())
             .parse()
-            .context("Invalid NETSPEED_INTERVAL_SECONDS")?;
+            .map_err(ConfigError::parse("NETSPEED_INTERVAL_SECONDS"))?;
 
-        let cron_expression = env::var("NETSPEED_SCHEDULE").ok();
+        // Other modes ignore the interval, as `validate_schedule` does
+        let uses_interval = matches!(
+            schedule_mode,
+            ScheduleMode::Interval | ScheduleMode::Adaptive
+        );
+        if uses_interval && interval_seconds == 0 {
+            return Err(ConfigError::out_of_range(
+                "NETSPEED_INTERVAL_SECONDS",
+                "at least 1",
+            ));
+        }
 
-        let timezone =
-            env::var("NETSPEED_TIMEZONE").unwrap_or_else(|_| "Europe/Brussels".to_string());
+        let cron_expression = source.var("NETSPEED_SCHEDULE").ok();
+
+        let timezone = source.var("NETSPEED_TIMEZONE").unwrap_or_else(|_| {
+            detect_system_timezone(Path::new("/etc/localtime")).unwrap_or_else(|| "UTC".to_string())
+        });
 
         // Validate timezone
-        timezone
-            .parse::<chrono_tz::Tz>()
-            .with_context(|| format!("Invalid timezone: {}", timezone))?;
+        if timezone.parse::<chrono_tz::Tz>().is_err() {
+            return Err(ConfigError::InvalidTimezone(timezone));
+        }
 
-        let allow_overlap = env::var("NETSPEED_ALLOW_OVERLAP")
+        let allow_overlap = source
+            .var("NETSPEED_ALLOW_OVERLAP")
             .unwrap_or_else(|_| "false".to_string())
             .parse()
-            .context("Invalid NETSPEED_ALLOW_OVERLAP")?;
+            .map_err(ConfigError::parse("NETSPEED_ALLOW_OVERLAP"))?;
+
+        let max_concurrent_runs = source
+            .var("NETSPEED_MAX_CONCURRENT_RUNS")
+            .unwrap_or_else(|_| "1".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_MAX_CONCURRENT_RUNS"))?;
+
+        if max_concurrent_runs == 0 {
+            return Err(ConfigError::out_of_range(
+                "NETSPEED_MAX_CONCURRENT_RUNS",
+                "at least 1",
+            ));
+        }
+
+        let min_gap_seconds = source
+            .var("NETSPEED_MIN_GAP_SECONDS")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_MIN_GAP_SECONDS"))?;
+
+        let jitter_seconds = source
+            .var("NETSPEED_SCHEDULE_JITTER_SECONDS")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_SCHEDULE_JITTER_SECONDS"))?;
+
+        let adaptive_min_seconds = source
+            .var("NETSPEED_ADAPTIVE_MIN_SECONDS")
+            .unwrap_or_else(|_| "900".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_ADAPTIVE_MIN_SECONDS"))?;
+
+        let adaptive_max_seconds = source
+            .var("NETSPEED_ADAPTIVE_MAX_SECONDS")
+            .unwrap_or_else(|_| "14400".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_ADAPTIVE_MAX_SECONDS"))?;
+
+        if adaptive_min_seconds == 0 || adaptive_min_seconds > adaptive_max_seconds {
+            return Err(ConfigError::out_of_range(
+                "NETSPEED_ADAPTIVE_MIN_SECONDS",
+                "greater than 0 and at most NETSPEED_ADAPTIVE_MAX_SECONDS",
+            ));
+        }
+
+        let adaptive_tolerance: f64 = source
+            .var("NETSPEED_ADAPTIVE_TOLERANCE")
+            .unwrap_or_else(|_| "0.1".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_ADAPTIVE_TOLERANCE"))?;
+
+        if !(0.0..=1.0).contains(&adaptive_tolerance) {
+            return Err(ConfigError::out_of_range(
+                "NETSPEED_ADAPTIVE_TOLERANCE",
+                "between 0 and 1",
+            ));
+        }
+
+        let strict_config: bool = source
+            .var("NETSPEED_STRICT_CONFIG")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_STRICT_CONFIG"))?;
+
+        let ignored = ignored_schedule_vars_in(&schedule_mode, |var| source.is_set(var));
+        if !ignored.is_empty() {
+            if strict_config {
+                return Err(ConfigError::IgnoredScheduleVars {
+                    vars: ignored,
+                    mode: schedule_mode,
+                });
+            }
+            tracing::warn!(
+                "{} set but ignored in {:?} schedule mode",
+                ignored.join(", "),
+                schedule_mode
+            );
+        }
+
+        let output_format = match source
+            .var("NETSPEED_OUTPUT_FORMAT")
+            .unwrap_or_else(|_| "ookla".to_string())
+            .as_str()
+        {
+            "ookla" => OutputFormat::Ookla,
+            "librespeed" => OutputFormat::Librespeed,
+            other => return Err(ConfigError::parse("NETSPEED_OUTPUT_FORMAT")(other)),
+        };
 
         // Hardcoded Ookla Speedtest configuration
         let command = "speedtest".to_string();
 
-        let args = vec![
+        let mut args = vec![
             "--format=json".to_string(),
             "--accept-license".to_string(),
             "--accept-gdpr".to_string(),
         ];
 
-        let timeout_seconds = env::var("NETSPEED_TIMEOUT_SECONDS")
+        let ookla_progress: bool = source
+            .var("NETSPEED_OOKLA_PROGRESS")
+            .unwrap_or_else(|_| "true".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_OOKLA_PROGRESS"))?;
+        if !ookla_progress {
+            args.push("--progress=no".to_string());
+        }
+
+        let single_connection = source
+            .var("NETSPEED_SINGLE_CONNECTION")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_SINGLE_CONNECTION"))?;
+        if single_connection {
+            args.push("--single".to_string());
+        }
+
+        let server_id: Option<u32> = source
+            .var("NETSPEED_SERVER_ID")
+            .ok()
+            .map(|v| v.parse())
+            .transpose()
+            .map_err(ConfigError::parse("NETSPEED_SERVER_ID"))?;
+        if let Some(server_id) = server_id {
+            args.push(format!("--server-id={}", server_id));
+        }
+
+        let server_failover_threshold = source
+            .var("NETSPEED_SERVER_FAILOVER_THRESHOLD")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_SERVER_FAILOVER_THRESHOLD"))?;
+
+        let selection_timeout_seconds = source
+            .var("NETSPEED_SELECTION_TIMEOUT_SECONDS")
+            .ok()
+            .map(|v| v.parse())
+            .transpose()
+            .map_err(ConfigError::parse("NETSPEED_SELECTION_TIMEOUT_SECONDS"))?;
+
+        if selection_timeout_seconds == Some(0) {
+            return Err(ConfigError::ZeroTimeout(
+                "NETSPEED_SELECTION_TIMEOUT_SECONDS",
+            ));
+        }
+
+        // Server selection is only visible in the CLI's progress stream, which
+        // it doesn't print when not attached to a terminal unless asked to
+        if selection_timeout_seconds.is_some() {
+            if output_format == OutputFormat::Librespeed {
+                return Err(ConfigError::requires(
+                    "NETSPEED_SELECTION_TIMEOUT_SECONDS",
+                    "NETSPEED_OUTPUT_FORMAT=ookla",
+                ));
+            }
+            if !ookla_progress {
+                return Err(ConfigError::requires(
+                    "NETSPEED_SELECTION_TIMEOUT_SECONDS",
+                    "NETSPEED_OOKLA_PROGRESS=true",
+                ));
+            }
+            args.push("--progress=yes".to_string());
+        }
+
+        // The Ookla flags above don't apply to librespeed-cli
+        let (command, args) = match output_format {
+            OutputFormat::Ookla => (command, normalize_ookla_args(args)),
+            OutputFormat::Librespeed => ("librespeed-cli".to_string(), vec!["--json".to_string()]),
+        };
+
+        let timeout_seconds = source
+            .var("NETSPEED_TIMEOUT_SECONDS")
             .unwrap_or_else(|_| "120".to_string())
             .parse()
-            .context("Invalid NETSPEED_TIMEOUT_SECONDS")?;
+            .map_err(ConfigError::parse("NETSPEED_TIMEOUT_SECONDS"))?;
 
         if timeout_seconds == 0 {
-            anyhow::bail!("NETSPEED_TIMEOUT_SECONDS must be greater than 0");
+            return Err(ConfigError::ZeroTimeout("NETSPEED_TIMEOUT_SECONDS"));
         }
 
-        let ntfy_url = env::var("NETSPEED_NTFY_URL").ok();
+        let first_output_timeout_seconds = source
+            .var("NETSPEED_FIRST_OUTPUT_TIMEOUT_SECONDS")
+            .ok()
+            .map(|v| v.parse())
+            .transpose()
+            .map_err(ConfigError::parse("NETSPEED_FIRST_OUTPUT_TIMEOUT_SECONDS"))?;
+
+        if first_output_timeout_seconds == Some(0) {
+            return Err(ConfigError::ZeroTimeout(
+                "NETSPEED_FIRST_OUTPUT_TIMEOUT_SECONDS",
+            ));
+        }
+
+        let max_attempts = source
+            .var("NETSPEED_MAX_ATTEMPTS")
+            .unwrap_or_else(|_| "1".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_MAX_ATTEMPTS"))?;
+
+        if max_attempts == 0 {
+            return Err(ConfigError::out_of_range(
+                "NETSPEED_MAX_ATTEMPTS",
+                "at least 1",
+            ));
+        }
+
+        let output_stream = match source
+            .var("NETSPEED_OUTPUT_STREAM")
+            .unwrap_or_else(|_| "stdout".to_string())
+            .as_str()
+        {
+            "stdout" => OutputStream::Stdout,
+            "stderr" => OutputStream::Stderr,
+            "both" => OutputStream::Both,
+            other => return Err(ConfigError::parse("NETSPEED_OUTPUT_STREAM")(other)),
+        };
+
+        let allow_partial = source
+            .var("NETSPEED_ALLOW_PARTIAL")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_ALLOW_PARTIAL"))?;
+
+        let parse_last_line: bool = source
+            .var("NETSPEED_PARSE_LAST_LINE")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_PARSE_LAST_LINE"))?;
+        // The progress stream precedes the result, which is on the last line
+        let parse_last_line = parse_last_line || selection_timeout_seconds.is_some();
+
+        let max_latency_ms: Option<f64> = source
+            .var("NETSPEED_MAX_LATENCY_MS")
+            .ok()
+            .map(|v| v.parse())
+            .transpose()
+            .map_err(ConfigError::parse("NETSPEED_MAX_LATENCY_MS"))?;
+
+        if max_latency_ms.is_some_and(|max| max <= 0.0 || max.is_nan()) {
+            return Err(ConfigError::out_of_range(
+                "NETSPEED_MAX_LATENCY_MS",
+                "greater than 0",
+            ));
+        }
+
+        let ntfy_markdown = source
+            .var("NETSPEED_NTFY_MARKDOWN")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_NTFY_MARKDOWN"))?;
+
+        let ntfy_quality_tags = source
+            .var("NETSPEED_NTFY_QUALITY_TAGS")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_NTFY_QUALITY_TAGS"))?;
+
+        let ntfy_max_retries = source
+            .var("NETSPEED_NTFY_MAX_RETRIES")
+            .unwrap_or_else(|_| "3".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_NTFY_MAX_RETRIES"))?;
+
+        let notify_max_chars: Option<usize> = source
+            .var("NETSPEED_NOTIFY_MAX_CHARS")
+            .ok()
+            .map(|v| v.parse())
+            .transpose()
+            .map_err(ConfigError::parse("NETSPEED_NOTIFY_MAX_CHARS"))?;
+
+        if notify_max_chars == Some(0) {
+            return Err(ConfigError::out_of_range(
+                "NETSPEED_NOTIFY_MAX_CHARS",
+                "at least 1",
+            ));
+        }
+
+        let speed_unit = match source
+            .var("NETSPEED_SPEED_UNIT")
+            .unwrap_or_else(|_| "mbps".to_string())
+            .as_str()
+        {
+            "mbps" => SpeedUnit::Mbps,
+            "mbyteps" => SpeedUnit::MBytePerSec,
+            other => return Err(ConfigError::parse("NETSPEED_SPEED_UNIT")(other)),
+        };
+
+        let ntfy_url = source.var("NETSPEED_NTFY_URL").ok();
         let ntfy = ntfy_url.map(|url| NtfyConfig {
             url,
-            token: env::var("NETSPEED_NTFY_TOKEN").ok(),
-            title: env::var("NETSPEED_NTFY_TITLE").unwrap_or_else(|_| "netspeed-lite".to_string()),
-            tags: env::var("NETSPEED_NTFY_TAGS").unwrap_or_else(|_| "speedtest,isp".to_string()),
-            priority: env::var("NETSPEED_NTFY_PRIORITY")
+            token: source.var("NETSPEED_NTFY_TOKEN").ok(),
+            title: source
+                .var("NETSPEED_NTFY_TITLE")
+                .unwrap_or_else(|_| "netspeed-lite".to_string()),
+            tags: source
+                .var("NETSPEED_NTFY_TAGS")
+                .unwrap_or_else(|_| "speedtest,isp".to_string()),
+            priority: source
+                .var("NETSPEED_NTFY_PRIORITY")
                 .unwrap_or_else(|_| "3".to_string())
                 .parse()
                 .unwrap_or(3)
                 .clamp(1, 5),
-            click_url: env::var("NETSPEED_NTFY_CLICK").ok(),
+            click_url: source.var("NETSPEED_NTFY_CLICK").ok(),
+            markdown: ntfy_markdown,
+            speed_unit,
+            quality_tags: ntfy_quality_tags,
+            max_retries: ntfy_max_retries,
+            max_chars: notify_max_chars,
         });
 
-        let notify_on_str =
-            env::var("NETSPEED_NOTIFY_ON").unwrap_or_else(|_| "success,failure".to_string());
-        let notify_on = NotifyOn {
-            success: notify_on_str.contains("success"),
-            failure: notify_on_str.contains("failure"),
+        // Without a channel nothing is ever sent, so report that instead of the
+        // requested events
+        let notify_on_var = source.var("NETSPEED_NOTIFY_ON").ok();
+        let notify_on = if ntfy.is_none() {
+            if notify_on_var.is_some() {
+                tracing::warn!(
+                    "NETSPEED_NOTIFY_ON set: notifications requested but no channel configured"
+                );
+            }
+            NotifyOn::none()
+        } else {
+            let notify_on_str = notify_on_var.unwrap_or_else(|| "success,failure".to_string());
+            NotifyOn {
+                success: notify_on_str.contains("success"),
+                failure: notify_on_str.contains("failure"),
+            }
         };
 
-        let resource_interval_seconds = env::var("NETSPEED_RESOURCE_INTERVAL_SECONDS")
+        let failure_verbose = source
+            .var("NETSPEED_FAILURE_VERBOSE")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_FAILURE_VERBOSE"))?;
+
+        let notify_on_start = source
+            .var("NETSPEED_NOTIFY_ON_START")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_NOTIFY_ON_START"))?;
+
+        let notify_success_streak = source
+            .var("NETSPEED_NOTIFY_SUCCESS_STREAK")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_NOTIFY_SUCCESS_STREAK"))?;
+
+        let resource_interval_seconds = source
+            .var("NETSPEED_RESOURCE_INTERVAL_SECONDS")
             .unwrap_or_else(|_| "15".to_string())
             .parse()
-            .context("Invalid NETSPEED_RESOURCE_INTERVAL_SECONDS")?;
+            .map_err(ConfigError::parse("NETSPEED_RESOURCE_INTERVAL_SECONDS"))?;
+
+        let cpu_smoothing_alpha: f64 = source
+            .var("NETSPEED_CPU_SMOOTHING_ALPHA")
+            .unwrap_or_else(|_| "1".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_CPU_SMOOTHING_ALPHA"))?;
+
+        if !(cpu_smoothing_alpha > 0.0 && cpu_smoothing_alpha <= 1.0) {
+            return Err(ConfigError::out_of_range(
+                "NETSPEED_CPU_SMOOTHING_ALPHA",
+                "greater than 0 and at most 1",
+            ));
+        }
+
+        let notify_on_high_cpu: Option<f64> = source
+            .var("NETSPEED_NOTIFY_ON_HIGH_CPU")
+            .ok()
+            .map(|v| v.parse())
+            .transpose()
+            .map_err(ConfigError::parse("NETSPEED_NOTIFY_ON_HIGH_CPU"))?;
+
+        let notify_on_high_memory = source
+            .var("NETSPEED_NOTIFY_ON_HIGH_MEMORY")
+            .ok()
+            .map(|v| v.parse())
+            .transpose()
+            .map_err(ConfigError::parse("NETSPEED_NOTIFY_ON_HIGH_MEMORY"))?;
+
+        let high_usage_samples = source
+            .var("NETSPEED_HIGH_USAGE_SAMPLES")
+            .unwrap_or_else(|_| "3".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_HIGH_USAGE_SAMPLES"))?;
+
+        if high_usage_samples == 0 {
+            return Err(ConfigError::out_of_range(
+                "NETSPEED_HIGH_USAGE_SAMPLES",
+                "at least 1",
+            ));
+        }
+
+        let high_usage_cooldown_seconds = source
+            .var("NETSPEED_HIGH_USAGE_COOLDOWN_SECONDS")
+            .unwrap_or_else(|_| "3600".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_HIGH_USAGE_COOLDOWN_SECONDS"))?;
+
+        let suppress_initial_gauges = source
+            .var("NETSPEED_SUPPRESS_INITIAL_GAUGES")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_SUPPRESS_INITIAL_GAUGES"))?;
+
+        let max_jitter_ms: Option<f64> = source
+            .var("NETSPEED_MAX_JITTER_MS")
+            .ok()
+            .map(|v| v.parse())
+            .transpose()
+            .map_err(ConfigError::parse("NETSPEED_MAX_JITTER_MS"))?;
+
+        if max_jitter_ms.is_some_and(|max| max < 0.0 || max.is_nan()) {
+            return Err(ConfigError::out_of_range(
+                "NETSPEED_MAX_JITTER_MS",
+                "non-negative",
+            ));
+        }
+
+        let anomaly_sigma: f64 = source
+            .var("NETSPEED_ANOMALY_SIGMA")
+            .unwrap_or_else(|_| "3".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_ANOMALY_SIGMA"))?;
+
+        if anomaly_sigma < 0.0 || anomaly_sigma.is_nan() {
+            return Err(ConfigError::out_of_range(
+                "NETSPEED_ANOMALY_SIGMA",
+                "non-negative",
+            ));
+        }
+
+        let anomaly_notify = source
+            .var("NETSPEED_ANOMALY_NOTIFY")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_ANOMALY_NOTIFY"))?;
+
+        let debug_capture_count = source
+            .var("NETSPEED_DEBUG_CAPTURE_COUNT")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_DEBUG_CAPTURE_COUNT"))?;
+
+        let expose_external_ip = source
+            .var("NETSPEED_EXPOSE_EXTERNAL_IP")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_EXPOSE_EXTERNAL_IP"))?;
+
+        let expose_interface = source
+            .var("NETSPEED_EXPOSE_INTERFACE")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .map_err(ConfigError::parse("NETSPEED_EXPOSE_INTERFACE"))?;
+
+        let success_criterion_cmd = source
+            .var("NETSPEED_SUCCESS_CRITERION_CMD")
+            .ok()
+            .filter(|cmd| !cmd.trim().is_empty());
+
+        let source_label = source
+            .var("NETSPEED_SOURCE_LABEL")
+            .ok()
+            .filter(|source| !source.is_empty());
+
+        let health_webhook_url = source
+            .var("NETSPEED_HEALTH_WEBHOOK_URL")
+            .ok()
+            .filter(|url| !url.is_empty());
+
+        let textfile_path = source
+            .var("NETSPEED_TEXTFILE_PATH")
+            .ok()
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from);
+
+        let db_path = source
+            .var("NETSPEED_DB_PATH")
+            .ok()
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from);
 
         Ok(Config {
-            server: ServerConfig { bind_address },
+            server: ServerConfig {
+                bind_address,
+                init_grace_seconds,
+                bind_retries,
+                max_rss_bytes,
+                json_pretty,
+                metrics_token,
+                admin_token,
+                stale_after_seconds,
+            },
             schedule: ScheduleConfig {
                 mode: schedule_mode,
                 interval_seconds,
                 cron_expression,
                 timezone,
                 allow_overlap,
+                max_concurrent_runs,
+                min_gap_seconds,
+                jitter_seconds,
+                adaptive_min_seconds,
+                adaptive_max_seconds,
+                adaptive_tolerance,
             },
             speedtest: SpeedtestConfig {
                 command,
                 args,
                 timeout_seconds,
+                first_output_timeout_seconds,
+                selection_timeout_seconds,
+                max_attempts,
+                allow_partial,
+                parse_last_line,
+                max_latency_ms,
+                output_stream,
+                output_format,
             },
             ntfy,
             notify_on,
+            notify_success_streak,
+            server_failover_threshold,
+            single_connection,
+            failure_verbose,
+            notify_on_start,
             resource_interval_seconds,
+            cpu_smoothing_alpha,
+            notify_on_high_cpu,
+            notify_on_high_memory,
+            high_usage_samples,
+            high_usage_cooldown_seconds,
+            suppress_initial_gauges,
+            expose_external_ip,
+            expose_interface,
+            success_criterion_cmd,
+            source_label,
+            textfile_path,
+            db_path,
+            health_webhook_url,
+            max_jitter_ms,
+            anomaly_sigma,
+            anomaly_notify,
+            debug_capture_count,
         })
     }
 }
+
+/// Where configuration values come from: the environment, falling back to the
+/// config file named by `NETSPEED_CONFIG_FILE`, if any.
+#[derive(Debug, Default)]
+struct ConfigSource {
+    /// Values from the config file, keyed by environment variable name.
+    file: HashMap<String, String>,
+}
+
+impl ConfigSource {
+    /// Looks up a variable, with the same signature as `env::var`.
+    fn var(&self, name: &str) -> Result<String, env::VarError> {
+        match env::var(name) {
+            Err(env::VarError::NotPresent) => self
+                .file
+                .get(name)
+                .cloned()
+                .ok_or(env::VarError::NotPresent),
+            result => result,
+        }
+    }
+
+    fn is_set(&self, name: &str) -> bool {
+        env::var_os(name).is_some() || self.file.contains_key(name)
+    }
+}
+
+/// Parses a TOML config file into values keyed by environment variable name.
+///
+/// `schedule_mode = "cron"` becomes `NETSPEED_SCHEDULE_MODE=cron`; tables
+/// prefix their keys (`[ntfy]` + `url` becomes `NETSPEED_NTFY_URL`) and arrays
+/// are joined with commas.
+fn parse_config_file(content: &str) -> Result<HashMap<String, String>, String> {
+    fn flatten(
+        prefix: &str,
+        table: &toml::Table,
+        values: &mut HashMap<String, String>,
+    ) -> Result<(), String> {
+        for (key, value) in table {
+            let name = format!("{}_{}", prefix, key.to_uppercase());
+            let value = match value {
+                toml::Value::Table(table) => {
+                    flatten(&name, table, values)?;
+                    continue;
+                }
+                toml::Value::String(s) => s.clone(),
+                toml::Value::Array(items) => items
+                    .iter()
+                    .map(|item| match item {
+                        toml::Value::String(s) => Ok(s.clone()),
+                        toml::Value::Table(_) | toml::Value::Array(_) => {
+                            Err(format!("{} must be a list of plain values", name))
+                        }
+                        other => Ok(other.to_string()),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?
+                    .join(","),
+                other => other.to_string(),
+            };
+            values.insert(name, value);
+        }
+        Ok(())
+    }
+
+    let table: toml::Table = content
+        .parse()
+        .map_err(|e: toml::de::Error| e.to_string())?;
+    let mut values = HashMap::new();
+    flatten("NETSPEED", &table, &mut values)?;
+    Ok(values)
+}
+
+/// Detects the system timezone from a `localtime` symlink.
+///
+/// Most Linux distributions make `/etc/localtime` a symlink into the zoneinfo
+/// database (e.g. `/usr/share/zoneinfo/America/New_York`); the IANA name is the
+/// part of the target after `zoneinfo/`.
+///
+/// # Arguments
+///
+/// * `localtime` - Path to the `localtime` symlink, normally `/etc/localtime`
+///
+/// # Returns
+///
+/// Returns `Some(name)` if the path is a symlink into a zoneinfo directory and
+/// names a known timezone, or `None` otherwise (missing file, regular file copy,
+/// unrecognised zone).
+///
+/// # Examples
+///
+/// ```no_run
+/// use netspeed_lite::config::detect_system_timezone;
+/// use std::path::Path;
+///
+/// let tz = detect_system_timezone(Path::new("/etc/localtime")).unwrap_or_else(|| "UTC".to_string());
+/// ```
+pub fn detect_system_timezone(localtime: &Path) -> Option<String> {
+    let target = std::fs::read_link(localtime).ok()?;
+    let target = target.to_str()?;
+    let (_, name) = target.rsplit_once("zoneinfo/")?;
+
+    name.parse::<chrono_tz::Tz>().ok()?;
+    Some(name.to_string())
+}
+
+/// Returns the schedule environment variables that are set but have no effect
+/// in the given schedule mode, e.g. `NETSPEED_INTERVAL_SECONDS` in cron mode.
+///
+/// `Config::from_env` warns about these, or fails when `NETSPEED_STRICT_CONFIG`
+/// is enabled.
+///
+/// # Examples
+///
+/// ```no_run
+/// use netspeed_lite::config::{ignored_schedule_vars, ScheduleMode};
+///
+/// std::env::set_var("NETSPEED_INTERVAL_SECONDS", "600");
+/// assert_eq!(
+///     ignored_schedule_vars(&ScheduleMode::Cron),
+///     vec!["NETSPEED_INTERVAL_SECONDS"]
+/// );
+/// ```
+pub fn ignored_schedule_vars(mode: &ScheduleMode) -> Vec<&'static str> {
+    ignored_schedule_vars_in(mode, |var| env::var_os(var).is_some())
+}
+
+/// `ignored_schedule_vars`, with `is_set` deciding whether a variable is set.
+fn ignored_schedule_vars_in(
+    mode: &ScheduleMode,
+    is_set: impl Fn(&str) -> bool,
+) -> Vec<&'static str> {
+    const ADAPTIVE_VARS: [&str; 3] = [
+        "NETSPEED_ADAPTIVE_MIN_SECONDS",
+        "NETSPEED_ADAPTIVE_MAX_SECONDS",
+        "NETSPEED_ADAPTIVE_TOLERANCE",
+    ];
+
+    let mut ignored = Vec::new();
+    if *mode != ScheduleMode::Cron {
+        ignored.push("NETSPEED_SCHEDULE");
+    }
+    if matches!(mode, ScheduleMode::Cron | ScheduleMode::HourlyAligned) {
+        ignored.push("NETSPEED_INTERVAL_SECONDS");
+    }
+    if *mode != ScheduleMode::Interval {
+        ignored.push("NETSPEED_SCHEDULE_JITTER_SECONDS");
+    }
+    if *mode != ScheduleMode::Adaptive {
+        ignored.extend(ADAPTIVE_VARS);
+    }
+
+    ignored.retain(|var| is_set(var));
+    ignored
+}
+
+/// Ensures the Ookla CLI arguments request JSON output.
+///
+/// The runner can only parse `--format=json` output, so if it is missing it is
+/// injected (replacing any other `--format` value) and a warning is logged.
+///
+/// # Examples
+///
+/// ```
+/// use netspeed_lite::config::normalize_ookla_args;
+///
+/// let args = normalize_ookla_args(vec!["--accept-license".to_string()]);
+/// assert_eq!(args, vec!["--format=json", "--accept-license"]);
+/// ```
+pub fn normalize_ookla_args(mut args: Vec<String>) -> Vec<String> {
+    if !args.iter().any(|arg| arg == "--format=json") {
+        tracing::warn!("Speedtest arguments are missing --format=json, injecting it");
+        args.retain(|arg| !arg.starts_with("--format"));
+        args.insert(0, "--format=json".to_string());
+    }
+    args
+}
@@ -0,0 +1,219 @@
+//! # Run History
+//!
+//! This module persists completed runs to a SQLite database for long-term history.
+//! It handles:
+//! - Creating the `runs` table when the database is opened.
+//! - Recording each completed run as a `RunRecord`.
+//! - Reading back the most recent runs for `GET /history`.
+//!
+//! Prometheus retention is usually short; the database keeps every run without
+//! needing a TSDB. Storage requires the `history` cargo feature; without it,
+//! `RunHistory::open` fails and no history is kept.
+use crate::runner::{Direction, RunOutcome};
+use chrono::Utc;
+use serde::Serialize;
+use std::path::Path;
+use std::time::Duration;
+
+/// One completed run, as stored in the `runs` table.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RunRecord {
+    /// Unix timestamp of the run, in seconds.
+    pub timestamp: i64,
+    /// Download speed in bits per second; `None` for failures and partial
+    /// results missing the download.
+    pub download_bps: Option<f64>,
+    /// Upload speed in bits per second; `None` for failures and partial
+    /// results missing the upload.
+    pub upload_bps: Option<f64>,
+    pub latency_seconds: Option<f64>,
+    pub jitter_seconds: Option<f64>,
+    pub packet_loss_ratio: Option<f64>,
+    /// `"success"` or `"failure"`.
+    pub outcome: String,
+    pub duration_seconds: f64,
+    /// Stage at which a failed run failed (`ErrorCategory::stage`).
+    pub error_category: Option<String>,
+}
+
+impl RunRecord {
+    /// Builds the record of a completed run.
+    ///
+    /// Like `netspeed_run_timestamp_seconds`, a successful run is timestamped
+    /// with the time the CLI reports the test ran, falling back to now.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use netspeed_lite::history::RunRecord;
+    /// use netspeed_lite::runner::{ErrorCategory, RunOutcome};
+    /// use std::time::Duration;
+    ///
+    /// let outcome = RunOutcome::Failure(ErrorCategory::Timeout(120));
+    /// let record = RunRecord::from_outcome(&outcome, Duration::from_secs(120));
+    /// assert_eq!(record.outcome, "failure");
+    /// assert_eq!(record.error_category.as_deref(), Some("execute"));
+    /// ```
+    pub fn from_outcome(outcome: &RunOutcome, duration: Duration) -> Self {
+        let duration_seconds = duration.as_secs_f64();
+        match outcome {
+            RunOutcome::Success(result) => Self {
+                timestamp: result.measured_at.unwrap_or_else(Utc::now).timestamp(),
+                download_bps: (result.missing != Some(Direction::Download))
+                    .then_some(result.download_bps),
+                upload_bps: (result.missing != Some(Direction::Upload))
+                    .then_some(result.upload_bps),
+                latency_seconds: Some(result.latency_seconds),
+                jitter_seconds: result.jitter_seconds,
+                packet_loss_ratio: result.packet_loss_ratio,
+                outcome: "success".to_string(),
+                duration_seconds,
+                error_category: None,
+            },
+            RunOutcome::Failure(error) => Self {
+                timestamp: Utc::now().timestamp(),
+                download_bps: None,
+                upload_bps: None,
+                latency_seconds: None,
+                jitter_seconds: None,
+                packet_loss_ratio: None,
+                outcome: "failure".to_string(),
+                duration_seconds,
+                error_category: Some(error.stage().to_string()),
+            },
+        }
+    }
+}
+
+/// SQLite database holding the history of completed runs.
+#[cfg(feature = "history")]
+pub struct RunHistory {
+    connection: std::sync::Mutex<rusqlite::Connection>,
+}
+
+#[cfg(feature = "history")]
+impl RunHistory {
+    /// Opens (or creates) the database at `path` and creates the `runs` table
+    /// if it doesn't exist yet. `":memory:"` opens a private in-memory database.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Err` if the database cannot be opened or the table created.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use netspeed_lite::history::RunHistory;
+    /// use std::path::Path;
+    ///
+    /// let history = RunHistory::open(Path::new(":memory:")).unwrap();
+    /// assert!(history.recent(10).unwrap().is_empty());
+    /// ```
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        use anyhow::Context;
+
+        let connection = rusqlite::Connection::open(path)
+            .with_context(|| format!("Failed to open history database {}", path.display()))?;
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS runs (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    timestamp INTEGER NOT NULL,
+                    download_bps REAL,
+                    upload_bps REAL,
+                    latency_seconds REAL,
+                    jitter_seconds REAL,
+                    packet_loss_ratio REAL,
+                    outcome TEXT NOT NULL,
+                    duration_seconds REAL NOT NULL,
+                    error_category TEXT
+                )",
+            )
+            .context("Failed to create the runs table")?;
+
+        Ok(Self {
+            connection: std::sync::Mutex::new(connection),
+        })
+    }
+
+    /// Inserts a completed run.
+    pub fn record(&self, record: &RunRecord) -> anyhow::Result<()> {
+        self.connection
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .execute(
+                "INSERT INTO runs (timestamp, download_bps, upload_bps, latency_seconds,
+                jitter_seconds, packet_loss_ratio, outcome, duration_seconds, error_category)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                rusqlite::params![
+                    record.timestamp,
+                    record.download_bps,
+                    record.upload_bps,
+                    record.latency_seconds,
+                    record.jitter_seconds,
+                    record.packet_loss_ratio,
+                    record.outcome,
+                    record.duration_seconds,
+                    record.error_category,
+                ],
+            )?;
+        Ok(())
+    }
+
+    /// Returns the last `limit` recorded runs, oldest first.
+    pub fn recent(&self, limit: usize) -> anyhow::Result<Vec<RunRecord>> {
+        let connection = self
+            .connection
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let mut statement = connection.prepare(
+            "SELECT timestamp, download_bps, upload_bps, latency_seconds, jitter_seconds,
+                packet_loss_ratio, outcome, duration_seconds, error_category
+             FROM runs ORDER BY id DESC LIMIT ?1",
+        )?;
+        let mut records = statement
+            .query_map([limit as i64], |row| {
+                Ok(RunRecord {
+                    timestamp: row.get(0)?,
+                    download_bps: row.get(1)?,
+                    upload_bps: row.get(2)?,
+                    latency_seconds: row.get(3)?,
+                    jitter_seconds: row.get(4)?,
+                    packet_loss_ratio: row.get(5)?,
+                    outcome: row.get(6)?,
+                    duration_seconds: row.get(7)?,
+                    error_category: row.get(8)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        records.reverse();
+        Ok(records)
+    }
+}
+
+/// Stand-in used when the crate is built without the `history` feature; it
+/// can't be opened.
+#[cfg(not(feature = "history"))]
+pub struct RunHistory;
+
+#[cfg(not(feature = "history"))]
+impl RunHistory {
+    /// Always fails, as the crate was built without the `history` feature.
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        anyhow::bail!(
+            "Run history database {} requested but the `history` feature is disabled",
+            path.display()
+        )
+    }
+
+    /// Drops the run without storing anything.
+    pub fn record(&self, _record: &RunRecord) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Always returns no runs.
+    pub fn recent(&self, _limit: usize) -> anyhow::Result<Vec<RunRecord>> {
+        Ok(Vec::new())
+    }
+}
@@ -1,6 +1,10 @@
 pub mod config;
+pub mod history;
 pub mod metrics;
 pub mod notifier;
+pub mod resources;
 pub mod runner;
 pub mod scheduler;
 pub mod server;
+pub mod supervisor;
+pub mod webhook;
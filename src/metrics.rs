@@ -5,15 +5,63 @@
 //!
 //! Metrics include:
 //! - Speed test results: `netspeed_download_bps`, `netspeed_upload_bps`, `netspeed_latency_seconds`.
-//! - Network quality: `netspeed_jitter_seconds`, `netspeed_packet_loss_ratio`.
-//! - Operational: `netspeed_last_run_seconds`, `netspeed_notify_total`.
-//! - Resource usage: `netspeed_process_cpu_usage`, `netspeed_process_memory_bytes`.
-use prometheus::{Encoder, Gauge, IntCounterVec, Opts, Registry, TextEncoder};
-use std::sync::Arc;
+//! - Distributions for percentile queries: `netspeed_download_bps_histogram`,
+//!   `netspeed_upload_bps_histogram`, `netspeed_latency_seconds_histogram`.
+//! - Network quality: `netspeed_jitter_seconds`, `netspeed_packet_loss_ratio`,
+//!   `netspeed_download_loaded_latency_seconds`, `netspeed_upload_loaded_latency_seconds`.
+//! - Operational: `netspeed_last_run_seconds`, `netspeed_notify_total`, `netspeed_scrape_duration_seconds`,
+//!   `netspeed_single_connection`, `netspeed_run_gap_seconds`.
+//! - Resource usage: `netspeed_process_cpu_usage`, `netspeed_process_cpu_usage_raw`, `netspeed_process_memory_bytes`, `netspeed_process_memory_mb`.
+use anyhow::Context;
+use prometheus::core::Collector;
+use prometheus::{
+    Encoder, Gauge, GaugeVec, Histogram, HistogramOpts, IntCounter, IntCounterVec, Opts, Registry,
+    TextEncoder,
+};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Instant;
+
+/// Buckets for the download/upload histograms, in bits per second (1 Mbps to 1 Gbps).
+const BANDWIDTH_BUCKETS: [f64; 11] = [
+    1e6, 2e6, 5e6, 10e6, 25e6, 50e6, 100e6, 250e6, 500e6, 750e6, 1e9,
+];
+
+/// Buckets for the gap between run starts, in seconds (1 minute to 1 day).
+const RUN_GAP_BUCKETS: [f64; 11] = [
+    60.0, 300.0, 900.0, 1800.0, 3600.0, 5400.0, 7200.0, 14400.0, 21600.0, 43200.0, 86400.0,
+];
+
+/// Buckets for the latency histogram, in seconds (5 ms to 2 s).
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.02, 0.03, 0.05, 0.075, 0.1, 0.25, 0.5, 1.0, 2.0,
+];
+
+/// Registers `metric`, naming it in the error so that a clash (e.g. with a
+/// caller's own metric in `Metrics::from_registry`) is easy to track down.
+fn register<C: Collector + Clone + 'static>(registry: &Registry, metric: &C) -> anyhow::Result<()> {
+    registry
+        .register(Box::new(metric.clone()))
+        .with_context(|| {
+            let names: Vec<&str> = metric
+                .desc()
+                .iter()
+                .map(|desc| desc.fq_name.as_str())
+                .collect();
+            format!("Failed to register metric {}", names.join(", "))
+        })
+}
 
 #[derive(Clone)]
 pub struct Metrics {
     registry: Arc<Registry>,
+    measurements_pending: Arc<AtomicBool>,
+    last_notify_success: Arc<Mutex<Instant>>,
+    // Whether the optional measurements were ever reported, to tell unset from 0
+    jitter_reported: Arc<AtomicBool>,
+    packet_loss_reported: Arc<AtomicBool>,
 
     // Run status & counters
     pub last_success: Gauge,
@@ -23,7 +71,9 @@ pub struct Metrics {
 
     // Resource usage
     pub process_cpu_usage: Gauge,
+    pub process_cpu_usage_raw: Gauge,
     pub process_memory_bytes: Gauge,
+    pub process_memory_mb: Gauge,
 
     // Measurements
     pub download_bps: Gauge,
@@ -31,9 +81,30 @@ pub struct Metrics {
     pub latency_seconds: Gauge,
     pub jitter_seconds: Gauge,
     pub packet_loss_ratio: Gauge,
+    pub download_loaded_latency_seconds: Gauge,
+    pub upload_loaded_latency_seconds: Gauge,
 
     // Operational
     pub notify_total: IntCounterVec,
+    pub duty_cycle: Gauge,
+    pub task_restarts_total: IntCounterVec,
+    pub has_run: Gauge,
+    pub errors_total: IntCounterVec,
+    pub notify_responses_total: IntCounterVec,
+    pub external_ip_info: GaugeVec,
+    pub clock_skew_events_total: IntCounter,
+    pub jitter_exceeded: Gauge,
+    pub anomaly: Gauge,
+    pub seconds_since_last_notify_success: Gauge,
+    pub interface_info: GaugeVec,
+    pub scrape_duration_seconds: Gauge,
+    pub single_connection: Gauge,
+    pub download_bps_histogram: Histogram,
+    pub upload_bps_histogram: Histogram,
+    pub latency_seconds_histogram: Histogram,
+    pub run_gap_seconds: Histogram,
+    pub server_info: GaugeVec,
+    pub last_notification_info: GaugeVec,
 }
 
 impl Metrics {
@@ -41,17 +112,40 @@ impl Metrics {
     ///
     /// This function initializes and registers the following metrics:
     /// - `netspeed_last_success`: Gauge indicating if last run was successful (0 or 1)
-    /// - `netspeed_runs_total`: Counter for total runs by outcome (success/failure/skipped)
+    /// - `netspeed_runs_total`: Counter for total runs by outcome (success/failure/skipped/retried)
     /// - `netspeed_run_duration_seconds`: Gauge for last run duration
     /// - `netspeed_run_timestamp_seconds`: Gauge for last run timestamp
-    /// - `netspeed_process_cpu_usage`: Gauge for process CPU usage percentage
+    /// - `netspeed_process_cpu_usage`: Gauge for process CPU usage percentage (smoothed)
+    /// - `netspeed_process_cpu_usage_raw`: Gauge for the unsmoothed process CPU usage percentage
     /// - `netspeed_process_memory_bytes`: Gauge for process memory in bytes
+    /// - `netspeed_process_memory_mb`: Gauge for process memory in MiB (mirrors `netspeed_process_memory_bytes`)
     /// - `netspeed_download_bps`: Gauge for download speed in bits per second
     /// - `netspeed_upload_bps`: Gauge for upload speed in bits per second
     /// - `netspeed_latency_seconds`: Gauge for latency in seconds
     /// - `netspeed_jitter_seconds`: Gauge for jitter in seconds (optional)
     /// - `netspeed_packet_loss_ratio`: Gauge for packet loss ratio 0-1 (optional)
+    /// - `netspeed_download_loaded_latency_seconds`: Gauge for latency during the download phase in seconds (optional)
+    /// - `netspeed_upload_loaded_latency_seconds`: Gauge for latency during the upload phase in seconds (optional)
     /// - `netspeed_notify_total`: Counter for notifications sent by outcome
+    /// - `netspeed_duty_cycle`: Gauge for the fraction of the schedule interval spent testing (0-1)
+    /// - `netspeed_task_restarts_total`: Counter for background task restarts after a panic, by task
+    /// - `netspeed_has_run`: Gauge indicating whether any run has completed since startup (0 or 1)
+    /// - `netspeed_errors_total`: Counter for failed runs by stage (execute/parse/criterion)
+    /// - `netspeed_notify_responses_total`: Counter for ntfy responses by status class (2xx/4xx/5xx/error)
+    /// - `netspeed_external_ip_info`: Info gauge (always 1) labeled with the external IP, when exposure is enabled
+    /// - `netspeed_clock_skew_events_total`: Counter for scheduled runs skipped because the scheduler woke far later than planned
+    /// - `netspeed_jitter_exceeded`: Gauge indicating whether the last jitter exceeded the configured maximum (0 or 1)
+    /// - `netspeed_anomaly`: Gauge indicating whether the last run's download or upload was anomalous (0 or 1)
+    /// - `netspeed_seconds_since_last_notify_success`: Gauge for seconds since the last notification ntfy accepted (since startup if none yet)
+    /// - `netspeed_interface_info`: Info gauge (always 1) labeled with the network interface name, when exposure is enabled
+    /// - `netspeed_scrape_duration_seconds`: Gauge for the time taken to render the previous `/metrics` scrape
+    /// - `netspeed_single_connection`: Whether speed tests run over a single connection (`--single`, 0 or 1)
+    /// - `netspeed_download_bps_histogram`: Histogram of download speeds in bits per second, for percentiles over time
+    /// - `netspeed_upload_bps_histogram`: Histogram of upload speeds in bits per second
+    /// - `netspeed_latency_seconds_histogram`: Histogram of idle latencies in seconds
+    /// - `netspeed_run_gap_seconds`: Histogram of the time between consecutive run starts, to spot scheduler drift and missed runs
+    /// - `netspeed_server_info`: Info gauge (always 1) labeled with the `id`, `name` and `location` of the server used by the last successful run
+    /// - `netspeed_last_notification_info`: Unix timestamp of the last notification, labeled with its `channel` and `outcome` (success/failure)
     ///
     /// # Returns
     ///
@@ -67,63 +161,180 @@ impl Metrics {
     /// metrics.download_bps.set(100_000_000.0); // 100 Mbps
     /// ```
     pub fn new() -> anyhow::Result<Self> {
-        let registry = Registry::new();
+        Self::with_deferred_measurements(false)
+    }
+
+    /// Creates a new Metrics instance, optionally deferring the measurement gauges.
+    ///
+    /// When `defer` is true, the measurement gauges (`download_bps`, `upload_bps`,
+    /// `latency_seconds`, `jitter_seconds`, `packet_loss_ratio` and the loaded
+    /// latency gauges) are not registered until `expose_measurements` is called,
+    /// so they don't render as 0 before the first successful run. All other
+    /// metrics are registered immediately.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use netspeed_lite::metrics::Metrics;
+    ///
+    /// let metrics = Metrics::with_deferred_measurements(true).unwrap();
+    /// assert!(!metrics.render().unwrap().contains("netspeed_download_bps "));
+    /// ```
+    pub fn with_deferred_measurements(defer: bool) -> anyhow::Result<Self> {
+        Self::with_source_label(defer, None)
+    }
+
+    /// Creates a new Metrics instance whose metrics all carry a constant
+    /// `source` label, so federated setups can group series by origin.
+    ///
+    /// The label is distinct from the `instance` label Prometheus attaches at
+    /// scrape time. With `source` set to `None` this is equivalent to
+    /// `with_deferred_measurements`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use netspeed_lite::metrics::Metrics;
+    ///
+    /// let metrics = Metrics::with_source_label(false, Some("home")).unwrap();
+    /// assert!(metrics.render().unwrap().contains(r#"netspeed_has_run{source="home"} 0"#));
+    /// ```
+    pub fn with_source_label(defer: bool, source: Option<&str>) -> anyhow::Result<Self> {
+        let labels =
+            source.map(|source| HashMap::from([("source".to_string(), source.to_string())]));
+        let registry = Registry::new_custom(None, labels)?;
+        Self::register_into(Arc::new(registry), defer)
+    }
+
+    /// Creates a new Metrics instance registered into a caller-provided registry.
+    ///
+    /// This lets applications embedding the crate expose netspeed metrics
+    /// alongside their own from a single registry. All metrics, including the
+    /// measurement gauges, are registered immediately.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Err` naming the first metric that is already registered in
+    /// `registry`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use netspeed_lite::metrics::Metrics;
+    /// use prometheus::Registry;
+    /// use std::sync::Arc;
+    ///
+    /// let registry = Arc::new(Registry::new());
+    /// let metrics = Metrics::from_registry(registry.clone()).unwrap();
+    /// metrics.download_bps.set(100_000_000.0);
+    /// assert!(!registry.gather().is_empty());
+    /// ```
+    pub fn from_registry(registry: Arc<Registry>) -> anyhow::Result<Self> {
+        Self::register_into(registry, false)
+    }
 
+    /// Returns the registry holding all netspeed metrics.
+    ///
+    /// Use this to merge the metrics into another exposition, e.g. by gathering
+    /// it alongside an application's own registry.
+    pub fn registry(&self) -> Arc<Registry> {
+        Arc::clone(&self.registry)
+    }
+
+    fn register_into(registry: Arc<Registry>, defer: bool) -> anyhow::Result<Self> {
         // Run status & counters
         let last_success = Gauge::new(
             "netspeed_last_success",
             "Whether the last run was successful (0 or 1)",
         )?;
-        registry.register(Box::new(last_success.clone()))?;
+        register(&registry, &last_success)?;
 
         let runs_total = IntCounterVec::new(
             Opts::new("netspeed_runs_total", "Total number of speed test runs"),
             &["outcome"],
         )?;
-        registry.register(Box::new(runs_total.clone()))?;
+        register(&registry, &runs_total)?;
 
         let run_duration_seconds = Gauge::new(
             "netspeed_run_duration_seconds",
             "Duration of the last speed test run in seconds",
         )?;
-        registry.register(Box::new(run_duration_seconds.clone()))?;
+        register(&registry, &run_duration_seconds)?;
 
         let run_timestamp_seconds = Gauge::new(
             "netspeed_run_timestamp_seconds",
-            "Unix timestamp of the last speed test completion",
+            "Unix timestamp of the last speed test (as reported by the CLI, else its completion)",
         )?;
-        registry.register(Box::new(run_timestamp_seconds.clone()))?;
+        register(&registry, &run_timestamp_seconds)?;
 
         // Resource usage
         let process_cpu_usage =
             Gauge::new("netspeed_process_cpu_usage", "Process CPU usage percentage")?;
-        registry.register(Box::new(process_cpu_usage.clone()))?;
+        register(&registry, &process_cpu_usage)?;
+
+        let process_cpu_usage_raw = Gauge::new(
+            "netspeed_process_cpu_usage_raw",
+            "Unsmoothed process CPU usage percentage from the latest sample",
+        )?;
+        register(&registry, &process_cpu_usage_raw)?;
 
         let process_memory_bytes = Gauge::new(
             "netspeed_process_memory_bytes",
             "Process memory usage in bytes",
         )?;
-        registry.register(Box::new(process_memory_bytes.clone()))?;
+        register(&registry, &process_memory_bytes)?;
+
+        let process_memory_mb = Gauge::new(
+            "netspeed_process_memory_mb",
+            "Process memory usage in mebibytes",
+        )?;
+        register(&registry, &process_memory_mb)?;
 
         // Measurements
         let download_bps =
             Gauge::new("netspeed_download_bps", "Download speed in bits per second")?;
-        registry.register(Box::new(download_bps.clone()))?;
+        if !defer {
+            register(&registry, &download_bps)?;
+        }
 
         let upload_bps = Gauge::new("netspeed_upload_bps", "Upload speed in bits per second")?;
-        registry.register(Box::new(upload_bps.clone()))?;
+        if !defer {
+            register(&registry, &upload_bps)?;
+        }
 
         let latency_seconds = Gauge::new("netspeed_latency_seconds", "Latency in seconds")?;
-        registry.register(Box::new(latency_seconds.clone()))?;
+        if !defer {
+            register(&registry, &latency_seconds)?;
+        }
 
         let jitter_seconds = Gauge::new("netspeed_jitter_seconds", "Jitter in seconds (optional)")?;
-        registry.register(Box::new(jitter_seconds.clone()))?;
+        if !defer {
+            register(&registry, &jitter_seconds)?;
+        }
 
         let packet_loss_ratio = Gauge::new(
             "netspeed_packet_loss_ratio",
             "Packet loss ratio from 0 to 1 (optional)",
         )?;
-        registry.register(Box::new(packet_loss_ratio.clone()))?;
+        if !defer {
+            register(&registry, &packet_loss_ratio)?;
+        }
+
+        let download_loaded_latency_seconds = Gauge::new(
+            "netspeed_download_loaded_latency_seconds",
+            "Latency during the download phase in seconds (optional)",
+        )?;
+        if !defer {
+            register(&registry, &download_loaded_latency_seconds)?;
+        }
+
+        let upload_loaded_latency_seconds = Gauge::new(
+            "netspeed_upload_loaded_latency_seconds",
+            "Latency during the upload phase in seconds (optional)",
+        )?;
+        if !defer {
+            register(&registry, &upload_loaded_latency_seconds)?;
+        }
 
         // Operational
         let notify_total = IntCounterVec::new(
@@ -133,25 +344,316 @@ impl Metrics {
             ),
             &["outcome"],
         )?;
-        registry.register(Box::new(notify_total.clone()))?;
+        register(&registry, &notify_total)?;
+
+        let duty_cycle = Gauge::new(
+            "netspeed_duty_cycle",
+            "Fraction of the schedule interval spent running the last speed test (0-1)",
+        )?;
+        register(&registry, &duty_cycle)?;
+
+        let task_restarts_total = IntCounterVec::new(
+            Opts::new(
+                "netspeed_task_restarts_total",
+                "Total number of background task restarts after a panic",
+            ),
+            &["task"],
+        )?;
+        register(&registry, &task_restarts_total)?;
+
+        let has_run = Gauge::new(
+            "netspeed_has_run",
+            "Whether any speed test run has completed since startup (0 or 1)",
+        )?;
+        register(&registry, &has_run)?;
+
+        let errors_total = IntCounterVec::new(
+            Opts::new(
+                "netspeed_errors_total",
+                "Total number of failed speed test runs by failure stage",
+            ),
+            &["stage"],
+        )?;
+        register(&registry, &errors_total)?;
+
+        let notify_responses_total = IntCounterVec::new(
+            Opts::new(
+                "netspeed_notify_responses_total",
+                "Total number of ntfy responses by HTTP status class",
+            ),
+            &["status"],
+        )?;
+        register(&registry, &notify_responses_total)?;
+
+        let external_ip_info = GaugeVec::new(
+            Opts::new(
+                "netspeed_external_ip_info",
+                "External IP address of the last successful run (value is always 1)",
+            ),
+            &["ip"],
+        )?;
+        register(&registry, &external_ip_info)?;
+
+        let clock_skew_events_total = IntCounter::new(
+            "netspeed_clock_skew_events_total",
+            "Total number of scheduled runs skipped after waking far later than planned (e.g. host suspend)",
+        )?;
+        register(&registry, &clock_skew_events_total)?;
+
+        let jitter_exceeded = Gauge::new(
+            "netspeed_jitter_exceeded",
+            "Whether the last run's jitter exceeded the configured maximum (0 or 1)",
+        )?;
+        register(&registry, &jitter_exceeded)?;
+
+        let anomaly = Gauge::new(
+            "netspeed_anomaly",
+            "Whether the last run's download or upload deviated from its moving mean (0 or 1)",
+        )?;
+        register(&registry, &anomaly)?;
+
+        let seconds_since_last_notify_success = Gauge::new(
+            "netspeed_seconds_since_last_notify_success",
+            "Seconds since the last successfully delivered notification (since startup if none)",
+        )?;
+        register(&registry, &seconds_since_last_notify_success)?;
+
+        let interface_info = GaugeVec::new(
+            Opts::new(
+                "netspeed_interface_info",
+                "Network interface of the last successful run (value is always 1)",
+            ),
+            &["name"],
+        )?;
+        register(&registry, &interface_info)?;
+
+        let scrape_duration_seconds = Gauge::new(
+            "netspeed_scrape_duration_seconds",
+            "Time taken to render and encode the previous /metrics scrape",
+        )?;
+        register(&registry, &scrape_duration_seconds)?;
+
+        let single_connection = Gauge::new(
+            "netspeed_single_connection",
+            "Whether speed tests run over a single connection (1) or several (0)",
+        )?;
+        register(&registry, &single_connection)?;
+
+        let download_bps_histogram = Histogram::with_opts(
+            HistogramOpts::new(
+                "netspeed_download_bps_histogram",
+                "Distribution of download speeds in bits per second",
+            )
+            .buckets(BANDWIDTH_BUCKETS.to_vec()),
+        )?;
+        register(&registry, &download_bps_histogram)?;
+
+        let upload_bps_histogram = Histogram::with_opts(
+            HistogramOpts::new(
+                "netspeed_upload_bps_histogram",
+                "Distribution of upload speeds in bits per second",
+            )
+            .buckets(BANDWIDTH_BUCKETS.to_vec()),
+        )?;
+        register(&registry, &upload_bps_histogram)?;
+
+        let latency_seconds_histogram = Histogram::with_opts(
+            HistogramOpts::new(
+                "netspeed_latency_seconds_histogram",
+                "Distribution of idle latencies in seconds",
+            )
+            .buckets(LATENCY_BUCKETS.to_vec()),
+        )?;
+        register(&registry, &latency_seconds_histogram)?;
+
+        let run_gap_seconds = Histogram::with_opts(
+            HistogramOpts::new(
+                "netspeed_run_gap_seconds",
+                "Time between the starts of consecutive runs",
+            )
+            .buckets(RUN_GAP_BUCKETS.to_vec()),
+        )?;
+        register(&registry, &run_gap_seconds)?;
+
+        let server_info = GaugeVec::new(
+            Opts::new(
+                "netspeed_server_info",
+                "Speedtest server of the last successful run (value is always 1)",
+            ),
+            &["id", "name", "location"],
+        )?;
+        register(&registry, &server_info)?;
+
+        let last_notification_info = GaugeVec::new(
+            Opts::new(
+                "netspeed_last_notification_info",
+                "Unix timestamp of the last notification, labeled with its channel and outcome",
+            ),
+            &["channel", "outcome"],
+        )?;
+        register(&registry, &last_notification_info)?;
 
         Ok(Metrics {
-            registry: Arc::new(registry),
+            registry,
+            measurements_pending: Arc::new(AtomicBool::new(defer)),
+            jitter_reported: Arc::new(AtomicBool::new(false)),
+            packet_loss_reported: Arc::new(AtomicBool::new(false)),
+            last_notify_success: Arc::new(Mutex::new(Instant::now())),
             last_success,
             runs_total,
             run_duration_seconds,
             run_timestamp_seconds,
             process_cpu_usage,
+            process_cpu_usage_raw,
             process_memory_bytes,
+            process_memory_mb,
             download_bps,
             upload_bps,
             latency_seconds,
             jitter_seconds,
             packet_loss_ratio,
+            download_loaded_latency_seconds,
+            upload_loaded_latency_seconds,
             notify_total,
+            duty_cycle,
+            task_restarts_total,
+            has_run,
+            errors_total,
+            notify_responses_total,
+            external_ip_info,
+            clock_skew_events_total,
+            jitter_exceeded,
+            anomaly,
+            seconds_since_last_notify_success,
+            interface_info,
+            scrape_duration_seconds,
+            single_connection,
+            download_bps_histogram,
+            upload_bps_histogram,
+            latency_seconds_histogram,
+            run_gap_seconds,
+            server_info,
+            last_notification_info,
         })
     }
 
+    /// Registers deferred measurement gauges so they start being rendered.
+    ///
+    /// Does nothing if the gauges were registered at construction or have already
+    /// been exposed. Called by the scheduler after each successful run.
+    pub fn expose_measurements(&self) {
+        if !self.measurements_pending.swap(false, Ordering::SeqCst) {
+            return;
+        }
+
+        let gauges = [
+            &self.download_bps,
+            &self.upload_bps,
+            &self.latency_seconds,
+            &self.jitter_seconds,
+            &self.packet_loss_ratio,
+            &self.download_loaded_latency_seconds,
+            &self.upload_loaded_latency_seconds,
+        ];
+        for gauge in gauges {
+            if let Err(e) = register(&self.registry, gauge) {
+                tracing::warn!("{:#}", e);
+            }
+        }
+    }
+
+    /// Sets `netspeed_jitter_seconds`, marking jitter as reported.
+    pub fn set_jitter_seconds(&self, seconds: f64) {
+        self.jitter_seconds.set(seconds);
+        self.jitter_reported.store(true, Ordering::SeqCst);
+    }
+
+    /// Sets `netspeed_packet_loss_ratio`, marking packet loss as reported.
+    pub fn set_packet_loss_ratio(&self, ratio: f64) {
+        self.packet_loss_ratio.set(ratio);
+        self.packet_loss_reported.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns the last reported jitter, or `None` if no run has reported one
+    /// (the gauge alone can't tell that apart from 0).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use netspeed_lite::metrics::Metrics;
+    ///
+    /// let metrics = Metrics::new().unwrap();
+    /// assert_eq!(metrics.jitter_seconds_value(), None);
+    /// metrics.set_jitter_seconds(0.0);
+    /// assert_eq!(metrics.jitter_seconds_value(), Some(0.0));
+    /// ```
+    pub fn jitter_seconds_value(&self) -> Option<f64> {
+        self.jitter_reported
+            .load(Ordering::SeqCst)
+            .then(|| self.jitter_seconds.get())
+    }
+
+    /// Returns the last reported packet loss ratio, or `None` if no run has
+    /// reported one.
+    pub fn packet_loss_ratio_value(&self) -> Option<f64> {
+        self.packet_loss_reported
+            .load(Ordering::SeqCst)
+            .then(|| self.packet_loss_ratio.get())
+    }
+
+    /// Records a successfully delivered notification, resetting
+    /// `netspeed_seconds_since_last_notify_success` to 0.
+    pub fn record_notify_success(&self) {
+        *self
+            .last_notify_success
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Instant::now();
+        self.seconds_since_last_notify_success.set(0.0);
+    }
+
+    /// Records the channel and outcome of the most recent notification in
+    /// `netspeed_last_notification_info`, replacing the previous series.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use netspeed_lite::metrics::Metrics;
+    ///
+    /// let metrics = Metrics::new().unwrap();
+    /// metrics.record_notification("ntfy", "success");
+    /// assert!(metrics.render().unwrap().contains(
+    ///     r#"netspeed_last_notification_info{channel="ntfy",outcome="success"}"#
+    /// ));
+    /// ```
+    pub fn record_notification(&self, channel: &str, outcome: &str) {
+        self.last_notification_info.reset();
+        self.last_notification_info
+            .with_label_values(&[channel, outcome])
+            .set(chrono::Utc::now().timestamp() as f64);
+    }
+
+    /// Refreshes `netspeed_seconds_since_last_notify_success` from the last
+    /// recorded success (or startup). Called from the resource monitoring loop.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use netspeed_lite::metrics::Metrics;
+    ///
+    /// let metrics = Metrics::new().unwrap();
+    /// metrics.update_seconds_since_notify_success();
+    /// assert!(metrics.seconds_since_last_notify_success.get() < 1.0);
+    /// ```
+    pub fn update_seconds_since_notify_success(&self) {
+        let elapsed = self
+            .last_notify_success
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .elapsed();
+        self.seconds_since_last_notify_success
+            .set(elapsed.as_secs_f64());
+    }
+
     /// Renders all registered metrics in Prometheus text format.
     ///
     /// This function gathers all metrics from the registry and encodes them
@@ -178,6 +680,38 @@ impl Metrics {
         encoder.encode(&metric_families, &mut buffer)?;
         Ok(String::from_utf8(buffer)?)
     }
+
+    /// Writes the rendered metrics to `path` for node_exporter's textfile collector.
+    ///
+    /// The metrics are written to a temporary file next to `path` and then
+    /// renamed over it, so the collector never reads a partially written file.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Err` if rendering fails or the file cannot be written or renamed.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use netspeed_lite::metrics::Metrics;
+    /// use std::path::Path;
+    ///
+    /// let metrics = Metrics::new().unwrap();
+    /// metrics
+    ///     .write_textfile(Path::new("/var/lib/node_exporter/textfile/netspeed.prom"))
+    ///     .unwrap();
+    /// ```
+    pub fn write_textfile(&self, path: &Path) -> anyhow::Result<()> {
+        let rendered = self.render()?;
+
+        let mut temp_name = path.as_os_str().to_owned();
+        temp_name.push(".tmp");
+        let temp_path = PathBuf::from(temp_name);
+
+        std::fs::write(&temp_path, rendered)?;
+        std::fs::rename(&temp_path, path)?;
+        Ok(())
+    }
 }
 
 impl Default for Metrics {
@@ -5,18 +5,119 @@
 //! - Construction of notification payloads (JSON).
 //! - Formatting of messages with emojis and details.
 //! - Conditional sending based on `notify_on` configuration (success, failure, or both).
-use crate::config::NtfyConfig;
+//!
+//! Sending requires the `notify` cargo feature (enabled by default). Without it,
+//! `Notifier` is a no-op so the scheduler keeps the same API in metrics-only builds.
+use crate::config::{NtfyConfig, SpeedUnit, SpeedtestConfig};
 use crate::metrics::Metrics;
-use crate::runner::{ErrorCategory, RunOutcome, SpeedtestResult};
+use crate::runner::{Direction, ErrorCategory, RunOutcome, SpeedtestResult};
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use std::time::Duration;
 
+/// Delay before the first retry of a failed notification, doubled for each
+/// further retry.
+#[cfg(feature = "notify")]
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// Why a single attempt to send a notification failed.
+#[cfg(feature = "notify")]
+#[derive(Debug, thiserror::Error)]
+enum SendError {
+    #[error(transparent)]
+    Request(#[from] reqwest::Error),
+    #[error("ntfy returned status: {0}")]
+    Status(reqwest::StatusCode),
+}
+
+#[cfg(feature = "notify")]
+impl SendError {
+    /// Network errors and 5xx responses may clear up on their own; a 4xx
+    /// (bad token, unknown topic) will fail the same way every time.
+    fn is_retryable(&self) -> bool {
+        match self {
+            SendError::Request(e) => !e.is_builder(),
+            SendError::Status(status) => status.is_server_error(),
+        }
+    }
+}
+
+/// Body of the message sent by `Notifier::send_test`.
+pub const TEST_NOTIFICATION_MESSAGE: &str =
+    "Test notification from netspeed-lite: notifications are configured correctly";
+
+/// Returns the body of the startup notification, including the crate version.
+///
+/// # Examples
+///
+/// ```
+/// use netspeed_lite::notifier::startup_message;
+///
+/// assert!(startup_message().starts_with("netspeed-lite started, version "));
+/// ```
+pub fn startup_message() -> String {
+    format!(
+        "netspeed-lite started, version {}",
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+#[cfg(feature = "notify")]
 pub struct Notifier {
     config: NtfyConfig,
     metrics: Metrics,
     client: reqwest::Client,
+    max_jitter_ms: Option<f64>,
 }
 
+/// No-op notifier used when the crate is built without the `notify` feature.
+#[cfg(not(feature = "notify"))]
+pub struct Notifier;
+
+#[cfg(not(feature = "notify"))]
+impl Notifier {
+    /// Creates a no-op Notifier. Nothing is ever sent because the crate was built
+    /// without the `notify` feature.
+    pub fn new(config: NtfyConfig, _metrics: Metrics) -> Self {
+        tracing::warn!(
+            "Notifications to {} requested but the `notify` feature is disabled",
+            config.url
+        );
+        Self
+    }
+
+    /// Drops the notification without sending anything.
+    pub async fn notify(&self, _outcome: &RunOutcome, _duration: Duration) {
+        tracing::debug!("Notification skipped: built without the `notify` feature");
+    }
+
+    /// Drops the message without sending anything.
+    pub async fn notify_message(&self, _icon: &str, _message: String) {
+        tracing::debug!("Notification skipped: built without the `notify` feature");
+    }
+
+    /// Drops the message without sending anything.
+    pub async fn notify_run_message(&self, _outcome: &RunOutcome, _icon: &str, _message: String) {
+        tracing::debug!("Notification skipped: built without the `notify` feature");
+    }
+
+    /// Returns the notifier unchanged; there are no tags to rate.
+    pub fn with_max_jitter_ms(self, _max_jitter_ms: Option<f64>) -> Self {
+        self
+    }
+
+    /// Sends nothing: there is no channel without the `notify` feature.
+    pub async fn notify_start(&self) {
+        tracing::debug!("Startup notification skipped: built without the `notify` feature");
+    }
+
+    /// Always fails: there is no channel to test without the `notify` feature.
+    pub async fn send_test(&self) -> Result<()> {
+        anyhow::bail!("built without the `notify` feature")
+    }
+}
+
+#[cfg(feature = "notify")]
 impl Notifier {
     /// Creates a new Notifier instance with an HTTP client configured for ntfy.sh.
     ///
@@ -36,7 +137,7 @@ impl Notifier {
     /// # Examples
     ///
     /// ```no_run
-    /// use netspeed_lite::config::NtfyConfig;
+    /// use netspeed_lite::config::{NtfyConfig, SpeedUnit};
     /// use netspeed_lite::metrics::Metrics;
     /// use netspeed_lite::notifier::Notifier;
     ///
@@ -47,6 +148,11 @@ impl Notifier {
     ///     tags: "speedtest,isp".to_string(),
     ///     priority: 3,
     ///     click_url: None,
+    ///     markdown: false,
+    ///     speed_unit: SpeedUnit::Mbps,
+    ///     quality_tags: false,
+    ///     max_retries: 3,
+    ///     max_chars: None,
     /// };
     /// let metrics = Metrics::new().unwrap();
     /// let notifier = Notifier::new(config, metrics);
@@ -62,9 +168,17 @@ impl Notifier {
             config,
             metrics,
             client,
+            max_jitter_ms: None,
         }
     }
 
+    /// Sets the `NETSPEED_MAX_JITTER_MS` bound above which a successful run
+    /// is tagged `yellow` rather than `green`.
+    pub fn with_max_jitter_ms(mut self, max_jitter_ms: Option<f64>) -> Self {
+        self.max_jitter_ms = max_jitter_ms;
+        self
+    }
+
     /// Sends a notification about a speedtest run outcome.
     ///
     /// This function formats the notification message based on the outcome (success or failure),
@@ -82,8 +196,10 @@ impl Notifier {
     /// - Increments `notify_total{outcome="success"}` metric
     ///
     /// On failure:
-    /// - Logs an error message
-    /// - Increments `notify_total{outcome="failure"}` metric
+    /// - Retries network errors and 5xx responses up to `max_retries` times,
+    ///   waiting 1s, then 2s, 4s...
+    /// - Once all attempts are exhausted, logs an error message and increments
+    ///   the `notify_total{outcome="failure"}` metric
     ///
     /// # Examples
     ///
@@ -100,12 +216,133 @@ impl Notifier {
     ///     latency_seconds: 0.020,
     ///     jitter_seconds: Some(0.002),
     ///     packet_loss_ratio: None,
+    ///     missing: None,
+    ///     external_ip: None,
+    ///     download_latency_seconds: None,
+    ///     upload_latency_seconds: None,
+    ///     interface_name: None,
+    ///     server_id: None,
+    ///     server_name: None,
+    ///     server_location: None,
+    ///     measured_at: None,
     /// };
     /// notifier.notify(&RunOutcome::Success(result), Duration::from_secs(30)).await;
     /// # };
     /// ```
     pub async fn notify(&self, outcome: &RunOutcome, duration: Duration) {
-        let result = self.send_notification(outcome, duration).await;
+        let (title, message) = match outcome {
+            RunOutcome::Success(result) => {
+                let title = format!("{} ✅", self.config.title);
+                let unit = self.config.speed_unit;
+                let message = if self.config.markdown {
+                    format_success_markdown(result, duration, unit)
+                } else {
+                    format_success_message_with(result, duration, unit)
+                };
+                (title, message)
+            }
+            RunOutcome::Failure(error) => {
+                let title = format!("{} ❌", self.config.title);
+                let message = if self.config.markdown {
+                    format_failure_markdown(error)
+                } else {
+                    format_failure_message(error)
+                };
+                (title, message)
+            }
+        };
+
+        self.deliver(title, message, &self.run_tags(outcome)).await;
+    }
+
+    /// Sends a custom message reporting a run, e.g. a verbose failure report.
+    ///
+    /// Like `notify_message`, but tagged like `notify` would tag `outcome`.
+    pub async fn notify_run_message(&self, outcome: &RunOutcome, icon: &str, message: String) {
+        let title = format!("{} {}", self.config.title, icon);
+        self.deliver(title, message, &self.run_tags(outcome)).await;
+    }
+
+    /// Returns the configured tags, plus the quality tag of `outcome` when
+    /// `NETSPEED_NTFY_QUALITY_TAGS` is enabled.
+    fn run_tags(&self, outcome: &RunOutcome) -> String {
+        if self.config.quality_tags {
+            append_tag(&self.config.tags, quality_tag(outcome, self.max_jitter_ms))
+        } else {
+            self.config.tags.clone()
+        }
+    }
+
+    /// Sends a free-form notification that isn't tied to a single run outcome.
+    ///
+    /// The title is the configured title followed by `icon`. Metrics are updated
+    /// exactly as for `notify`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use netspeed_lite::notifier::Notifier;
+    ///
+    /// # async {
+    /// # let notifier: Notifier = unimplemented!();
+    /// notifier
+    ///     .notify_message("🎉", "24 consecutive successful runs".to_string())
+    ///     .await;
+    /// # };
+    /// ```
+    pub async fn notify_message(&self, icon: &str, message: String) {
+        let title = format!("{} {}", self.config.title, icon);
+        self.deliver(title, message, &self.config.tags).await;
+    }
+
+    /// Announces that the process has started, with the running version (see
+    /// `startup_message`), so restarts show up in the notification history.
+    pub async fn notify_start(&self) {
+        self.notify_message("🚀", startup_message()).await;
+    }
+
+    /// Sends a canned test message to check the ntfy configuration.
+    ///
+    /// Unlike `notify`, the outcome is returned to the caller without retrying,
+    /// and `notify_total` is left untouched, since no run is being reported.
+    /// `notify_responses_total` is still updated.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if ntfy accepted the message, or `Err` describing the
+    /// connection error or unexpected status.
+    pub async fn send_test(&self) -> Result<()> {
+        let title = format!("{} 🧪", self.config.title);
+        self.send_notification(
+            title,
+            TEST_NOTIFICATION_MESSAGE.to_string(),
+            &self.config.tags,
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn deliver(&self, title: String, message: String, tags: &str) {
+        let mut retries = 0;
+        let result = loop {
+            match self
+                .send_notification(title.clone(), message.clone(), tags)
+                .await
+            {
+                Err(e) if e.is_retryable() && retries < self.config.max_retries => {
+                    let delay = RETRY_BASE_DELAY.saturating_mul(2u32.saturating_pow(retries));
+                    retries += 1;
+                    tracing::debug!(
+                        "Notification attempt {} failed ({}), retrying in {:?}",
+                        retries,
+                        e,
+                        delay
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                result => break result,
+            }
+        };
 
         match result {
             Ok(_) => {
@@ -114,6 +351,7 @@ impl Notifier {
                     .notify_total
                     .with_label_values(&["success"])
                     .inc();
+                self.metrics.record_notification("ntfy", "success");
             }
             Err(e) => {
                 tracing::error!("Failed to send notification: {}", e);
@@ -121,24 +359,17 @@ impl Notifier {
                     .notify_total
                     .with_label_values(&["failure"])
                     .inc();
+                self.metrics.record_notification("ntfy", "failure");
             }
         }
     }
 
-    async fn send_notification(&self, outcome: &RunOutcome, duration: Duration) -> Result<()> {
-        let (title, message) = match outcome {
-            RunOutcome::Success(result) => {
-                let title = format!("{} ✅", self.config.title);
-                let message = format_success_message(result, duration);
-                (title, message)
-            }
-            RunOutcome::Failure(error) => {
-                let title = format!("{} ❌", self.config.title);
-                let message = format_failure_message(error);
-                (title, message)
-            }
-        };
-
+    async fn send_notification(
+        &self,
+        title: String,
+        message: String,
+        tags: &str,
+    ) -> Result<(), SendError> {
         let mut request = self.client.post(&self.config.url);
 
         // Add authentication if configured
@@ -149,35 +380,136 @@ impl Notifier {
         // Add ntfy headers
         request = request
             .header("Title", title)
-            .header("Tags", &self.config.tags)
+            .header("Tags", tags)
             .header("Priority", self.config.priority.to_string());
 
         if let Some(click_url) = &self.config.click_url {
             request = request.header("Click", click_url);
         }
 
-        // Send the message as body
+        if self.config.markdown {
+            request = request.header("Markdown", "yes");
+        }
+
+        // Send the message as body, shortened for channels that cap its length
+        let message = match self.config.max_chars {
+            Some(max_chars) => truncate_message(&message, max_chars),
+            None => message,
+        };
         request = request.body(message);
 
-        let response = request.send().await?;
+        let response = match request.send().await {
+            Ok(response) => response,
+            Err(e) => {
+                self.metrics
+                    .notify_responses_total
+                    .with_label_values(&["error"])
+                    .inc();
+                return Err(SendError::Request(e));
+            }
+        };
+
+        self.metrics
+            .notify_responses_total
+            .with_label_values(&[status_class(response.status().as_u16())])
+            .inc();
 
         if !response.status().is_success() {
-            anyhow::bail!("ntfy returned status: {}", response.status());
+            return Err(SendError::Status(response.status()));
         }
 
+        self.metrics.record_notify_success();
         Ok(())
     }
 }
 
+/// Returns the ntfy tag rating a run against the configured thresholds.
+///
+/// A run that failed them is `red`; this includes a latency above
+/// `NETSPEED_MAX_LATENCY_MS` and a rejection by the success criterion, both
+/// of which turn the run into a failure. A partial result, or jitter above
+/// `max_jitter_ms`, is `yellow`. Anything else is `green`.
+///
+/// # Examples
+///
+/// ```
+/// use netspeed_lite::notifier::quality_tag;
+/// use netspeed_lite::runner::{ErrorCategory, RunOutcome};
+///
+/// let outcome = RunOutcome::Failure(ErrorCategory::Timeout(120));
+/// assert_eq!(quality_tag(&outcome, None), "red");
+/// ```
+pub fn quality_tag(outcome: &RunOutcome, max_jitter_ms: Option<f64>) -> &'static str {
+    match outcome {
+        RunOutcome::Failure(_) => "red",
+        RunOutcome::Success(result) => {
+            let jitter_exceeded = result
+                .jitter_seconds
+                .zip(max_jitter_ms)
+                .is_some_and(|(jitter, max)| jitter * 1000.0 > max);
+            if result.missing.is_some() || jitter_exceeded {
+                "yellow"
+            } else {
+                "green"
+            }
+        }
+    }
+}
+
+/// Appends `tag` to a comma-separated ntfy tag list.
+#[cfg(feature = "notify")]
+fn append_tag(tags: &str, tag: &str) -> String {
+    if tags.is_empty() {
+        tag.to_string()
+    } else {
+        format!("{},{}", tags, tag)
+    }
+}
+
+/// Loaded latency must exceed idle latency by this factor before a
+/// notification calls out bufferbloat.
+const BUFFERBLOAT_FACTOR: f64 = 2.0;
+
+/// Returns the worst loaded latency in milliseconds if it far exceeds the idle
+/// latency, i.e. the link suffers from bufferbloat.
+fn bufferbloat_ms(result: &SpeedtestResult) -> Option<f64> {
+    let loaded = result
+        .download_latency_seconds
+        .into_iter()
+        .chain(result.upload_latency_seconds)
+        .reduce(f64::max)?;
+
+    (loaded > result.latency_seconds * BUFFERBLOAT_FACTOR).then_some(loaded * 1000.0)
+}
+
+/// Formats a speed in the given unit, or "n/a" if the direction is missing
+/// from a partial result.
+fn format_speed(result: &SpeedtestResult, direction: Direction, unit: SpeedUnit) -> String {
+    if result.missing == Some(direction) {
+        return "n/a".to_string();
+    }
+
+    let bps = match direction {
+        Direction::Download => result.download_bps,
+        Direction::Upload => result.upload_bps,
+    };
+    match unit {
+        SpeedUnit::Mbps => format!("{:.1} Mbps", bps / 1_000_000.0),
+        SpeedUnit::MBytePerSec => format!("{:.1} MB/s", bps / 8_000_000.0),
+    }
+}
+
 /// Formats a success notification message with speedtest results.
 ///
+/// Speeds are shown in Mbps; see `format_success_message_with` for other units.
 /// Converts speedtest results into a human-readable message with:
-/// - Download speed in Mbps
-/// - Upload speed in Mbps
+/// - Download speed in Mbps ("n/a" if missing from a partial result)
+/// - Upload speed in Mbps ("n/a" if missing from a partial result)
 /// - Latency in milliseconds
 /// - Duration in seconds
 /// - Jitter in milliseconds (if available)
 /// - Packet loss percentage (if available)
+/// - Loaded latency, if it is more than twice the idle latency (bufferbloat)
 ///
 /// # Arguments
 ///
@@ -201,19 +533,72 @@ impl Notifier {
 ///     latency_seconds: 0.020,
 ///     jitter_seconds: Some(0.002),
 ///     packet_loss_ratio: None,
+///     missing: None,
+///     external_ip: None,
+///     download_latency_seconds: None,
+///     upload_latency_seconds: None,
+///     interface_name: None,
+///     server_id: None,
+///     server_name: None,
+///     server_location: None,
+///     measured_at: None,
 /// };
 /// let message = format_success_message(&result, Duration::from_secs(30));
 /// assert!(message.contains("100.0 Mbps"));
 /// ```
 pub fn format_success_message(result: &SpeedtestResult, duration: Duration) -> String {
-    let download_mbps = result.download_bps / 1_000_000.0;
-    let upload_mbps = result.upload_bps / 1_000_000.0;
+    format_success_message_with(result, duration, SpeedUnit::Mbps)
+}
+
+/// Formats a success notification message with speeds in the given unit.
+///
+/// # Arguments
+///
+/// * `result` - The speedtest results to format
+/// * `duration` - How long the test took
+/// * `unit` - Unit used to display download/upload speeds
+///
+/// # Examples
+///
+/// ```
+/// use netspeed_lite::config::SpeedUnit;
+/// use netspeed_lite::notifier::format_success_message_with;
+/// use netspeed_lite::runner::SpeedtestResult;
+/// use std::time::Duration;
+///
+/// let result = SpeedtestResult {
+///     download_bps: 100_000_000.0,
+///     upload_bps: 10_000_000.0,
+///     latency_seconds: 0.020,
+///     jitter_seconds: None,
+///     packet_loss_ratio: None,
+///     missing: None,
+///     external_ip: None,
+///     download_latency_seconds: None,
+///     upload_latency_seconds: None,
+///     interface_name: None,
+///     server_id: None,
+///     server_name: None,
+///     server_location: None,
+///     measured_at: None,
+/// };
+/// let message =
+///     format_success_message_with(&result, Duration::from_secs(30), SpeedUnit::MBytePerSec);
+/// assert!(message.contains("Download: 12.5 MB/s"));
+/// ```
+pub fn format_success_message_with(
+    result: &SpeedtestResult,
+    duration: Duration,
+    unit: SpeedUnit,
+) -> String {
+    let download = format_speed(result, Direction::Download, unit);
+    let upload = format_speed(result, Direction::Upload, unit);
     let latency_ms = result.latency_seconds * 1000.0;
 
     let mut message = format!(
-        "⬇️ Download: {:.1} Mbps\n⬆️ Upload: {:.1} Mbps\n📡 Ping: {:.1} ms\n⏱️ Duration: {}s",
-        download_mbps,
-        upload_mbps,
+        "⬇️ Download: {}\n⬆️ Upload: {}\n📡 Ping: {:.1} ms\n⏱️ Duration: {}s",
+        download,
+        upload,
         latency_ms,
         duration.as_secs()
     );
@@ -227,6 +612,84 @@ pub fn format_success_message(result: &SpeedtestResult, duration: Duration) -> S
         message.push_str(&format!("\n📉 Loss: {:.1}%", loss * 100.0));
     }
 
+    if let Some(loaded_ms) = bufferbloat_ms(result) {
+        message.push_str(&format!("\n🐢 Bufferbloat: {:.1} ms under load", loaded_ms));
+    }
+
+    message
+}
+
+/// Formats a success notification message as Markdown.
+///
+/// Used instead of `format_success_message` when `NETSPEED_NTFY_MARKDOWN` is
+/// enabled. The results are rendered as a two-column table with bold labels.
+///
+/// # Arguments
+///
+/// * `result` - The speedtest results to format
+/// * `duration` - How long the test took
+/// * `unit` - Unit used to display download/upload speeds
+///
+/// # Returns
+///
+/// A Markdown string suitable for ntfy's `Markdown: yes` mode.
+///
+/// # Examples
+///
+/// ```
+/// use netspeed_lite::config::SpeedUnit;
+/// use netspeed_lite::notifier::format_success_markdown;
+/// use netspeed_lite::runner::SpeedtestResult;
+/// use std::time::Duration;
+///
+/// let result = SpeedtestResult {
+///     download_bps: 100_000_000.0,
+///     upload_bps: 10_000_000.0,
+///     latency_seconds: 0.020,
+///     jitter_seconds: None,
+///     packet_loss_ratio: None,
+///     missing: None,
+///     external_ip: None,
+///     download_latency_seconds: None,
+///     upload_latency_seconds: None,
+///     interface_name: None,
+///     server_id: None,
+///     server_name: None,
+///     server_location: None,
+///     measured_at: None,
+/// };
+/// let message = format_success_markdown(&result, Duration::from_secs(30), SpeedUnit::Mbps);
+/// assert!(message.contains("| **Download** | 100.0 Mbps |"));
+/// ```
+pub fn format_success_markdown(
+    result: &SpeedtestResult,
+    duration: Duration,
+    unit: SpeedUnit,
+) -> String {
+    let mut rows = vec![
+        ("Download", format_speed(result, Direction::Download, unit)),
+        ("Upload", format_speed(result, Direction::Upload, unit)),
+        ("Ping", format!("{:.1} ms", result.latency_seconds * 1000.0)),
+        ("Duration", format!("{}s", duration.as_secs())),
+    ];
+
+    if let Some(jitter) = result.jitter_seconds {
+        rows.push(("Jitter", format!("{:.1} ms", jitter * 1000.0)));
+    }
+
+    if let Some(loss) = result.packet_loss_ratio {
+        rows.push(("Loss", format!("{:.1}%", loss * 100.0)));
+    }
+
+    if let Some(loaded_ms) = bufferbloat_ms(result) {
+        rows.push(("Bufferbloat", format!("{:.1} ms under load", loaded_ms)));
+    }
+
+    let mut message = String::from("| Metric | Value |\n| --- | --- |");
+    for (label, value) in rows {
+        message.push_str(&format!("\n| **{}** | {} |", label, value));
+    }
+
     message
 }
 
@@ -256,9 +719,144 @@ pub fn format_failure_message(error: &ErrorCategory) -> String {
     match error {
         ErrorCategory::Timeout(seconds) => format!("timeout after {}s", seconds),
         ErrorCategory::CommandNotFound(cmd) => format!("command not found: {}", cmd),
-        ErrorCategory::CommandFailed(code) => format!("exit={}", code),
+        ErrorCategory::CommandFailed { code, stderr } if stderr.is_empty() => {
+            format!("exit={}", code)
+        }
+        ErrorCategory::CommandFailed { code, stderr } => format!("exit={}: {}", code, stderr),
         ErrorCategory::InvalidOutput(msg) => format!("invalid output: {}", msg),
         ErrorCategory::MissingFields(fields) => format!("missing fields: {}", fields),
         ErrorCategory::Internal(msg) => format!("internal error: {}", msg),
+        ErrorCategory::CriterionRejected(code) => {
+            format!("rejected by success criterion: exit={}", code)
+        }
+        ErrorCategory::SelectionTimeout(seconds) => {
+            format!("server selection timeout after {}s", seconds)
+        }
+    }
+}
+
+/// Formats a detailed failure notification message for troubleshooting.
+///
+/// Used instead of `format_failure_message` when `NETSPEED_FAILURE_VERBOSE` is
+/// enabled. Includes the full error description, the tail of the command's
+/// standard error if it failed, the command line that was run and when the
+/// failure happened.
+///
+/// # Arguments
+///
+/// * `error` - The error category that caused the failure
+/// * `speedtest` - The speedtest configuration whose command failed
+/// * `timestamp` - When the failure occurred
+///
+/// # Examples
+///
+/// ```
+/// use chrono::{TimeZone, Utc};
+/// use netspeed_lite::config::{OutputFormat, OutputStream, SpeedtestConfig};
+/// use netspeed_lite::notifier::format_failure_verbose;
+/// use netspeed_lite::runner::ErrorCategory;
+///
+/// let speedtest = SpeedtestConfig {
+///     command: "speedtest".to_string(),
+///     args: vec!["--format=json".to_string()],
+///     timeout_seconds: 120,
+///     first_output_timeout_seconds: None,
+///     selection_timeout_seconds: None,
+///     max_attempts: 1,
+///     allow_partial: false,
+///     parse_last_line: false,
+///     max_latency_ms: None,
+///     output_stream: OutputStream::Stdout,
+///     output_format: OutputFormat::Ookla,
+/// };
+/// let at = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+/// let message = format_failure_verbose(&ErrorCategory::Timeout(120), &speedtest, at);
+/// assert!(message.contains("command: speedtest --format=json"));
+/// ```
+pub fn format_failure_verbose(
+    error: &ErrorCategory,
+    speedtest: &SpeedtestConfig,
+    timestamp: DateTime<Utc>,
+) -> String {
+    let mut command = speedtest.command.clone();
+    for arg in &speedtest.args {
+        command.push(' ');
+        command.push_str(arg);
+    }
+
+    let mut message = error.to_string();
+    if let Some(stderr) = error.stderr() {
+        message.push_str("\nstderr: ");
+        message.push_str(stderr);
+    }
+
+    format!(
+        "{}\ncommand: {}\ntime: {}",
+        message,
+        command,
+        timestamp.to_rfc3339()
+    )
+}
+
+/// Formats a failure notification message as Markdown.
+///
+/// Wraps the plain `format_failure_message` text with a bold label.
+///
+/// # Examples
+///
+/// ```
+/// use netspeed_lite::notifier::format_failure_markdown;
+/// use netspeed_lite::runner::ErrorCategory;
+///
+/// let message = format_failure_markdown(&ErrorCategory::Timeout(120));
+/// assert_eq!(message, "**Speed test failed:** timeout after 120s");
+/// ```
+pub fn format_failure_markdown(error: &ErrorCategory) -> String {
+    format!("**Speed test failed:** {}", format_failure_message(error))
+}
+
+/// Buckets an HTTP status code into its class for the
+/// `netspeed_notify_responses_total` metric.
+///
+/// Codes outside the 1xx-5xx range are reported as `"error"`, the same bucket
+/// used when no response was received at all.
+///
+/// # Examples
+///
+/// ```
+/// use netspeed_lite::notifier::status_class;
+///
+/// assert_eq!(status_class(200), "2xx");
+/// assert_eq!(status_class(503), "5xx");
+/// ```
+pub fn status_class(status: u16) -> &'static str {
+    match status {
+        100..=199 => "1xx",
+        200..=299 => "2xx",
+        300..=399 => "3xx",
+        400..=499 => "4xx",
+        500..=599 => "5xx",
+        _ => "error",
+    }
+}
+
+/// Shortens `message` to at most `max_chars` characters, replacing the end
+/// with an ellipsis when it is cut. Messages that already fit are unchanged.
+///
+/// # Examples
+///
+/// ```
+/// use netspeed_lite::notifier::truncate_message;
+///
+/// assert_eq!(truncate_message("Download: 812.3 Mbps", 10), "Download:…");
+/// assert_eq!(truncate_message("OK", 10), "OK");
+/// ```
+pub fn truncate_message(message: &str, max_chars: usize) -> String {
+    if message.chars().count() <= max_chars {
+        return message.to_string();
     }
+
+    let mut truncated: String = message.chars().take(max_chars.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
 }
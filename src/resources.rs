@@ -0,0 +1,508 @@
+//! # Resource Monitoring
+//!
+//! This module samples the process's own resource usage into the resource gauges.
+//! It handles:
+//! - Sampling memory usage (RSS) and CPU usage through a platform `ResourceSampler`:
+//!   `/proc` on Linux, `proc_pidinfo`/`getrusage` on macOS.
+//! - Smoothing the CPU reading with an exponential moving average.
+//! - Running the periodic sampling loop used by the binary.
+//! - Notifying when the process's own CPU or memory usage stays high.
+//!
+//! The sampler is selected at compile time. On platforms other than Linux and
+//! macOS, samples fail and are logged.
+use crate::config::Config;
+use crate::metrics::Metrics;
+use crate::notifier::Notifier;
+use anyhow::Result;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Periodically samples process memory and CPU usage into the resource gauges,
+/// refreshes `netspeed_seconds_since_last_notify_success`, and sends high usage
+/// notifications through `notifier` when thresholds are configured.
+pub async fn monitor_resources(metrics: Metrics, config: Config, notifier: Option<Arc<Notifier>>) {
+    let mut sampler = PlatformSampler::new();
+    let mut cpu_smoother = CpuSmoother::new(config.cpu_smoothing_alpha);
+    let mut alerts = HighUsageAlerts::from_config(&config);
+
+    loop {
+        // Update Memory (RSS)
+        match sampler.memory_rss() {
+            Ok(bytes) => update_memory_gauges(&metrics, bytes),
+            Err(e) => tracing::warn!("Failed to read memory RSS: {}", e),
+        }
+
+        // Update CPU Usage
+        match sampler.cpu_usage() {
+            Ok(usage) => {
+                metrics.process_cpu_usage_raw.set(usage);
+                metrics.process_cpu_usage.set(cpu_smoother.update(usage));
+            }
+            Err(e) => tracing::warn!("Failed to read CPU usage: {}", e),
+        }
+
+        metrics.update_seconds_since_notify_success();
+        alerts.check(&metrics, notifier.as_deref()).await;
+
+        tokio::time::sleep(Duration::from_secs(config.resource_interval_seconds)).await;
+    }
+}
+
+/// Tracks one resource against a threshold and decides when to alert.
+///
+/// An alert fires once the value has exceeded `threshold` for
+/// `required_samples` consecutive samples, and then not again for the same
+/// resource until `cooldown` has passed. A sample at or below the threshold
+/// resets the streak.
+///
+/// # Examples
+///
+/// ```
+/// use netspeed_lite::resources::UsageAlert;
+/// use std::time::Duration;
+///
+/// let mut alert = UsageAlert::new(80.0, 2, Duration::from_secs(3600));
+/// assert!(!alert.record(95.0));
+/// assert!(alert.record(95.0));
+/// assert!(!alert.record(95.0)); // cooling down
+/// ```
+#[derive(Debug, Clone)]
+pub struct UsageAlert {
+    threshold: f64,
+    required_samples: u32,
+    cooldown: Duration,
+    consecutive: u32,
+    last_alert: Option<Instant>,
+}
+
+impl UsageAlert {
+    /// Creates an alert for values above `threshold`.
+    pub fn new(threshold: f64, required_samples: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            required_samples,
+            cooldown,
+            consecutive: 0,
+            last_alert: None,
+        }
+    }
+
+    /// Records a sample and returns whether a notification should be sent now.
+    pub fn record(&mut self, value: f64) -> bool {
+        if value <= self.threshold {
+            self.consecutive = 0;
+            return false;
+        }
+
+        self.consecutive = self.consecutive.saturating_add(1);
+        let cooling_down = self
+            .last_alert
+            .is_some_and(|last| last.elapsed() < self.cooldown);
+        if self.consecutive < self.required_samples || cooling_down {
+            return false;
+        }
+
+        self.last_alert = Some(Instant::now());
+        true
+    }
+}
+
+/// High usage alerts for the process's CPU and memory, from the
+/// `NETSPEED_NOTIFY_ON_HIGH_*` settings.
+#[derive(Debug, Clone)]
+pub struct HighUsageAlerts {
+    cpu: Option<UsageAlert>,
+    memory: Option<UsageAlert>,
+}
+
+impl HighUsageAlerts {
+    /// Builds the alerts enabled in `config`; resources without a threshold are
+    /// never checked.
+    pub fn from_config(config: &Config) -> Self {
+        let cooldown = Duration::from_secs(config.high_usage_cooldown_seconds);
+        let alert = |threshold| UsageAlert::new(threshold, config.high_usage_samples, cooldown);
+
+        Self {
+            cpu: config.notify_on_high_cpu.map(alert),
+            memory: config
+                .notify_on_high_memory
+                .map(|bytes| alert(bytes as f64)),
+        }
+    }
+
+    /// Checks the current resource gauges and sends a notification for each
+    /// resource whose alert fires.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use netspeed_lite::config::Config;
+    /// use netspeed_lite::metrics::Metrics;
+    /// use netspeed_lite::resources::HighUsageAlerts;
+    ///
+    /// # async {
+    /// let config = Config::from_env().unwrap();
+    /// let metrics = Metrics::new().unwrap();
+    /// let mut alerts = HighUsageAlerts::from_config(&config);
+    /// alerts.check(&metrics, None).await;
+    /// # };
+    /// ```
+    pub async fn check(&mut self, metrics: &Metrics, notifier: Option<&Notifier>) {
+        let mut messages = Vec::new();
+
+        let cpu = metrics.process_cpu_usage.get();
+        if let Some(alert) = &mut self.cpu {
+            if alert.record(cpu) {
+                messages.push(format!(
+                    "High CPU usage: {:.1}% (threshold {:.1}%)",
+                    cpu, alert.threshold
+                ));
+            }
+        }
+
+        let memory = metrics.process_memory_bytes.get();
+        if let Some(alert) = &mut self.memory {
+            if alert.record(memory) {
+                messages.push(format!(
+                    "High memory usage: {:.1} MiB (threshold {:.1} MiB)",
+                    memory / (1024.0 * 1024.0),
+                    alert.threshold / (1024.0 * 1024.0)
+                ));
+            }
+        }
+
+        for message in messages {
+            tracing::warn!("{}", message);
+            if let Some(notifier) = notifier {
+                notifier.notify_message("🔥", message).await;
+            }
+        }
+    }
+}
+
+/// Exponential smoothing of CPU usage samples.
+///
+/// Each sample moves the smoothed value `alpha` of the way towards it; the first
+/// sample seeds the value directly. An `alpha` of 1 passes samples through
+/// unchanged.
+///
+/// # Examples
+///
+/// ```
+/// use netspeed_lite::resources::CpuSmoother;
+///
+/// let mut smoother = CpuSmoother::new(0.5);
+/// assert_eq!(smoother.update(40.0), 40.0);
+/// assert_eq!(smoother.update(80.0), 60.0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct CpuSmoother {
+    alpha: f64,
+    value: Option<f64>,
+}
+
+impl CpuSmoother {
+    /// Creates a smoother with the given factor, expected in (0, 1].
+    pub fn new(alpha: f64) -> Self {
+        Self { alpha, value: None }
+    }
+
+    /// Adds a raw sample and returns the smoothed usage.
+    pub fn update(&mut self, sample: f64) -> f64 {
+        let smoothed = match self.value {
+            Some(previous) => previous + self.alpha * (sample - previous),
+            None => sample,
+        };
+        self.value = Some(smoothed);
+        smoothed
+    }
+}
+
+// --- Resource Sampling ---
+
+/// Samples the process's own memory and CPU usage.
+///
+/// `PlatformSampler` is the implementation for the platform being built for.
+pub trait ResourceSampler {
+    /// Returns the process's resident set size (RSS) in bytes.
+    fn memory_rss(&self) -> Result<u64>;
+
+    /// Returns the process's CPU usage as a percentage of the machine's total
+    /// CPU time since the previous call.
+    fn cpu_usage(&mut self) -> Result<f64>;
+}
+
+/// The `ResourceSampler` for the platform being built for.
+#[cfg(target_os = "linux")]
+pub type PlatformSampler = ProcSampler;
+
+/// The `ResourceSampler` for the platform being built for.
+#[cfg(target_os = "macos")]
+pub type PlatformSampler = MacSampler;
+
+/// The `ResourceSampler` for the platform being built for.
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub type PlatformSampler = UnsupportedSampler;
+
+/// Sets the memory gauges from an RSS reading: `netspeed_process_memory_bytes`
+/// (authoritative) and `netspeed_process_memory_mb` (MiB, for dashboards).
+///
+/// # Examples
+///
+/// ```
+/// use netspeed_lite::metrics::Metrics;
+/// use netspeed_lite::resources::update_memory_gauges;
+///
+/// let metrics = Metrics::new().unwrap();
+/// update_memory_gauges(&metrics, 64 * 1024 * 1024);
+/// assert_eq!(metrics.process_memory_mb.get(), 64.0);
+/// ```
+pub fn update_memory_gauges(metrics: &Metrics, bytes: u64) {
+    metrics.process_memory_bytes.set(bytes as f64);
+    metrics
+        .process_memory_mb
+        .set(bytes as f64 / (1024.0 * 1024.0));
+}
+
+/// Extracts the `VmRSS` value in bytes from the contents of a `/proc/<pid>/status` file.
+///
+/// # Returns
+///
+/// Returns `Err` if there is no `VmRSS` line (e.g. kernel threads, or a status
+/// file from another platform) or its value cannot be parsed, rather than
+/// reporting a misleading 0 bytes.
+///
+/// # Examples
+///
+/// ```
+/// use netspeed_lite::resources::parse_memory_rss;
+///
+/// let status = "Name:\tnetspeed-lite\nVmRSS:\t    5632 kB\n";
+/// assert_eq!(parse_memory_rss(status).unwrap(), 5632 * 1024);
+/// assert!(parse_memory_rss("Name:\tnetspeed-lite\n").is_err());
+/// ```
+pub fn parse_memory_rss(status: &str) -> Result<u64> {
+    let line = status
+        .lines()
+        .find(|line| line.starts_with("VmRSS:"))
+        .ok_or_else(|| anyhow::anyhow!("VmRSS not found in process status"))?;
+
+    // Example: VmRSS:    5632 kB
+    let kb: u64 = line
+        .split_whitespace()
+        .nth(1)
+        .ok_or_else(|| anyhow::anyhow!("VmRSS has no value"))?
+        .parse()?;
+
+    Ok(kb * 1024) // Convert kB to bytes
+}
+
+/// Samples resource usage from the Linux proc filesystem.
+///
+/// Stores the previous tick counts to calculate the CPU usage delta.
+#[cfg(target_os = "linux")]
+pub struct ProcSampler {
+    last_proc_ticks: u64,
+    last_sys_ticks: u64,
+}
+
+#[cfg(target_os = "linux")]
+impl ProcSampler {
+    /// Creates a new ProcSampler with initial tick counts of 0.
+    pub fn new() -> Self {
+        Self {
+            last_proc_ticks: 0,
+            last_sys_ticks: 0,
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Default for ProcSampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl ResourceSampler for ProcSampler {
+    /// Reads the `VmRSS` field of `/proc/self/status`, the amount of physical
+    /// memory currently in use by the process.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Err` if the file cannot be read, or the `VmRSS` field is missing
+    /// or cannot be parsed.
+    fn memory_rss(&self) -> Result<u64> {
+        let content = std::fs::read_to_string("/proc/self/status")?;
+        parse_memory_rss(&content)
+    }
+
+    /// Computes CPU usage from tick deltas:
+    /// 1. Reading process CPU ticks (utime + stime) from `/proc/self/stat`
+    /// 2. Reading total system CPU ticks from `/proc/stat`
+    /// 3. Computing the delta since the last measurement
+    /// 4. Calculating percentage: (process_delta / system_delta) * 100
+    ///
+    /// # Returns
+    ///
+    /// Returns `Err` if the proc files cannot be read or parsed, and `Ok(0.0)`
+    /// if the system delta is 0.
+    fn cpu_usage(&mut self) -> Result<f64> {
+        // 1. Read process ticks from /proc/self/stat
+        // Format: pid... utime(13) stime(14)
+        let stat_content = std::fs::read_to_string("/proc/self/stat")?;
+        let close_paren_idx = stat_content
+            .rfind(')')
+            .ok_or_else(|| anyhow::anyhow!("Invalid stat fmt"))?;
+        let after_paren = &stat_content[close_paren_idx + 1..];
+
+        // utime is index 11 (13-2), stime is index 12 (14-2) relative to parts after ')'
+        let mut parts = after_paren.split_whitespace();
+        let utime: u64 = parts
+            .nth(11)
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| anyhow::anyhow!("Failed to parse utime"))?;
+        let stime: u64 = parts
+            .next()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| anyhow::anyhow!("Failed to parse stime"))?;
+        let current_proc_ticks = utime + stime;
+
+        // 2. Read system ticks from /proc/stat
+        let sys_content = std::fs::read_to_string("/proc/stat")?;
+        let first_line = sys_content
+            .lines()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Empty /proc/stat"))?;
+        // skip "cpu" and sum all tick values
+        let current_sys_ticks: u64 = first_line
+            .split_whitespace()
+            .skip(1)
+            .filter_map(|s| s.parse::<u64>().ok())
+            .sum();
+
+        // 3. Calculate Delta
+        let delta_proc = current_proc_ticks.saturating_sub(self.last_proc_ticks);
+        let delta_sys = current_sys_ticks.saturating_sub(self.last_sys_ticks);
+
+        self.last_proc_ticks = current_proc_ticks;
+        self.last_sys_ticks = current_sys_ticks;
+
+        if delta_sys == 0 {
+            return Ok(0.0);
+        }
+
+        // Percentage = (proc_delta / sys_delta) * 100
+        // Units (jiffies) cancel out, so no need for CLK_TCK
+        Ok((delta_proc as f64 / delta_sys as f64) * 100.0)
+    }
+}
+
+/// Samples resource usage on macOS: memory from `proc_pidinfo` task info, CPU
+/// time from `getrusage`.
+#[cfg(target_os = "macos")]
+pub struct MacSampler {
+    /// Process CPU time and wall-clock time at the previous CPU sample.
+    last: Option<(Duration, Instant)>,
+}
+
+#[cfg(target_os = "macos")]
+impl MacSampler {
+    /// Creates a new MacSampler with no previous CPU sample.
+    pub fn new() -> Self {
+        Self { last: None }
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl Default for MacSampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl ResourceSampler for MacSampler {
+    /// Reads `pti_resident_size` from the process's `PROC_PIDTASKINFO`.
+    fn memory_rss(&self) -> Result<u64> {
+        let mut info = std::mem::MaybeUninit::<libc::proc_taskinfo>::zeroed();
+        let size = std::mem::size_of::<libc::proc_taskinfo>() as libc::c_int;
+        // SAFETY: `info` is a writable buffer of exactly `size` bytes, and
+        // `proc_taskinfo` is plain data for which all-zero bytes are valid.
+        let written = unsafe {
+            libc::proc_pidinfo(
+                libc::getpid(),
+                libc::PROC_PIDTASKINFO,
+                0,
+                info.as_mut_ptr().cast(),
+                size,
+            )
+        };
+        if written != size {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        // SAFETY: `proc_pidinfo` filled the whole struct.
+        Ok(unsafe { info.assume_init() }.pti_resident_size)
+    }
+
+    /// Computes CPU usage as the process's user + system CPU time since the
+    /// previous sample, divided by the wall-clock time elapsed across all cores.
+    fn cpu_usage(&mut self) -> Result<f64> {
+        let mut usage = std::mem::MaybeUninit::<libc::rusage>::zeroed();
+        // SAFETY: `usage` is a writable `rusage` for `getrusage` to fill.
+        if unsafe { libc::getrusage(libc::RUSAGE_SELF, usage.as_mut_ptr()) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        // SAFETY: `getrusage` succeeded, so the struct is initialized.
+        let usage = unsafe { usage.assume_init() };
+        let to_duration = |tv: libc::timeval| {
+            Duration::from_secs(tv.tv_sec as u64) + Duration::from_micros(tv.tv_usec as u64)
+        };
+        let cpu_time = to_duration(usage.ru_utime) + to_duration(usage.ru_stime);
+        let now = Instant::now();
+
+        let Some((last_cpu_time, last_sampled)) = self.last.replace((cpu_time, now)) else {
+            return Ok(0.0);
+        };
+
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let wall = now.duration_since(last_sampled).as_secs_f64() * cores as f64;
+        if wall == 0.0 {
+            return Ok(0.0);
+        }
+
+        let delta_cpu = cpu_time.saturating_sub(last_cpu_time).as_secs_f64();
+        Ok((delta_cpu / wall) * 100.0)
+    }
+}
+
+/// Sampler for platforms without resource sampling support; every sample fails.
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub struct UnsupportedSampler;
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+impl UnsupportedSampler {
+    /// Creates the sampler.
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+impl Default for UnsupportedSampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+impl ResourceSampler for UnsupportedSampler {
+    fn memory_rss(&self) -> Result<u64> {
+        anyhow::bail!("Resource sampling is not supported on this platform")
+    }
+
+    fn cpu_usage(&mut self) -> Result<f64> {
+        anyhow::bail!("Resource sampling is not supported on this platform")
+    }
+}
@@ -4,23 +4,81 @@
 //! It handles:
 //! - Constructing the command with proper arguments.
 //! - Executing the process and capturing stdout/stderr.
-//! - Parsing the JSON output into a `SpeedtestResult` struct.
+//! - Aborting runs that stall during server selection.
+//! - Parsing the JSON output (Ookla or librespeed-cli) into a `SpeedtestResult` struct.
 //! - Handling parsing errors and standardizing the result format.
+//! - Checking results against a user-supplied success criterion command.
+use crate::config::{OutputFormat, OutputStream, SpeedtestConfig};
 use anyhow::Result;
-use serde::Deserialize;
-use std::process::Stdio;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::process::{Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration, Instant};
 use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
 use tokio::process::Command;
 use tokio::time::timeout;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct SpeedtestResult {
     pub download_bps: f64,
     pub upload_bps: f64,
     pub latency_seconds: f64,
     pub jitter_seconds: Option<f64>,
     pub packet_loss_ratio: Option<f64>,
+    /// Direction missing from a partial result (only set when partial results are allowed).
+    pub missing: Option<Direction>,
+    /// Public IP address the test ran from, if reported and valid.
+    pub external_ip: Option<IpAddr>,
+    /// Latency measured while the download was saturating the link (bufferbloat).
+    pub download_latency_seconds: Option<f64>,
+    /// Latency measured while the upload was saturating the link (bufferbloat).
+    pub upload_latency_seconds: Option<f64>,
+    /// Name of the network interface the test ran on (e.g. `eth0`), if reported.
+    pub interface_name: Option<String>,
+    /// Ookla ID of the server the test ran against, if reported.
+    pub server_id: Option<u32>,
+    /// Name of the server the test ran against (e.g. the hosting ISP), if reported.
+    pub server_name: Option<String>,
+    /// Location (usually the city) of the server the test ran against, if reported.
+    pub server_location: Option<String>,
+    /// When the CLI reports the test ran, if reported and valid. Serialized as RFC3339.
+    #[serde(serialize_with = "serialize_measured_at")]
+    pub measured_at: Option<DateTime<Utc>>,
+}
+
+/// Serializes `SpeedtestResult::measured_at` as an RFC3339 string, since chrono
+/// is built without serde support.
+fn serialize_measured_at<S: serde::Serializer>(
+    measured_at: &Option<DateTime<Utc>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match measured_at {
+        Some(time) => serializer.serialize_some(&time.to_rfc3339()),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// A transfer direction of a speed test.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Download,
+    Upload,
+}
+
+/// Options controlling how speedtest output is parsed.
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Accept output where exactly one of download/upload is missing.
+    pub allow_partial: bool,
+    /// When the output isn't a single JSON document, parse its last non-empty
+    /// line instead (the summary that follows line-by-line progress objects).
+    pub parse_last_line: bool,
+    /// Reject output whose latency exceeds this many milliseconds.
+    pub max_latency_ms: Option<f64>,
 }
 
 #[derive(Debug)]
@@ -37,8 +95,10 @@ pub enum ErrorCategory {
     #[error("Command not found: {0}")]
     CommandNotFound(String),
 
-    #[error("Command failed with exit code {0}")]
-    CommandFailed(i32),
+    /// The command exited with a nonzero status; `stderr` holds the end of its
+    /// standard error (at most `STDERR_TAIL_BYTES`), trimmed.
+    #[error("Command failed with exit code {code}")]
+    CommandFailed { code: i32, stderr: String },
 
     #[error("Invalid output: {0}")]
     InvalidOutput(String),
@@ -48,6 +108,87 @@ pub enum ErrorCategory {
 
     #[error("Internal error: {0}")]
     Internal(String),
+
+    #[error("Rejected by success criterion with exit code {0}")]
+    CriterionRejected(i32),
+
+    #[error("Server selection stalled: no server chosen within {0} seconds")]
+    SelectionTimeout(u64),
+}
+
+impl ErrorCategory {
+    /// Returns the stage at which the run failed, used as the `stage` label on
+    /// `netspeed_errors_total`.
+    ///
+    /// `"parse"` means the CLI ran to completion but its output could not be
+    /// interpreted (typically a CLI version changing its JSON format);
+    /// `"execute"` covers everything that went wrong running the command itself;
+    /// `"criterion"` means the result was rejected by the success criterion command.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use netspeed_lite::runner::ErrorCategory;
+    ///
+    /// assert_eq!(ErrorCategory::Timeout(120).stage(), "execute");
+    /// assert_eq!(ErrorCategory::InvalidOutput("eof".into()).stage(), "parse");
+    /// ```
+    pub fn stage(&self) -> &'static str {
+        match self {
+            ErrorCategory::InvalidOutput(_) | ErrorCategory::MissingFields(_) => "parse",
+            ErrorCategory::Timeout(_)
+            | ErrorCategory::SelectionTimeout(_)
+            | ErrorCategory::CommandNotFound(_)
+            | ErrorCategory::CommandFailed { .. }
+            | ErrorCategory::Internal(_) => "execute",
+            ErrorCategory::CriterionRejected(_) => "criterion",
+        }
+    }
+
+    /// Returns the tail of the command's standard error for a failed command,
+    /// or `None` if it printed nothing there or the failure was something else.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use netspeed_lite::runner::ErrorCategory;
+    ///
+    /// let error = ErrorCategory::CommandFailed {
+    ///     code: 1,
+    ///     stderr: "Limit reached".to_string(),
+    /// };
+    /// assert_eq!(error.stderr(), Some("Limit reached"));
+    /// assert_eq!(ErrorCategory::Timeout(120).stderr(), None);
+    /// ```
+    pub fn stderr(&self) -> Option<&str> {
+        match self {
+            ErrorCategory::CommandFailed { stderr, .. } if !stderr.is_empty() => Some(stderr),
+            _ => None,
+        }
+    }
+
+    /// Returns whether a failure may be transient and is worth retrying when
+    /// `NETSPEED_MAX_ATTEMPTS` allows it.
+    ///
+    /// A missing command or unparseable output will fail the same way again,
+    /// so only timeouts, command failures and internal errors are retried.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use netspeed_lite::runner::ErrorCategory;
+    ///
+    /// assert!(ErrorCategory::Timeout(120).is_retryable());
+    /// assert!(!ErrorCategory::CommandNotFound("speedtest".into()).is_retryable());
+    /// ```
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ErrorCategory::Timeout(_)
+                | ErrorCategory::CommandFailed { .. }
+                | ErrorCategory::Internal(_)
+        )
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -55,11 +196,53 @@ struct SpeedtestOutput {
     download: Option<BandwidthInfo>,
     upload: Option<BandwidthInfo>,
     ping: Option<PingInfo>,
+    interface: Option<InterfaceInfo>,
+    server: Option<ServerInfo>,
+    timestamp: Option<String>, // RFC3339, when the test ran
+    #[serde(rename = "packetLoss")]
+    packet_loss: Option<f64>, // percentage, only in newer CLI versions
+}
+
+/// One result from `librespeed-cli --json`.
+#[derive(Debug, Deserialize)]
+struct LibrespeedOutput {
+    download: Option<f64>, // in Mbps
+    upload: Option<f64>,   // in Mbps
+    ping: Option<f64>,     // in milliseconds
+    jitter: Option<f64>,   // in milliseconds
+    client: Option<LibrespeedClient>,
+    server: Option<LibrespeedServer>,
+    timestamp: Option<String>, // RFC3339, when the test ran
+}
+
+#[derive(Debug, Deserialize)]
+struct LibrespeedServer {
+    name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LibrespeedClient {
+    ip: Option<String>,
+}
+
+/// librespeed-cli prints a list of results (one per server tested); older
+/// versions print a single object.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum LibrespeedDocument {
+    List(Vec<LibrespeedOutput>),
+    Single(LibrespeedOutput),
 }
 
 #[derive(Debug, Deserialize)]
 struct BandwidthInfo {
     bandwidth: Option<f64>, // in bytes per second
+    latency: Option<LoadedLatencyInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LoadedLatencyInfo {
+    iqm: Option<f64>, // in milliseconds
 }
 
 #[derive(Debug, Deserialize)]
@@ -68,63 +251,295 @@ struct PingInfo {
     jitter: Option<f64>,  // in milliseconds
 }
 
+#[derive(Debug, Default, Deserialize)]
+struct ServerInfo {
+    id: Option<u32>,
+    name: Option<String>,
+    location: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct InterfaceInfo {
+    #[serde(rename = "externalIp")]
+    external_ip: Option<String>,
+    // `macAddr` is deliberately not deserialized so it can never be exposed
+    name: Option<String>,
+}
+
 pub struct RunResult {
     pub outcome: RunOutcome,
     pub duration: Duration,
+    /// Raw output of the command, if it ran to completion: the stream selected
+    /// by `NETSPEED_OUTPUT_STREAM` (stdout for `both`).
+    pub output: Option<String>,
+    /// Number of failed attempts that were retried before this outcome.
+    pub retries: u32,
+}
+
+/// Resolves a bare command name to an absolute path using `PATH`.
+///
+/// Commands containing a path separator are returned unchanged. This lets startup
+/// fail fast with a clear error instead of a `CommandNotFound` on the first run.
+///
+/// # Returns
+///
+/// Returns the absolute path of the command, or `Err` if it cannot be found in `PATH`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use netspeed_lite::runner::resolve_command;
+///
+/// let path = resolve_command("speedtest").expect("speedtest not installed");
+/// println!("Using {}", path);
+/// ```
+pub fn resolve_command(command: &str) -> Result<String> {
+    if command.contains(std::path::MAIN_SEPARATOR) {
+        return Ok(command.to_string());
+    }
+
+    let path = which::which(command)
+        .map_err(|e| anyhow::anyhow!("Command '{}' not found in PATH: {}", command, e))?;
+
+    Ok(path.to_string_lossy().into_owned())
 }
 
 /// Executes a speedtest command and returns the result.
 ///
 /// This function spawns the speedtest process, waits for it to complete (with timeout),
 /// captures its output, parses the JSON result, and returns structured data.
+/// Failures that may be transient (see `ErrorCategory::is_retryable`) are
+/// retried after a short delay until `max_attempts` attempts have been made.
 ///
 /// # Arguments
 ///
-/// * `command` - The command to execute (e.g., "speedtest")
-/// * `args` - Command-line arguments to pass to the command
-/// * `timeout_seconds` - Maximum time to wait for the command to complete
+/// * `config` - Speedtest configuration (command, arguments, timeout, parse options)
 ///
 /// # Returns
 ///
 /// Returns a `RunResult` containing:
 /// - `outcome`: Either `Success(SpeedtestResult)` with parsed metrics, or `Failure(ErrorCategory)` with error details
-/// - `duration`: How long the command took to execute
+/// - `duration`: How long the command took to execute, across all attempts
+/// - `output`: The command's raw output from the last attempt, if it ran to completion
+/// - `retries`: How many failed attempts were retried
 ///
 /// # Examples
 ///
 /// ```no_run
+/// use netspeed_lite::config::{OutputFormat, OutputStream, SpeedtestConfig};
 /// use netspeed_lite::runner::run_speedtest;
 ///
 /// # async {
-/// let args = vec!["--format=json".to_string(), "--accept-license".to_string()];
-/// let result = run_speedtest("speedtest", &args, 120).await;
+/// let config = SpeedtestConfig {
+///     command: "speedtest".to_string(),
+///     args: vec!["--format=json".to_string(), "--accept-license".to_string()],
+///     timeout_seconds: 120,
+///     first_output_timeout_seconds: Some(30),
+///     selection_timeout_seconds: None,
+///     max_attempts: 1,
+///     allow_partial: false,
+///     parse_last_line: false,
+///     max_latency_ms: None,
+///     output_stream: OutputStream::Stdout,
+///     output_format: OutputFormat::Ookla,
+/// };
+/// let result = run_speedtest(&config).await;
 /// println!("Test duration: {:?}", result.duration);
 /// # };
 /// ```
-pub async fn run_speedtest(command: &str, args: &[String], timeout_seconds: u64) -> RunResult {
+pub async fn run_speedtest(config: &SpeedtestConfig) -> RunResult {
     let start = Instant::now();
+    let mut retries = 0;
+
+    loop {
+        let (outcome, output) = run_attempt(config).await;
+
+        if let RunOutcome::Failure(e) = &outcome {
+            if e.is_retryable() && retries + 1 < config.max_attempts {
+                retries += 1;
+                tracing::warn!(
+                    error = %e,
+                    attempt = retries,
+                    max_attempts = config.max_attempts,
+                    "Speed test attempt failed, retrying in {:?}",
+                    RETRY_DELAY
+                );
+                tokio::time::sleep(RETRY_DELAY).await;
+                continue;
+            }
+        }
+
+        return RunResult {
+            outcome,
+            duration: start.elapsed(),
+            output,
+            retries,
+        };
+    }
+}
+
+/// Delay between a failed attempt and its retry.
+const RETRY_DELAY: Duration = Duration::from_secs(2);
 
-    let outcome = match execute_speedtest(command, args, timeout_seconds).await {
+/// Runs the speedtest command once, returning the outcome and the raw output
+/// of the selected stream if the command ran to completion.
+async fn run_attempt(config: &SpeedtestConfig) -> (RunOutcome, Option<String>) {
+    let (result, output) = match execute_speedtest(config).await {
+        Ok(output) => {
+            let raw = match config.output_stream {
+                OutputStream::Stderr => &output.stderr,
+                OutputStream::Stdout | OutputStream::Both => &output.stdout,
+            };
+            let raw = String::from_utf8_lossy(raw).into_owned();
+            (interpret_output(config, &output), Some(raw))
+        }
+        Err(e) => (Err(e), None),
+    };
+
+    let outcome = match result {
         Ok(result) => RunOutcome::Success(result),
         Err(e) => RunOutcome::Failure(e),
     };
 
-    let duration = start.elapsed();
-
-    RunResult { outcome, duration }
+    (outcome, output)
 }
 
-async fn execute_speedtest(
+/// Maximum time the success criterion command may take before the run fails.
+const CRITERION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Runs the success criterion command against a parsed result.
+///
+/// The command is run with `sh -c`, receiving the result as JSON on stdin.
+/// A zero exit status accepts the result; anything else rejects it, which lets
+/// users apply arbitrary rules to otherwise valid measurements.
+///
+/// # Returns
+///
+/// Returns `Ok(())` if the command accepted the result, or:
+/// - `CriterionRejected(code)` if it exited with a nonzero status
+/// - `Timeout` if it ran longer than 30 seconds
+/// - `Internal` if it could not be spawned or fed its input
+///
+/// # Examples
+///
+/// ```no_run
+/// use netspeed_lite::runner::{check_success_criterion, parse_speedtest_output};
+///
+/// # async {
+/// # let json = "";
+/// let result = parse_speedtest_output(json).unwrap();
+/// let verdict =
+///     check_success_criterion("jq -e '.download_bps > 100000000' >/dev/null", &result).await;
+/// # };
+/// ```
+pub async fn check_success_criterion(
     command: &str,
-    args: &[String],
-    timeout_seconds: u64,
-) -> Result<SpeedtestResult, ErrorCategory> {
+    result: &SpeedtestResult,
+) -> Result<(), ErrorCategory> {
+    let input = serde_json::to_vec(result)
+        .map_err(|e| ErrorCategory::Internal(format!("Failed to serialize result: {}", e)))?;
+
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| {
+            ErrorCategory::Internal(format!("Failed to spawn success criterion: {}", e))
+        })?;
+
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let run = async {
+        // A criterion may exit without reading its input; that's not an error
+        let _ = stdin.write_all(&input).await;
+        drop(stdin);
+        child.wait().await.map_err(|e| {
+            ErrorCategory::Internal(format!("Failed to wait for success criterion: {}", e))
+        })
+    };
+
+    let status = timeout(CRITERION_TIMEOUT, run)
+        .await
+        .map_err(|_| ErrorCategory::Timeout(CRITERION_TIMEOUT.as_secs()))??;
+
+    if !status.success() {
+        return Err(ErrorCategory::CriterionRejected(
+            status.code().unwrap_or(-1),
+        ));
+    }
+
+    Ok(())
+}
+
+/// A line of the Ookla CLI's progress stream (`--format=json --progress=yes`),
+/// which reports each stage of the test as a JSON object before the result.
+#[derive(Debug, Deserialize)]
+struct ProgressEvent {
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+/// Returns whether speedtest output shows that server selection has completed:
+/// a `testStart` event in the JSON progress stream, or the `Server:` line of
+/// the human-readable output.
+///
+/// Without a progress stream the Ookla CLI prints nothing before its result,
+/// which is why `NETSPEED_SELECTION_TIMEOUT_SECONDS` turns `--progress=yes` on.
+///
+/// # Examples
+///
+/// ```
+/// use netspeed_lite::runner::selection_complete;
+///
+/// assert!(selection_complete(b"   Server: Example ISP - Amsterdam (id: 1234)\n"));
+/// assert!(selection_complete(br#"{"type":"testStart","server":{"id":1234}}"#));
+/// assert!(!selection_complete(b"Retrieving server list...\n"));
+/// ```
+pub fn selection_complete(output: &[u8]) -> bool {
+    String::from_utf8_lossy(output).lines().any(|line| {
+        let line = line.trim();
+        line.starts_with("Server:")
+            || serde_json::from_str::<ProgressEvent>(line)
+                .is_ok_and(|event| event.kind == "testStart")
+    })
+}
+
+/// Reads `reader` to the end into `buf`, setting `seen` once it produces any
+/// output and `selected` once the output shows that server selection has
+/// completed.
+async fn read_watching<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+    seen: &AtomicBool,
+    selected: &AtomicBool,
+) -> std::io::Result<()> {
+    let mut chunk = [0u8; 1024];
+    loop {
+        if !selected.load(Ordering::Relaxed) && selection_complete(buf) {
+            selected.store(true, Ordering::Relaxed);
+        }
+        let n = reader.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        seen.store(true, Ordering::Relaxed);
+        buf.extend_from_slice(&chunk[..n]);
+    }
+}
+
+async fn execute_speedtest(config: &SpeedtestConfig) -> Result<Output, ErrorCategory> {
+    let command = config.command.as_str();
+    let args = &config.args;
+    let timeout_seconds = config.timeout_seconds;
     let timeout_duration = Duration::from_secs(timeout_seconds);
 
-    let child = Command::new(command)
+    let mut child = Command::new(command)
         .args(args)
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
+        .kill_on_drop(true)
         .spawn()
         .map_err(|e| {
             if e.kind() == std::io::ErrorKind::NotFound {
@@ -134,18 +549,140 @@ async fn execute_speedtest(
             }
         })?;
 
-    let output = timeout(timeout_duration, child.wait_with_output())
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+
+    // Read both streams incrementally so a command that never starts
+    // producing output, or never gets past server selection, can be abandoned
+    // long before the total timeout. Dropping the child on any early return
+    // kills the process.
+    let stdout_seen = AtomicBool::new(false);
+    let stderr_seen = AtomicBool::new(false);
+    let selected = AtomicBool::new(false);
+    let read_stdout = async {
+        let mut buf = Vec::new();
+        read_watching(&mut stdout, &mut buf, &stdout_seen, &selected)
+            .await
+            .map_err(|e| ErrorCategory::Internal(format!("Failed to read stdout: {}", e)))?;
+        Ok::<_, ErrorCategory>(buf)
+    };
+
+    let read_stderr = async {
+        let mut buf = Vec::new();
+        read_watching(&mut stderr, &mut buf, &stderr_seen, &selected)
+            .await
+            .map_err(|e| ErrorCategory::Internal(format!("Failed to read stderr: {}", e)))?;
+        Ok::<_, ErrorCategory>(buf)
+    };
+
+    // The first output is expected on the stream carrying the result
+    let first_output_deadline = async {
+        let Some(first_output_seconds) = config.first_output_timeout_seconds else {
+            return std::future::pending().await;
+        };
+        tokio::time::sleep(Duration::from_secs(first_output_seconds)).await;
+        let seen = match config.output_stream {
+            OutputStream::Stdout => stdout_seen.load(Ordering::Relaxed),
+            OutputStream::Stderr => stderr_seen.load(Ordering::Relaxed),
+            OutputStream::Both => {
+                stdout_seen.load(Ordering::Relaxed) || stderr_seen.load(Ordering::Relaxed)
+            }
+        };
+        if seen {
+            return std::future::pending().await;
+        }
+        tracing::warn!(
+            "Speedtest produced no output within {} seconds",
+            first_output_seconds
+        );
+        Err::<(), _>(ErrorCategory::Timeout(first_output_seconds))
+    };
+
+    let selection_deadline = async {
+        let Some(selection_seconds) = config.selection_timeout_seconds else {
+            return std::future::pending().await;
+        };
+        tokio::time::sleep(Duration::from_secs(selection_seconds)).await;
+        if selected.load(Ordering::Relaxed) {
+            return std::future::pending().await;
+        }
+        tracing::warn!(
+            "Speedtest did not finish server selection within {} seconds",
+            selection_seconds
+        );
+        Err::<(), _>(ErrorCategory::SelectionTimeout(selection_seconds))
+    };
+
+    let collect = async {
+        let (stdout, stderr) = tokio::select! {
+            output = async { tokio::try_join!(read_stdout, read_stderr) } => output?,
+            Err(e) = first_output_deadline => return Err(e),
+            Err(e) = selection_deadline => return Err(e),
+        };
+        let status = child
+            .wait()
+            .await
+            .map_err(|e| ErrorCategory::Internal(format!("Failed to wait for command: {}", e)))?;
+        Ok::<_, ErrorCategory>(Output {
+            status,
+            stdout,
+            stderr,
+        })
+    };
+
+    timeout(timeout_duration, collect)
         .await
         .map_err(|_| ErrorCategory::Timeout(timeout_seconds))?
-        .map_err(|e| ErrorCategory::Internal(format!("Failed to wait for command: {}", e)))?;
+}
+
+/// Bytes of standard error kept in `ErrorCategory::CommandFailed`, enough for
+/// the CLI's closing error message (license, network or server errors).
+pub const STDERR_TAIL_BYTES: usize = 500;
+
+/// Returns the last `STDERR_TAIL_BYTES` of `stderr` as trimmed text, starting
+/// on a character boundary.
+fn stderr_tail(stderr: &[u8]) -> String {
+    let mut start = stderr.len().saturating_sub(STDERR_TAIL_BYTES);
+    // Skip UTF-8 continuation bytes of a character cut in half
+    while stderr.get(start).is_some_and(|byte| byte & 0xC0 == 0x80) {
+        start += 1;
+    }
+    String::from_utf8_lossy(&stderr[start..]).trim().to_string()
+}
 
+/// Turns the output of a completed command into a result: a nonzero exit is a
+/// failure, otherwise the stream selected by `output_stream` is parsed.
+fn interpret_output(
+    config: &SpeedtestConfig,
+    output: &Output,
+) -> Result<SpeedtestResult, ErrorCategory> {
     if !output.status.success() {
-        let exit_code = output.status.code().unwrap_or(-1);
-        return Err(ErrorCategory::CommandFailed(exit_code));
+        return Err(ErrorCategory::CommandFailed {
+            code: output.status.code().unwrap_or(-1),
+            stderr: stderr_tail(&output.stderr),
+        });
     }
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    parse_speedtest_output(&stdout)
+    let options = ParseOptions {
+        allow_partial: config.allow_partial,
+        parse_last_line: config.parse_last_line,
+        max_latency_ms: config.max_latency_ms,
+    };
+    let parse = |stream: &[u8]| {
+        let text = String::from_utf8_lossy(stream);
+        match config.output_format {
+            OutputFormat::Ookla => parse_speedtest_output_with(&text, &options),
+            OutputFormat::Librespeed => parse_librespeed_output_with(&text, &options),
+        }
+    };
+    match config.output_stream {
+        OutputStream::Stdout => parse(&output.stdout),
+        OutputStream::Stderr => parse(&output.stderr),
+        // Report stdout's error if neither stream holds a result
+        OutputStream::Both => {
+            parse(&output.stdout).or_else(|e| parse(&output.stderr).map_err(|_| e))
+        }
+    }
 }
 
 /// Parses Ookla Speedtest CLI JSON output into a `SpeedtestResult`.
@@ -189,22 +726,75 @@ async fn execute_speedtest(
 /// assert_eq!(result.download_bps, 812300000.0);
 /// ```
 pub fn parse_speedtest_output(json_str: &str) -> Result<SpeedtestResult, ErrorCategory> {
+    parse_speedtest_output_with(json_str, &ParseOptions::default())
+}
+
+/// Parses Ookla Speedtest CLI JSON output using the given `ParseOptions`.
+///
+/// With `allow_partial`, output missing exactly one of `download.bandwidth` /
+/// `upload.bandwidth` is accepted: the missing speed is reported as 0 and its
+/// direction recorded in `SpeedtestResult::missing`. Output missing both is still
+/// rejected with `MissingFields`.
+///
+/// With `max_latency_ms`, a latency above the bound is treated as a broken
+/// measurement and rejected with `InvalidOutput`.
+///
+/// With `parse_last_line`, output that fails to parse as a whole (e.g. progress
+/// objects emitted one per line before the summary) is parsed from its last
+/// non-empty line instead.
+///
+/// # Examples
+///
+/// ```
+/// use netspeed_lite::runner::{parse_speedtest_output_with, Direction, ParseOptions};
+///
+/// let json = r#"{
+///     "download": {"bandwidth": 101537500},
+///     "ping": {"latency": 18.4}
+/// }"#;
+///
+/// let options = ParseOptions {
+///     allow_partial: true,
+///     ..Default::default()
+/// };
+/// let result = parse_speedtest_output_with(json, &options).unwrap();
+/// assert_eq!(result.missing, Some(Direction::Upload));
+/// ```
+pub fn parse_speedtest_output_with(
+    json_str: &str,
+    options: &ParseOptions,
+) -> Result<SpeedtestResult, ErrorCategory> {
     let output: SpeedtestOutput = serde_json::from_str(json_str)
+        .or_else(|e| {
+            let last_line = json_str.lines().rev().find(|line| !line.trim().is_empty());
+            match last_line {
+                Some(line) if options.parse_last_line => serde_json::from_str(line),
+                _ => Err(e),
+            }
+        })
         .map_err(|e| ErrorCategory::InvalidOutput(format!("JSON parse error: {}", e)))?;
 
-    // Extract download bandwidth (bytes/s -> bits/s)
-    let download_bps = output
-        .download
-        .and_then(|d| d.bandwidth)
-        .ok_or_else(|| ErrorCategory::MissingFields("download.bandwidth".to_string()))?
-        * 8.0; // Convert bytes to bits
+    // Extract optional loaded latency (ms -> seconds), the interquartile mean
+    // of latency samples taken while each direction was saturating the link
+    let loaded_latency = |info: &Option<BandwidthInfo>| {
+        info.as_ref()
+            .and_then(|i| i.latency.as_ref())
+            .and_then(|l| l.iqm)
+            .map(|ms| ms / 1000.0)
+    };
+    let download_latency_seconds = loaded_latency(&output.download);
+    let upload_latency_seconds = loaded_latency(&output.upload);
+
+    // Extract bandwidths (bytes/s -> bits/s)
+    let download = output.download.and_then(|d| d.bandwidth).map(|b| b * 8.0);
+    let upload = output.upload.and_then(|u| u.bandwidth).map(|b| b * 8.0);
 
-    // Extract upload bandwidth (bytes/s -> bits/s)
-    let upload_bps = output
-        .upload
-        .and_then(|u| u.bandwidth)
-        .ok_or_else(|| ErrorCategory::MissingFields("upload.bandwidth".to_string()))?
-        * 8.0; // Convert bytes to bits
+    let (download_bps, upload_bps, missing) = resolve_directions(
+        download,
+        upload,
+        options,
+        ["download.bandwidth", "upload.bandwidth"],
+    )?;
 
     // Extract latency (ms -> seconds)
     let latency_seconds = output
@@ -221,33 +811,203 @@ pub fn parse_speedtest_output(json_str: &str) -> Result<SpeedtestResult, ErrorCa
         .and_then(|p| p.jitter)
         .map(|j| j / 1000.0);
 
-    // Validate values
-    if download_bps < 0.0 || download_bps.is_nan() {
+    // Extract optional packet loss (percent -> ratio)
+    let packet_loss_ratio = output.packet_loss.map(|percent| percent / 100.0);
+
+    // Extract optional interface details, ignoring an external IP that isn't an address
+    let (external_ip, interface_name) = match output.interface {
+        Some(interface) => (
+            interface.external_ip.and_then(|ip| ip.parse().ok()),
+            interface.name.filter(|name| !name.is_empty()),
+        ),
+        None => (None, None),
+    };
+
+    let server = output.server.unwrap_or_default();
+
+    validate_result(
+        SpeedtestResult {
+            download_bps,
+            upload_bps,
+            latency_seconds,
+            jitter_seconds,
+            packet_loss_ratio,
+            missing,
+            external_ip,
+            download_latency_seconds,
+            upload_latency_seconds,
+            interface_name,
+            server_id: server.id,
+            server_name: server.name.filter(|name| !name.is_empty()),
+            server_location: server.location.filter(|location| !location.is_empty()),
+            measured_at: parse_measured_at(output.timestamp.as_deref()),
+        },
+        options,
+    )
+}
+
+/// Parses `librespeed-cli --json` output into a `SpeedtestResult`.
+///
+/// librespeed-cli reports speeds in Mbps and ping/jitter in milliseconds; they
+/// are converted to bits per second and seconds like the Ookla output. When the
+/// output lists several servers, the first result is used.
+///
+/// # Returns
+///
+/// Returns `Err(ErrorCategory)` for the same reasons as `parse_speedtest_output`,
+/// with missing fields named after librespeed's (`download`, `upload`, `ping`).
+///
+/// # Examples
+///
+/// ```
+/// use netspeed_lite::runner::parse_librespeed_output;
+///
+/// let json = r#"[{"ping": 18.4, "jitter": 2.1, "upload": 42.1, "download": 812.3}]"#;
+///
+/// let result = parse_librespeed_output(json).unwrap();
+/// assert_eq!(result.download_bps, 812_300_000.0);
+/// ```
+pub fn parse_librespeed_output(json_str: &str) -> Result<SpeedtestResult, ErrorCategory> {
+    parse_librespeed_output_with(json_str, &ParseOptions::default())
+}
+
+/// Parses `librespeed-cli --json` output with the given options; see
+/// `parse_speedtest_output_with` for their meaning.
+pub fn parse_librespeed_output_with(
+    json_str: &str,
+    options: &ParseOptions,
+) -> Result<SpeedtestResult, ErrorCategory> {
+    let document: LibrespeedDocument = serde_json::from_str(json_str)
+        .map_err(|e| ErrorCategory::InvalidOutput(format!("JSON parse error: {}", e)))?;
+    let output = match document {
+        LibrespeedDocument::List(results) => results
+            .into_iter()
+            .next()
+            .ok_or_else(|| ErrorCategory::InvalidOutput("No results in output".to_string()))?,
+        LibrespeedDocument::Single(result) => result,
+    };
+
+    // Mbps -> bits/s
+    let (download_bps, upload_bps, missing) = resolve_directions(
+        output.download.map(|mbps| mbps * 1_000_000.0),
+        output.upload.map(|mbps| mbps * 1_000_000.0),
+        options,
+        ["download", "upload"],
+    )?;
+
+    // ms -> seconds
+    let latency_seconds = output
+        .ping
+        .ok_or_else(|| ErrorCategory::MissingFields("ping".to_string()))?
+        / 1000.0;
+    let jitter_seconds = output.jitter.map(|ms| ms / 1000.0);
+
+    let external_ip = output
+        .client
+        .and_then(|client| client.ip)
+        .and_then(|ip| ip.parse().ok());
+    let server_name = output
+        .server
+        .and_then(|server| server.name)
+        .filter(|name| !name.is_empty());
+    let measured_at = parse_measured_at(output.timestamp.as_deref());
+
+    validate_result(
+        SpeedtestResult {
+            download_bps,
+            upload_bps,
+            latency_seconds,
+            jitter_seconds,
+            packet_loss_ratio: None,
+            missing,
+            external_ip,
+            download_latency_seconds: None,
+            upload_latency_seconds: None,
+            interface_name: None,
+            server_id: None,
+            server_name,
+            server_location: None,
+            measured_at,
+        },
+        options,
+    )
+}
+
+/// Parses the RFC3339 timestamp a CLI reports for its test, ignoring one that
+/// doesn't parse so the caller falls back to the completion time.
+fn parse_measured_at(timestamp: Option<&str>) -> Option<DateTime<Utc>> {
+    timestamp
+        .and_then(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok())
+        .map(|time| time.with_timezone(&Utc))
+}
+
+/// Splits the optional download/upload speeds of a parsed result into the
+/// reported speeds and the missing direction, honoring `allow_partial`.
+/// `fields` names the download and upload fields for `MissingFields` errors.
+fn resolve_directions(
+    download: Option<f64>,
+    upload: Option<f64>,
+    options: &ParseOptions,
+    fields: [&str; 2],
+) -> Result<(f64, f64, Option<Direction>), ErrorCategory> {
+    match (download, upload) {
+        (Some(download), Some(upload)) => Ok((download, upload, None)),
+        (Some(download), None) if options.allow_partial => {
+            Ok((download, 0.0, Some(Direction::Upload)))
+        }
+        (None, Some(upload)) if options.allow_partial => {
+            Ok((0.0, upload, Some(Direction::Download)))
+        }
+        (None, _) => Err(ErrorCategory::MissingFields(fields[0].to_string())),
+        (_, None) => Err(ErrorCategory::MissingFields(fields[1].to_string())),
+    }
+}
+
+/// Rejects results with impossible values, or a latency above
+/// `max_latency_ms`, whatever output format they were parsed from.
+fn validate_result(
+    result: SpeedtestResult,
+    options: &ParseOptions,
+) -> Result<SpeedtestResult, ErrorCategory> {
+    if result.download_bps < 0.0 || result.download_bps.is_nan() {
         return Err(ErrorCategory::InvalidOutput(format!(
             "Invalid download speed: {}",
-            download_bps
+            result.download_bps
         )));
     }
 
-    if upload_bps < 0.0 || upload_bps.is_nan() {
+    if result.upload_bps < 0.0 || result.upload_bps.is_nan() {
         return Err(ErrorCategory::InvalidOutput(format!(
             "Invalid upload speed: {}",
-            upload_bps
+            result.upload_bps
         )));
     }
 
-    if latency_seconds < 0.0 || latency_seconds.is_nan() {
+    if result.latency_seconds < 0.0 || result.latency_seconds.is_nan() {
         return Err(ErrorCategory::InvalidOutput(format!(
             "Invalid latency: {}",
-            latency_seconds
+            result.latency_seconds
         )));
     }
 
-    Ok(SpeedtestResult {
-        download_bps,
-        upload_bps,
-        latency_seconds,
-        jitter_seconds,
-        packet_loss_ratio: None, // Ookla CLI doesn't provide packet loss
-    })
+    if let Some(loss) = result.packet_loss_ratio {
+        if !(0.0..=1.0).contains(&loss) {
+            return Err(ErrorCategory::InvalidOutput(format!(
+                "Invalid packet loss ratio: {}",
+                loss
+            )));
+        }
+    }
+
+    if let Some(max_latency_ms) = options.max_latency_ms {
+        let latency_ms = result.latency_seconds * 1000.0;
+        if latency_ms > max_latency_ms {
+            return Err(ErrorCategory::InvalidOutput(format!(
+                "Latency {} ms exceeds maximum of {} ms",
+                latency_ms, max_latency_ms
+            )));
+        }
+    }
+
+    Ok(result)
 }
@@ -7,23 +7,418 @@
 //! 3. `Cron`: Runs according to a standard Cron expression.
 //!
 //! It provides `calculate_next_run` to determine the next execution time based on the selected mode.
-use crate::config::{Config, ScheduleMode};
+use crate::config::{Config, ScheduleConfig, ScheduleMode, SpeedtestConfig};
+use crate::history::{RunHistory, RunRecord};
 use crate::metrics::Metrics;
-use crate::notifier::Notifier;
-use crate::runner::{run_speedtest, RunOutcome};
+use crate::notifier::{format_failure_verbose, Notifier};
+use crate::runner::{check_success_criterion, run_speedtest, Direction, ErrorCategory, RunOutcome};
+use crate::webhook::{HealthTransition, HealthWebhook};
 use chrono::{DateTime, Datelike, Duration, TimeZone, Timelike, Utc};
 use chrono_tz::Tz;
 use cron::Schedule;
+use serde::Serialize;
+use std::collections::VecDeque;
 use std::str::FromStr;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::time::Instant;
+use tokio::sync::{mpsc, Notify, Semaphore};
 use tokio::time::{sleep, Duration as TokioDuration};
 
+/// Computes the next `count` run times of a cron expression in the given timezone.
+///
+/// # Arguments
+///
+/// * `expression` - Cron expression (`sec min hour day month weekday [year]`)
+/// * `timezone` - IANA timezone name used to evaluate the expression
+/// * `count` - Number of upcoming run times to return
+///
+/// # Returns
+///
+/// Returns the upcoming run times in the given timezone, or `Err` if the
+/// expression or timezone is invalid.
+///
+/// # Examples
+///
+/// ```
+/// use netspeed_lite::scheduler::upcoming_cron_runs;
+///
+/// let runs = upcoming_cron_runs("0 0 * * * *", "Europe/Brussels", 3).unwrap();
+/// assert_eq!(runs.len(), 3);
+/// ```
+pub fn upcoming_cron_runs(
+    expression: &str,
+    timezone: &str,
+    count: usize,
+) -> anyhow::Result<Vec<DateTime<Tz>>> {
+    let schedule = Schedule::from_str(expression)
+        .map_err(|e| anyhow::anyhow!("Invalid cron expression: {}", e))?;
+    let tz: Tz = timezone
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid timezone: {}", timezone))?;
+
+    Ok(schedule.upcoming(tz).take(count).collect())
+}
+
+/// Validates a schedule before it is applied.
+///
+/// Checks that the timezone is a known IANA name, that cron mode has a parsable
+/// cron expression, and that interval-based modes have a non-zero interval.
+///
+/// # Examples
+///
+/// ```
+/// use netspeed_lite::config::{ScheduleConfig, ScheduleMode};
+/// use netspeed_lite::scheduler::validate_schedule;
+///
+/// let schedule = ScheduleConfig {
+///     mode: ScheduleMode::Cron,
+///     interval_seconds: 3600,
+///     cron_expression: Some("not a cron".to_string()),
+///     timezone: "UTC".to_string(),
+///     allow_overlap: false,
+///     max_concurrent_runs: 1,
+///     min_gap_seconds: 0,
+///     jitter_seconds: 0,
+///     adaptive_min_seconds: 900,
+///     adaptive_max_seconds: 14400,
+///     adaptive_tolerance: 0.1,
+/// };
+/// assert!(validate_schedule(&schedule).is_err());
+/// ```
+pub fn validate_schedule(schedule: &ScheduleConfig) -> anyhow::Result<()> {
+    schedule
+        .timezone
+        .parse::<Tz>()
+        .map_err(|_| anyhow::anyhow!("Invalid timezone: {}", schedule.timezone))?;
+
+    match schedule.mode {
+        ScheduleMode::Cron => {
+            let expression = schedule
+                .cron_expression
+                .as_deref()
+                .ok_or_else(|| anyhow::anyhow!("Cron mode requires a cron expression"))?;
+            Schedule::from_str(expression)
+                .map_err(|e| anyhow::anyhow!("Invalid cron expression: {}", e))?;
+        }
+        ScheduleMode::Interval | ScheduleMode::Adaptive => {
+            if schedule.interval_seconds == 0 {
+                anyhow::bail!("Interval must be greater than 0");
+            }
+        }
+        ScheduleMode::HourlyAligned => {}
+    }
+
+    Ok(())
+}
+
+/// Computes the fraction of a schedule interval spent running a speed test.
+///
+/// The result is `run_duration / interval`, clamped to `0.0..=1.0`. A zero
+/// interval yields `1.0`.
+///
+/// # Examples
+///
+/// ```
+/// use netspeed_lite::scheduler::duty_cycle;
+/// use std::time::Duration;
+///
+/// let ratio = duty_cycle(Duration::from_secs(36), Duration::from_secs(3600));
+/// assert!((ratio - 0.01).abs() < 1e-9);
+/// ```
+pub fn duty_cycle(run_duration: std::time::Duration, interval: std::time::Duration) -> f64 {
+    if interval.is_zero() {
+        return 1.0;
+    }
+
+    (run_duration.as_secs_f64() / interval.as_secs_f64()).clamp(0.0, 1.0)
+}
+
+/// How much later than planned the scheduler may wake before the run is treated
+/// as a catch-up after a clock jump (e.g. host suspend) and skipped.
+const CLOCK_SKEW_THRESHOLD: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Detects a clock jump from how long a planned sleep actually took.
+///
+/// Returns the skew (actual minus intended) if it exceeds one minute, which
+/// happens when the host was suspended while the scheduler slept.
+///
+/// # Examples
+///
+/// ```
+/// use netspeed_lite::scheduler::detect_clock_skew;
+/// use std::time::Duration;
+///
+/// let planned = Duration::from_secs(3600);
+/// assert_eq!(detect_clock_skew(planned, Duration::from_secs(3601)), None);
+/// assert_eq!(
+///     detect_clock_skew(planned, Duration::from_secs(3 * 3600)),
+///     Some(Duration::from_secs(2 * 3600))
+/// );
+/// ```
+pub fn detect_clock_skew(
+    intended: std::time::Duration,
+    actual: std::time::Duration,
+) -> Option<std::time::Duration> {
+    actual
+        .checked_sub(intended)
+        .filter(|skew| *skew > CLOCK_SKEW_THRESHOLD)
+}
+
+/// Number of recent download results the adaptive schedule compares against.
+const ADAPTIVE_WINDOW: usize = 3;
+
+/// Interval state for the adaptive schedule mode.
+///
+/// Each recorded run adjusts the interval: a download result within `tolerance`
+/// (relative) of the recent average doubles it, up to `max_seconds`; a result
+/// outside the tolerance, or a failed run, halves it, down to `min_seconds`.
+/// The first result after startup or a swing only establishes a baseline.
+///
+/// # Examples
+///
+/// ```
+/// use netspeed_lite::scheduler::AdaptiveInterval;
+///
+/// let mut adaptive = AdaptiveInterval::new(3600, 900, 14400, 0.1);
+/// adaptive.record(Some(100_000_000.0));
+/// assert_eq!(adaptive.record(Some(102_000_000.0)), 7200);
+/// assert_eq!(adaptive.record(None), 3600);
+/// ```
+#[derive(Debug, Clone)]
+pub struct AdaptiveInterval {
+    current_seconds: u64,
+    min_seconds: u64,
+    max_seconds: u64,
+    tolerance: f64,
+    recent: VecDeque<f64>,
+}
+
+impl AdaptiveInterval {
+    /// Creates the adaptive state, starting at `initial_seconds` clamped to the bounds.
+    pub fn new(initial_seconds: u64, min_seconds: u64, max_seconds: u64, tolerance: f64) -> Self {
+        Self {
+            current_seconds: initial_seconds.clamp(min_seconds, max_seconds),
+            min_seconds,
+            max_seconds,
+            tolerance,
+            recent: VecDeque::with_capacity(ADAPTIVE_WINDOW),
+        }
+    }
+
+    /// Returns the current interval in seconds.
+    pub fn current_seconds(&self) -> u64 {
+        self.current_seconds
+    }
+
+    /// Records the download result of a run (`None` for a failed run) and
+    /// returns the adjusted interval in seconds.
+    pub fn record(&mut self, download_bps: Option<f64>) -> u64 {
+        let Some(download_bps) = download_bps else {
+            self.recent.clear();
+            self.shrink();
+            return self.current_seconds;
+        };
+
+        if !self.recent.is_empty() {
+            let average = self.recent.iter().sum::<f64>() / self.recent.len() as f64;
+            let change = if average > 0.0 {
+                (download_bps - average).abs() / average
+            } else {
+                f64::INFINITY
+            };
+
+            if change <= self.tolerance {
+                self.current_seconds = (self.current_seconds * 2).min(self.max_seconds);
+            } else {
+                // Re-baseline on the new level so a lasting change doesn't keep shrinking
+                self.recent.clear();
+                self.shrink();
+            }
+        }
+
+        if self.recent.len() == ADAPTIVE_WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(download_bps);
+
+        self.current_seconds
+    }
+
+    fn shrink(&mut self) {
+        self.current_seconds = (self.current_seconds / 2).max(self.min_seconds);
+    }
+}
+
+/// Smoothing factor of the anomaly detector's moving mean and variance.
+const ANOMALY_ALPHA: f64 = 0.2;
+
+/// Number of results the anomaly detector needs before it flags anything.
+const ANOMALY_MIN_SAMPLES: u32 = 5;
+
+/// Flags results that deviate abnormally from a series' recent behaviour.
+///
+/// Keeps an exponentially weighted moving mean and variance of the recorded
+/// values. Once seeded with enough samples, a value whose z-score exceeds
+/// `sigma` is flagged as anomalous. Every value, anomalous or not, updates the
+/// moving statistics, so a lasting change becomes the new normal. A `sigma` of
+/// 0 disables detection.
+///
+/// # Examples
+///
+/// ```
+/// use netspeed_lite::scheduler::AnomalyDetector;
+///
+/// let mut detector = AnomalyDetector::new(3.0);
+/// for mbps in [100.0, 102.0, 99.0, 101.0, 100.0, 98.0] {
+///     assert!(!detector.record(mbps));
+/// }
+/// assert!(detector.record(20.0));
+/// ```
+#[derive(Debug, Clone)]
+pub struct AnomalyDetector {
+    sigma: f64,
+    mean: f64,
+    variance: f64,
+    samples: u32,
+}
+
+impl AnomalyDetector {
+    /// Creates a detector flagging values more than `sigma` standard deviations
+    /// from the moving mean.
+    pub fn new(sigma: f64) -> Self {
+        Self {
+            sigma,
+            mean: 0.0,
+            variance: 0.0,
+            samples: 0,
+        }
+    }
+
+    /// Records a value and returns whether it is anomalous.
+    pub fn record(&mut self, value: f64) -> bool {
+        let std_dev = self.variance.sqrt();
+        // Without any spread there is no scale to judge a deviation against
+        let anomalous = self.sigma > 0.0
+            && self.samples >= ANOMALY_MIN_SAMPLES
+            && std_dev > 0.0
+            && (value - self.mean).abs() / std_dev > self.sigma;
+
+        if self.samples == 0 {
+            self.mean = value;
+        } else {
+            let diff = value - self.mean;
+            let increment = ANOMALY_ALPHA * diff;
+            self.mean += increment;
+            self.variance = (1.0 - ANOMALY_ALPHA) * (self.variance + diff * increment);
+        }
+        self.samples = self.samples.saturating_add(1);
+
+        anomalous
+    }
+}
+
+/// Seedable source of the random delays added to interval runs
+/// (`NETSPEED_SCHEDULE_JITTER_SECONDS`).
+///
+/// A SplitMix64 generator: its statistical quality is plenty for spreading out
+/// start times, and a fixed seed gives a reproducible sequence.
+///
+/// # Examples
+///
+/// ```
+/// use netspeed_lite::scheduler::ScheduleJitter;
+///
+/// let mut jitter = ScheduleJitter::new(42);
+/// assert!(jitter.offset_seconds(300) <= 300);
+/// assert_eq!(jitter.offset_seconds(0), 0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ScheduleJitter {
+    state: u64,
+}
+
+impl ScheduleJitter {
+    /// Creates a jitter source with a fixed seed.
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Creates a jitter source seeded from the clock and the process ID, so
+    /// instances started at the same moment still draw different delays.
+    pub fn from_entropy() -> Self {
+        let nanos = Utc::now().timestamp_nanos_opt().unwrap_or_default() as u64;
+        Self::new(nanos ^ (u64::from(std::process::id()) << 32))
+    }
+
+    /// Returns a random delay in `[0, max_seconds]`.
+    pub fn offset_seconds(&mut self, max_seconds: u64) -> u64 {
+        if max_seconds == 0 {
+            return 0;
+        }
+
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+
+        match max_seconds.checked_add(1) {
+            Some(range) => z % range,
+            None => z,
+        }
+    }
+}
+
+/// Number of health events kept for `/annotations`.
+const MAX_HEALTH_EVENTS: usize = 100;
+
+/// A failure or recovery: the health state changing to `unhealthy`, or from
+/// `unhealthy` back to `healthy`.
+#[derive(Debug, Clone)]
+pub struct HealthEvent {
+    pub time: DateTime<Utc>,
+    /// `true` for a recovery, `false` for a failure.
+    pub recovered: bool,
+    /// The error that caused a failure.
+    pub error: Option<String>,
+}
+
+/// Raw output of a past run, kept for `/debug/outputs` and `/debug/last-output`.
+#[derive(Debug, Clone, Serialize)]
+pub struct CapturedOutput {
+    /// When the run finished, RFC3339.
+    pub timestamp: String,
+    pub success: bool,
+    pub output: String,
+}
+
 pub struct Scheduler {
     config: Config,
     metrics: Metrics,
     notifier: Option<Notifier>,
     run_in_progress: Arc<AtomicBool>,
+    run_permits: Semaphore,
+    health_webhook: Option<HealthWebhook>,
+    history: Option<RunHistory>,
+    health_state: Mutex<&'static str>,
+    health_events: Mutex<VecDeque<HealthEvent>>,
+    consecutive_successes: AtomicU64,
+    consecutive_failures: AtomicU64,
+    server_failover: AtomicBool,
+    last_run_end: Mutex<Option<Instant>>,
+    last_run_start: Mutex<Option<Instant>>,
+    adaptive: Mutex<AdaptiveInterval>,
+    download_anomaly: Mutex<AnomalyDetector>,
+    upload_anomaly: Mutex<AnomalyDetector>,
+    schedule: RwLock<ScheduleConfig>,
+    schedule_changed: Notify,
+    debug_outputs: Mutex<VecDeque<CapturedOutput>>,
+    jitter: Mutex<ScheduleJitter>,
+    /// Deadline of the next scheduled run, once planned (see `next_run_time`).
+    next_run: Mutex<Option<DateTime<Utc>>>,
+    run_trigger: mpsc::Sender<String>,
+    run_requests: tokio::sync::Mutex<mpsc::Receiver<String>>,
 }
 
 impl Scheduler {
@@ -47,11 +442,61 @@ impl Scheduler {
     /// let scheduler = Scheduler::new(config, metrics, None);
     /// ```
     pub fn new(config: Config, metrics: Metrics, notifier: Option<Notifier>) -> Self {
+        let adaptive = AdaptiveInterval::new(
+            config.schedule.interval_seconds,
+            config.schedule.adaptive_min_seconds,
+            config.schedule.adaptive_max_seconds,
+            config.schedule.adaptive_tolerance,
+        );
+
+        let anomaly = AnomalyDetector::new(config.anomaly_sigma);
+        let health_webhook = config.health_webhook_url.clone().map(HealthWebhook::new);
+        // A broken database disables the history rather than the scheduler
+        let history = config
+            .db_path
+            .as_deref()
+            .and_then(|path| match RunHistory::open(path) {
+                Ok(history) => Some(history),
+                Err(e) => {
+                    tracing::error!("Run history disabled: {:#}", e);
+                    None
+                }
+            });
+        // Without overlap only one run may be in flight, whatever the cap says
+        let max_runs = if config.schedule.allow_overlap {
+            config.schedule.max_concurrent_runs
+        } else {
+            1
+        };
+        // A single slot: a second request while one is queued is rejected
+        let (run_trigger, run_requests) = mpsc::channel(1);
+        let notifier = notifier.map(|notifier| notifier.with_max_jitter_ms(config.max_jitter_ms));
+
         Self {
+            schedule: RwLock::new(config.schedule.clone()),
+            schedule_changed: Notify::new(),
             config,
             metrics,
             notifier,
             run_in_progress: Arc::new(AtomicBool::new(false)),
+            run_permits: Semaphore::new(max_runs),
+            health_webhook,
+            history,
+            health_state: Mutex::new("initializing"),
+            health_events: Mutex::new(VecDeque::new()),
+            consecutive_successes: AtomicU64::new(0),
+            consecutive_failures: AtomicU64::new(0),
+            server_failover: AtomicBool::new(false),
+            last_run_end: Mutex::new(None),
+            last_run_start: Mutex::new(None),
+            adaptive: Mutex::new(adaptive),
+            download_anomaly: Mutex::new(anomaly.clone()),
+            upload_anomaly: Mutex::new(anomaly),
+            debug_outputs: Mutex::new(VecDeque::new()),
+            jitter: Mutex::new(ScheduleJitter::from_entropy()),
+            next_run: Mutex::new(None),
+            run_trigger,
+            run_requests: tokio::sync::Mutex::new(run_requests),
         }
     }
 
@@ -59,7 +504,8 @@ impl Scheduler {
     ///
     /// This function:
     /// 1. Calculates the next run time based on the configured schedule mode
-    /// 2. Sleeps until that time
+    /// 2. Sleeps until that time, skipping the run if it woke far too late (see `detect_clock_skew`),
+    ///    or runs immediately when a run is requested through `run_trigger`
     /// 3. Checks for overlap (if configured to prevent it)
     /// 4. Executes the speedtest
     /// 5. Updates metrics and sends notifications
@@ -72,6 +518,7 @@ impl Scheduler {
     /// - **HourlyAligned**: Runs at the top of each hour (e.g., 13:00, 14:00)
     /// - **Interval**: Runs every N seconds from the last run
     /// - **Cron**: Runs according to a cron expression
+    /// - **Adaptive**: Runs every N seconds, with N adjusted after each run by `AdaptiveInterval`
     ///
     /// # Examples
     ///
@@ -86,10 +533,11 @@ impl Scheduler {
     /// # };
     /// ```
     pub async fn run(&self) {
-        tracing::info!("Starting scheduler in {:?} mode", self.config.schedule.mode);
+        tracing::info!("Starting scheduler in {:?} mode", self.schedule().mode);
+        let mut run_requests = self.run_requests.lock().await;
 
         loop {
-            let next_run = self.calculate_next_run();
+            let next_run = self.next_run_time();
             let now = Utc::now();
 
             if next_run > now {
@@ -101,11 +549,49 @@ impl Scheduler {
                     next_run,
                     sleep_duration
                 );
-                sleep(sleep_duration).await;
+                // Wall-clock time keeps moving while the host is suspended,
+                // unlike the monotonic clock behind `sleep`
+                let slept_from = Utc::now();
+                tokio::select! {
+                    _ = sleep(sleep_duration) => {}
+                    _ = self.schedule_changed.notified() => {
+                        tracing::info!("Schedule updated, recalculating next run");
+                        continue;
+                    }
+                    Some(run_id) = run_requests.recv() => {
+                        tracing::info!(run_id = %run_id, "Starting on-demand run");
+                        self.run_once().await;
+                        self.clear_next_run();
+                        continue;
+                    }
+                    _ = async {
+                        match self.time_until_stale() {
+                            Some(stale_in) => sleep(stale_in).await,
+                            None => std::future::pending().await,
+                        }
+                    } => {
+                        self.update_health_state(None).await;
+                        continue;
+                    }
+                }
+                let slept_for = (Utc::now() - slept_from).to_std().unwrap_or_default();
+
+                if let Some(skew) = detect_clock_skew(sleep_duration, slept_for) {
+                    tracing::warn!(
+                        "Woke {:?} later than planned (clock jump or suspend), skipping catch-up run",
+                        skew
+                    );
+                    self.metrics.clock_skew_events_total.inc();
+                    self.clear_next_run();
+                    continue;
+                }
             }
 
+            // The planned run is due; the next one is planned after it ends
+            self.clear_next_run();
+
             // Check for overlap
-            if self.run_in_progress.load(Ordering::SeqCst) && !self.config.schedule.allow_overlap {
+            if self.run_in_progress.load(Ordering::SeqCst) && !self.schedule().allow_overlap {
                 tracing::warn!("Previous run still in progress, skipping this run");
                 self.metrics
                     .runs_total
@@ -122,75 +608,266 @@ impl Scheduler {
                 continue;
             }
 
+            // Defer until the minimum gap since the previous run has elapsed
+            if let Some(remaining) = self.cooldown_remaining() {
+                tracing::info!("Deferring run by {:?} to respect minimum gap", remaining);
+                sleep(remaining).await;
+            }
+
             // Execute the run
-            self.execute_run().await;
+            self.run_once().await;
+            self.clear_next_run();
         }
     }
 
-    fn calculate_next_run(&self) -> DateTime<Utc> {
-        match self.config.schedule.mode {
-            ScheduleMode::HourlyAligned => self.calculate_next_aligned_run(),
-            ScheduleMode::Interval => self.calculate_next_interval_run(),
-            ScheduleMode::Cron => self.calculate_next_cron_run(),
+    /// Executes a single speed test run immediately, outside the schedule.
+    ///
+    /// Metrics are updated and notifications sent exactly as for a scheduled run.
+    /// The run is rejected (and counted as `skipped`) if it would start within
+    /// `min_gap_seconds` of the end of the previous run, or if the maximum
+    /// number of concurrent runs (1 unless overlap is allowed) is already in
+    /// flight.
+    ///
+    /// # Returns
+    ///
+    /// Returns `true` if the run was executed, `false` if it was rejected.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use netspeed_lite::scheduler::Scheduler;
+    ///
+    /// # async {
+    /// # let scheduler: Scheduler = unimplemented!();
+    /// scheduler.run_once().await;
+    /// # };
+    /// ```
+    pub async fn run_once(&self) -> bool {
+        if let Some(remaining) = self.cooldown_remaining() {
+            tracing::warn!(
+                "Run requested within minimum gap ({:?} remaining), skipping",
+                remaining
+            );
+            self.metrics
+                .runs_total
+                .with_label_values(&["skipped"])
+                .inc();
+            return false;
         }
+
+        let Ok(_permit) = self.run_permits.try_acquire() else {
+            tracing::warn!("Maximum number of concurrent runs in progress, skipping");
+            self.metrics
+                .runs_total
+                .with_label_values(&["skipped"])
+                .inc();
+            return false;
+        };
+
+        self.execute_run().await;
+        true
     }
 
-    fn calculate_next_cron_run(&self) -> DateTime<Utc> {
-        let expression = self
-            .config
-            .schedule
-            .cron_expression
-            .as_ref()
-            .expect("Cron expression required for Cron mode");
+    /// Returns a sender that requests an immediate run from the scheduler loop.
+    ///
+    /// Each message is the id of the requested run, used for logging. The
+    /// channel holds a single request, so `try_send` fails while one is queued.
+    pub fn run_trigger(&self) -> mpsc::Sender<String> {
+        self.run_trigger.clone()
+    }
 
-        let schedule = Schedule::from_str(expression).expect("Invalid cron expression");
-        let tz: Tz = self
-            .config
-            .schedule
-            .timezone
-            .parse()
-            .expect("Invalid timezone");
+    /// Returns whether a speed test run is currently executing.
+    pub fn is_running(&self) -> bool {
+        self.run_in_progress.load(Ordering::SeqCst)
+    }
 
-        schedule
-            .upcoming(tz)
-            .next()
-            .map(|dt| dt.with_timezone(&Utc))
-            .unwrap_or_else(|| Utc::now() + Duration::minutes(1))
+    /// Returns whether `max_concurrent_runs` runs are already executing, so
+    /// another run would be rejected.
+    pub fn at_run_capacity(&self) -> bool {
+        self.run_permits.available_permits() == 0
     }
 
-    fn calculate_next_aligned_run(&self) -> DateTime<Utc> {
-        let tz: Tz = self
-            .config
+    /// Returns the time left before the minimum gap since the previous run has
+    /// elapsed, or `None` if a run may start now.
+    pub fn cooldown_remaining(&self) -> Option<std::time::Duration> {
+        let min_gap = std::time::Duration::from_secs(self.schedule().min_gap_seconds);
+        let last_end = (*self
+            .last_run_end
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner))?;
+
+        min_gap
+            .checked_sub(last_end.elapsed())
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    /// Returns a snapshot of the schedule currently in effect.
+    pub fn schedule(&self) -> ScheduleConfig {
+        self.schedule
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Replaces the schedule of the running scheduler.
+    ///
+    /// The new schedule is validated first (see `validate_schedule`). A scheduler
+    /// sleeping towards the previous schedule's next run wakes up and recalculates.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Err` without changing anything if the schedule is invalid.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use netspeed_lite::config::ScheduleMode;
+    /// use netspeed_lite::scheduler::Scheduler;
+    ///
+    /// # let scheduler: Scheduler = unimplemented!();
+    /// let mut schedule = scheduler.schedule();
+    /// schedule.mode = ScheduleMode::Cron;
+    /// schedule.cron_expression = Some("0 */30 * * * *".to_string());
+    /// scheduler.update_schedule(schedule).unwrap();
+    /// ```
+    pub fn update_schedule(&self, schedule: ScheduleConfig) -> anyhow::Result<()> {
+        validate_schedule(&schedule)?;
+
+        tracing::info!("Switching schedule to {:?} mode", schedule.mode);
+        *self
             .schedule
-            .timezone
-            .parse()
-            .expect("Invalid timezone");
-        let now_tz = Utc::now().with_timezone(&tz);
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = schedule;
+        self.clear_next_run();
+        self.schedule_changed.notify_one();
 
-        // Calculate next top of hour
-        let next_hour = if now_tz.minute() == 0 && now_tz.second() == 0 && now_tz.nanosecond() == 0
-        {
-            // If we're exactly at the top of the hour, schedule for next hour
-            now_tz + Duration::hours(1)
-        } else {
-            // Otherwise, go to the next top of hour
-            tz.with_ymd_and_hms(
-                now_tz.year(),
-                now_tz.month(),
-                now_tz.day(),
-                now_tz.hour() + 1,
-                0,
-                0,
-            )
-            .single()
-            .unwrap_or_else(|| now_tz + Duration::hours(1))
+        Ok(())
+    }
+
+    /// Returns when the next scheduled run is due under the current schedule.
+    ///
+    /// The run is planned once and kept: while the scheduler loop waits, this
+    /// is the deadline it is sleeping toward, including the random delay of up
+    /// to `jitter_seconds` drawn for it in interval mode. The plan is dropped
+    /// once the run is due, after each run, and when the schedule changes.
+    pub fn next_run_time(&self) -> DateTime<Utc> {
+        *self
+            .next_run
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_or_insert_with(|| self.calculate_next_run())
+    }
+
+    /// Forgets the planned run, so the next call to `next_run_time` plans a
+    /// new one from now.
+    fn clear_next_run(&self) {
+        *self.next_run.lock().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+
+    /// Computes a new next run time, drawing a new jitter delay in interval mode.
+    fn calculate_next_run(&self) -> DateTime<Utc> {
+        let schedule = self.schedule();
+        match schedule.mode {
+            ScheduleMode::HourlyAligned => calculate_next_aligned_run(&schedule),
+            ScheduleMode::Interval => {
+                let jitter = self
+                    .jitter
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .offset_seconds(schedule.jitter_seconds);
+                calculate_next_interval_run(&schedule, jitter)
+            }
+            ScheduleMode::Cron => calculate_next_cron_run(&schedule),
+            ScheduleMode::Adaptive => {
+                Utc::now() + Duration::seconds(self.adaptive_interval_seconds() as i64)
+            }
+        }
+    }
+
+    /// Computes the next `count` scheduled run times under the current schedule.
+    ///
+    /// Cron mode follows the cron expression. The other modes start from the
+    /// pending run (`next_run_time`), so the first entry is the deadline the
+    /// scheduler loop is waiting for, and repeat their interval (one hour for
+    /// hourly-aligned) from there. Later entries are estimates: they carry no
+    /// jitter, and each interval actually starts when the previous run ends.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use netspeed_lite::scheduler::Scheduler;
+    /// # let scheduler: Scheduler = unimplemented!();
+    /// for run in scheduler.upcoming_runs(5) {
+    ///     println!("{}", run.to_rfc3339());
+    /// }
+    /// ```
+    pub fn upcoming_runs(&self, count: usize) -> Vec<DateTime<Utc>> {
+        let schedule = self.schedule();
+        let step = match schedule.mode {
+            ScheduleMode::Cron => {
+                let expression = schedule.cron_expression.as_deref().unwrap_or_default();
+                return upcoming_cron_runs(expression, &schedule.timezone, count)
+                    .map(|runs| runs.iter().map(|dt| dt.with_timezone(&Utc)).collect())
+                    .unwrap_or_default();
+            }
+            ScheduleMode::HourlyAligned => Duration::hours(1),
+            ScheduleMode::Interval => {
+                Duration::seconds(schedule.interval_seconds.max(MIN_INTERVAL_SECONDS) as i64)
+            }
+            ScheduleMode::Adaptive => Duration::seconds(self.adaptive_interval_seconds() as i64),
         };
 
-        next_hour.with_timezone(&Utc)
+        std::iter::successors(Some(self.next_run_time()), |run| Some(*run + step))
+            .take(count)
+            .collect()
     }
 
-    fn calculate_next_interval_run(&self) -> DateTime<Utc> {
-        Utc::now() + Duration::seconds(self.config.schedule.interval_seconds as i64)
+    /// Returns the current adaptive interval in seconds. Only meaningful in
+    /// adaptive mode; other modes never adjust it.
+    pub fn adaptive_interval_seconds(&self) -> u64 {
+        self.adaptive
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .current_seconds()
+    }
+
+    /// Sends a canned test message through every configured notification
+    /// channel and returns each channel's outcome, keyed by channel name.
+    ///
+    /// Channels that aren't configured are omitted, so with no notifier the
+    /// result is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use netspeed_lite::scheduler::Scheduler;
+    /// # async {
+    /// # let scheduler: Scheduler = unimplemented!();
+    /// for (channel, result) in scheduler.send_test_notification().await {
+    ///     println!("{}: {:?}", channel, result.is_ok());
+    /// }
+    /// # };
+    /// ```
+    pub async fn send_test_notification(&self) -> Vec<(&'static str, anyhow::Result<()>)> {
+        let mut results = Vec::new();
+        if let Some(notifier) = &self.notifier {
+            results.push(("ntfy", notifier.send_test().await));
+        }
+        results
+    }
+
+    /// Feeds a run's download result into the adaptive interval (adaptive mode only).
+    fn adapt_interval(&self, download_bps: Option<f64>) {
+        if self.schedule().mode != ScheduleMode::Adaptive {
+            return;
+        }
+
+        let mut adaptive = self.adaptive.lock().unwrap_or_else(PoisonError::into_inner);
+        let previous = adaptive.current_seconds();
+        let next = adaptive.record(download_bps);
+        if next != previous {
+            tracing::info!("Adaptive interval changed from {}s to {}s", previous, next);
+        }
     }
 
     fn update_success_metrics(
@@ -198,7 +875,8 @@ impl Scheduler {
         result: &crate::runner::SpeedtestResult,
         duration: std::time::Duration,
     ) {
-        let timestamp = Utc::now().timestamp() as f64;
+        // Prefer when the CLI says the test ran; long tests finish well after
+        let timestamp = result.measured_at.unwrap_or_else(Utc::now).timestamp() as f64;
         self.metrics.run_timestamp_seconds.set(timestamp);
         self.metrics
             .run_duration_seconds
@@ -209,21 +887,103 @@ impl Scheduler {
             .with_label_values(&["success"])
             .inc();
 
-        // Update measurement metrics
-        self.metrics.download_bps.set(result.download_bps);
-        self.metrics.upload_bps.set(result.upload_bps);
+        // Update measurement metrics, leaving a missing direction untouched
+        if result.missing != Some(Direction::Download) {
+            self.metrics.download_bps.set(result.download_bps);
+            self.metrics
+                .download_bps_histogram
+                .observe(result.download_bps);
+        }
+        if result.missing != Some(Direction::Upload) {
+            self.metrics.upload_bps.set(result.upload_bps);
+            self.metrics.upload_bps_histogram.observe(result.upload_bps);
+        }
         self.metrics.latency_seconds.set(result.latency_seconds);
+        self.metrics
+            .latency_seconds_histogram
+            .observe(result.latency_seconds);
+
+        if let Some(latency) = result.download_latency_seconds {
+            self.metrics.download_loaded_latency_seconds.set(latency);
+        }
+        if let Some(latency) = result.upload_latency_seconds {
+            self.metrics.upload_loaded_latency_seconds.set(latency);
+        }
 
         if let Some(jitter) = result.jitter_seconds {
-            self.metrics.jitter_seconds.set(jitter);
+            self.metrics.set_jitter_seconds(jitter);
+
+            if let Some(max_jitter_ms) = self.config.max_jitter_ms {
+                let exceeded = jitter * 1000.0 > max_jitter_ms;
+                self.metrics
+                    .jitter_exceeded
+                    .set(if exceeded { 1.0 } else { 0.0 });
+            }
         }
 
         if let Some(loss) = result.packet_loss_ratio {
-            self.metrics.packet_loss_ratio.set(loss);
+            self.metrics.set_packet_loss_ratio(loss);
+        }
+
+        // Replace rather than add, so a changed address doesn't leave a stale series
+        if self.config.expose_external_ip {
+            if let Some(ip) = result.external_ip {
+                self.metrics.external_ip_info.reset();
+                self.metrics
+                    .external_ip_info
+                    .with_label_values(&[&ip.to_string()])
+                    .set(1.0);
+            }
+        }
+
+        // Replace rather than add, so a change of server doesn't leave a stale series
+        self.metrics.server_info.reset();
+        if result.server_id.is_some() || result.server_name.is_some() {
+            let id = result
+                .server_id
+                .map(|id| id.to_string())
+                .unwrap_or_default();
+            self.metrics
+                .server_info
+                .with_label_values(&[
+                    id.as_str(),
+                    result.server_name.as_deref().unwrap_or_default(),
+                    result.server_location.as_deref().unwrap_or_default(),
+                ])
+                .set(1.0);
+        }
+
+        if self.config.expose_interface {
+            if let Some(name) = &result.interface_name {
+                self.metrics.interface_info.reset();
+                self.metrics
+                    .interface_info
+                    .with_label_values(&[name])
+                    .set(1.0);
+            }
         }
+
+        self.metrics.expose_measurements();
+    }
+
+    /// Updates the duty cycle gauge for interval-based modes. Cron schedules have
+    /// no fixed interval, so the gauge is left untouched.
+    fn update_duty_cycle(&self, duration: std::time::Duration) {
+        let schedule = self.schedule();
+        let interval_seconds = match schedule.mode {
+            ScheduleMode::HourlyAligned => 3600,
+            ScheduleMode::Interval => schedule.interval_seconds,
+            ScheduleMode::Adaptive => self.adaptive_interval_seconds(),
+            ScheduleMode::Cron => return,
+        };
+
+        self.metrics.duty_cycle.set(duty_cycle(
+            duration,
+            std::time::Duration::from_secs(interval_seconds),
+        ));
     }
 
-    fn update_failure_metrics(&self, duration: std::time::Duration) {
+    fn update_failure_metrics(&self, error: &ErrorCategory, duration: std::time::Duration) {
         let timestamp = Utc::now().timestamp() as f64;
         self.metrics.run_timestamp_seconds.set(timestamp);
         self.metrics
@@ -234,23 +994,314 @@ impl Scheduler {
             .runs_total
             .with_label_values(&["failure"])
             .inc();
+        self.metrics
+            .errors_total
+            .with_label_values(&[error.stage()])
+            .inc();
+    }
+
+    /// Feeds a successful run into the anomaly detectors, updates
+    /// `netspeed_anomaly` and, if enabled, notifies about an anomalous result.
+    async fn check_anomaly(&self, result: &crate::runner::SpeedtestResult) {
+        let mut anomalies = Vec::new();
+        if result.missing != Some(Direction::Download)
+            && self
+                .download_anomaly
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .record(result.download_bps)
+        {
+            anomalies.push(format!(
+                "download {:.1} Mbps",
+                result.download_bps / 1_000_000.0
+            ));
+        }
+        if result.missing != Some(Direction::Upload)
+            && self
+                .upload_anomaly
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .record(result.upload_bps)
+        {
+            anomalies.push(format!(
+                "upload {:.1} Mbps",
+                result.upload_bps / 1_000_000.0
+            ));
+        }
+
+        self.metrics
+            .anomaly
+            .set(if anomalies.is_empty() { 0.0 } else { 1.0 });
+        if anomalies.is_empty() {
+            return;
+        }
+
+        let message = format!(
+            "Anomalous result: {} (more than {}σ from the recent mean)",
+            anomalies.join(", "),
+            self.config.anomaly_sigma
+        );
+        tracing::warn!("{}", message);
+        if self.config.anomaly_notify {
+            if let Some(notifier) = &self.notifier {
+                notifier.notify_message("⚠️", message).await;
+            }
+        }
+    }
+
+    /// Returns recent failures and recoveries, oldest first (at most the last 100).
+    pub fn health_events(&self) -> Vec<HealthEvent> {
+        self.health_events
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the state reported by the health webhook: `initializing` before
+    /// the first run, `stale` once the last run is older than
+    /// `stale_after_seconds`, and otherwise `healthy` or `unhealthy` depending
+    /// on the outcome of the last run.
+    ///
+    /// This is not the `/healthz` status. There, staleness is a separate
+    /// `stale` flag, and memory or jitter limits report `degraded`, which the
+    /// webhook never emits.
+    fn current_health_state(&self) -> &'static str {
+        let last_run = self.metrics.run_timestamp_seconds.get();
+        let now = Utc::now().timestamp() as f64;
+        if last_run <= 0.0 {
+            "initializing"
+        } else if is_stale(last_run, now, self.config.server.stale_after_seconds) {
+            "stale"
+        } else if self.metrics.last_success.get() > 0.0 {
+            "healthy"
+        } else {
+            "unhealthy"
+        }
+    }
+
+    /// Returns how long until the last run becomes stale, or `None` before the
+    /// first run and once the stale state has been recorded.
+    fn time_until_stale(&self) -> Option<TokioDuration> {
+        let last_run = self.metrics.run_timestamp_seconds.get();
+        let state = *self
+            .health_state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if last_run <= 0.0 || state == "stale" {
+            return None;
+        }
+
+        let stale_at = last_run + self.config.server.stale_after_seconds as f64;
+        let remaining = (stale_at - Utc::now().timestamp() as f64).max(0.0);
+        // Staleness is checked in whole seconds and only past the limit
+        Some(TokioDuration::from_secs(
+            (remaining as u64).saturating_add(1),
+        ))
+    }
+
+    /// Updates the health state (see `current_health_state`) after a run with
+    /// the run's `error`, or when the last run becomes stale. If the state
+    /// changed, the transition is posted to the health webhook, and failures
+    /// and recoveries are added to the health events.
+    async fn update_health_state(&self, error: Option<&ErrorCategory>) {
+        let new_state = self.current_health_state();
+        let old_state = std::mem::replace(
+            &mut *self
+                .health_state
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+            new_state,
+        );
+        if old_state == new_state {
+            return;
+        }
+
+        tracing::info!("Health state changed from {} to {}", old_state, new_state);
+        // Startup's first success is neither a failure nor a recovery
+        let recovered = old_state == "unhealthy" && new_state == "healthy";
+        if new_state == "unhealthy" || recovered {
+            let mut events = self
+                .health_events
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            if events.len() >= MAX_HEALTH_EVENTS {
+                events.pop_front();
+            }
+            events.push_back(HealthEvent {
+                time: Utc::now(),
+                recovered,
+                error: error.map(|e| e.to_string()),
+            });
+        }
+
+        if let Some(webhook) = &self.health_webhook {
+            let transition = HealthTransition {
+                old_state,
+                new_state,
+                timestamp: Utc::now().to_rfc3339(),
+            };
+            webhook.send(&transition).await;
+        }
+    }
+
+    /// Counts a successful run and sends a milestone notification every
+    /// `notify_success_streak` consecutive successes.
+    async fn record_success_streak(&self) {
+        let streak = self.consecutive_successes.fetch_add(1, Ordering::SeqCst) + 1;
+        let milestone = self.config.notify_success_streak;
+
+        // A milestone of 0 disables streak notifications (checked_rem yields None)
+        if streak.checked_rem(milestone) != Some(0) {
+            return;
+        }
+
+        tracing::info!(streak = streak, "Consecutive success milestone reached");
+        if let Some(notifier) = &self.notifier {
+            notifier
+                .notify_message("🎉", format!("{} consecutive successful runs", streak))
+                .await;
+        }
+    }
+
+    /// Returns the speedtest configuration the next run will use: the configured
+    /// one, minus the pinned `--server-id` while failed over (see
+    /// `NETSPEED_SERVER_FAILOVER_THRESHOLD`).
+    pub fn speedtest_config(&self) -> SpeedtestConfig {
+        let mut speedtest = self.config.speedtest.clone();
+        if self.server_failover.load(Ordering::SeqCst) {
+            speedtest.args.retain(|arg| !is_server_id_arg(arg));
+        }
+        speedtest
+    }
+
+    /// Tracks consecutive failures and drops the pinned server once they reach
+    /// `server_failover_threshold`; the first success afterwards restores it.
+    fn update_server_failover(&self, success: bool) {
+        if success {
+            self.consecutive_failures.store(0, Ordering::SeqCst);
+            if self.server_failover.swap(false, Ordering::SeqCst) {
+                tracing::info!(
+                    "Run succeeded with an auto-selected server, restoring the pinned server"
+                );
+            }
+            return;
+        }
+
+        let failures = self.consecutive_failures.fetch_add(1, Ordering::SeqCst) + 1;
+        let threshold = self.config.server_failover_threshold;
+        let pinned = self
+            .config
+            .speedtest
+            .args
+            .iter()
+            .any(|arg| is_server_id_arg(arg));
+        if threshold > 0
+            && failures >= threshold
+            && pinned
+            && !self.server_failover.swap(true, Ordering::SeqCst)
+        {
+            tracing::warn!(
+                "{} consecutive failures with the pinned server, letting Ookla select a server until a run succeeds",
+                failures
+            );
+        }
+    }
+
+    /// Returns the captured raw outputs of recent runs, oldest first.
+    ///
+    /// At most `NETSPEED_DEBUG_CAPTURE_COUNT` runs are kept; nothing is
+    /// captured when it is 0.
+    pub fn debug_outputs(&self) -> Vec<CapturedOutput> {
+        self.debug_outputs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the captured raw output of the most recent run, if any.
+    pub fn last_output(&self) -> Option<CapturedOutput> {
+        self.debug_outputs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .back()
+            .cloned()
+    }
+
+    /// Returns the run history database, if `NETSPEED_DB_PATH` is set and it
+    /// could be opened.
+    pub fn history(&self) -> Option<&RunHistory> {
+        self.history.as_ref()
+    }
+
+    /// Adds a run's raw output to the debug ring buffer, evicting the oldest
+    /// entry when full.
+    fn capture_output(&self, output: Option<String>, success: bool) {
+        let capacity = self.config.debug_capture_count;
+        let Some(output) = output.filter(|_| capacity > 0) else {
+            return;
+        };
+
+        let mut outputs = self
+            .debug_outputs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        while outputs.len() >= capacity {
+            outputs.pop_front();
+        }
+        outputs.push_back(CapturedOutput {
+            timestamp: Utc::now().to_rfc3339(),
+            success,
+            output,
+        });
     }
 
     async fn execute_run(&self) {
-        self.run_in_progress.store(true, Ordering::SeqCst);
+        let _in_progress = RunInProgress::start(&self.run_in_progress);
 
         let run_id = Utc::now().timestamp();
         tracing::info!(run_id = run_id, "Starting speed test run");
 
-        let result = run_speedtest(
-            &self.config.speedtest.command,
-            &self.config.speedtest.args,
-            self.config.speedtest.timeout_seconds,
-        )
-        .await;
+        let started = Instant::now();
+        if let Some(previous) = self
+            .last_run_start
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .replace(started)
+        {
+            self.metrics
+                .run_gap_seconds
+                .observe(started.duration_since(previous).as_secs_f64());
+        }
+
+        let result = run_speedtest(&self.speedtest_config()).await;
+        if result.retries > 0 {
+            self.metrics
+                .runs_total
+                .with_label_values(&["retried"])
+                .inc_by(u64::from(result.retries));
+        }
 
         let duration = result.duration;
-        let outcome = result.outcome;
+        let mut outcome = result.outcome;
+
+        // Apply the user's success criterion on top of the raw measurement
+        if let (Some(command), RunOutcome::Success(speedtest_result)) =
+            (&self.config.success_criterion_cmd, &outcome)
+        {
+            if let Err(e) = check_success_criterion(command, speedtest_result).await {
+                outcome = RunOutcome::Failure(e);
+            }
+        }
+
+        let success = matches!(outcome, RunOutcome::Success(_));
+        self.capture_output(result.output, success);
+        self.update_server_failover(success);
+        self.update_duty_cycle(duration);
+        self.metrics.has_run.set(1.0);
 
         // Update metrics and send notifications
         match &outcome {
@@ -265,6 +1316,11 @@ impl Scheduler {
                 );
 
                 self.update_success_metrics(speedtest_result, duration);
+                self.adapt_interval(
+                    (speedtest_result.missing != Some(Direction::Download))
+                        .then_some(speedtest_result.download_bps),
+                );
+                self.check_anomaly(speedtest_result).await;
 
                 // Send notification if configured
                 if let Some(notifier) = &self.notifier {
@@ -272,26 +1328,158 @@ impl Scheduler {
                         notifier.notify(&outcome, duration).await;
                     }
                 }
+
+                self.record_success_streak().await;
+                self.update_health_state(None).await;
             }
             RunOutcome::Failure(error) => {
                 tracing::error!(
                     run_id = run_id,
                     duration_secs = duration.as_secs(),
                     error = %error,
+                    stderr = error.stderr(),
                     "Speed test failed"
                 );
 
-                self.update_failure_metrics(duration);
+                self.update_failure_metrics(error, duration);
+                self.adapt_interval(None);
+                self.consecutive_successes.store(0, Ordering::SeqCst);
 
                 // Send notification if configured
                 if let Some(notifier) = &self.notifier {
                     if self.config.notify_on.failure {
-                        notifier.notify(&outcome, duration).await;
+                        if self.config.failure_verbose {
+                            let message =
+                                format_failure_verbose(error, &self.config.speedtest, Utc::now());
+                            notifier.notify_run_message(&outcome, "❌", message).await;
+                        } else {
+                            notifier.notify(&outcome, duration).await;
+                        }
                     }
                 }
+
+                self.update_health_state(Some(error)).await;
+            }
+        }
+
+        if let Some(history) = &self.history {
+            if let Err(e) = history.record(&RunRecord::from_outcome(&outcome, duration)) {
+                tracing::warn!("Failed to record run history: {:#}", e);
             }
         }
 
-        self.run_in_progress.store(false, Ordering::SeqCst);
+        if let Some(path) = &self.config.textfile_path {
+            if let Err(e) = self.metrics.write_textfile(path) {
+                tracing::warn!("Failed to write metrics textfile {}: {}", path.display(), e);
+            }
+        }
+
+        *self
+            .last_run_end
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(Instant::now());
     }
 }
+
+/// Marks a run as in progress for as long as it is held. The flag is cleared
+/// on drop, so a run that panics doesn't block every later run once the
+/// supervisor restarts the scheduler.
+struct RunInProgress<'a>(&'a AtomicBool);
+
+impl<'a> RunInProgress<'a> {
+    fn start(flag: &'a AtomicBool) -> Self {
+        flag.store(true, Ordering::SeqCst);
+        Self(flag)
+    }
+}
+
+impl Drop for RunInProgress<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Returns whether the last run, at `last_run_timestamp` (Unix seconds, 0
+/// before the first run), is more than `stale_after_seconds` old at `now`.
+///
+/// Shared by `/healthz` and the health webhook so both agree on staleness.
+///
+/// # Examples
+///
+/// ```
+/// use netspeed_lite::scheduler::is_stale;
+///
+/// assert!(is_stale(1_000.0, 9_000.0, 7200));
+/// assert!(!is_stale(1_000.0, 2_000.0, 7200));
+/// assert!(!is_stale(0.0, 9_000.0, 7200));
+/// ```
+pub fn is_stale(last_run_timestamp: f64, now: f64, stale_after_seconds: u64) -> bool {
+    last_run_timestamp > 0.0 && now - last_run_timestamp > stale_after_seconds as f64
+}
+
+/// Whether a speedtest argument pins the Ookla server.
+fn is_server_id_arg(arg: &str) -> bool {
+    arg.starts_with("--server-id=")
+}
+
+fn calculate_next_cron_run(schedule: &ScheduleConfig) -> DateTime<Utc> {
+    let expression = schedule
+        .cron_expression
+        .as_ref()
+        .expect("Cron expression required for Cron mode");
+
+    let cron = Schedule::from_str(expression).expect("Invalid cron expression");
+    let tz: Tz = schedule.timezone.parse().expect("Invalid timezone");
+
+    cron.upcoming(tz)
+        .next()
+        .map(|dt| dt.with_timezone(&Utc))
+        .unwrap_or_else(|| Utc::now() + Duration::minutes(1))
+}
+
+fn calculate_next_aligned_run(schedule: &ScheduleConfig) -> DateTime<Utc> {
+    let tz: Tz = schedule.timezone.parse().expect("Invalid timezone");
+    let now_tz = Utc::now().with_timezone(&tz);
+
+    // Calculate next top of hour
+    let next_hour = if now_tz.minute() == 0 && now_tz.second() == 0 && now_tz.nanosecond() == 0 {
+        // If we're exactly at the top of the hour, schedule for next hour
+        now_tz + Duration::hours(1)
+    } else {
+        // Otherwise, go to the next top of hour
+        tz.with_ymd_and_hms(
+            now_tz.year(),
+            now_tz.month(),
+            now_tz.day(),
+            now_tz.hour() + 1,
+            0,
+            0,
+        )
+        .single()
+        .unwrap_or_else(|| now_tz + Duration::hours(1))
+    };
+
+    next_hour.with_timezone(&Utc)
+}
+
+/// Shortest interval the scheduler will wait between runs.
+const MIN_INTERVAL_SECONDS: u64 = 1;
+
+/// Computes the next interval run: `interval_seconds` plus `jitter_seconds`
+/// from now. The jitter only ever delays the run, so it can't land in the past.
+fn calculate_next_interval_run(schedule: &ScheduleConfig, jitter_seconds: u64) -> DateTime<Utc> {
+    // Config loading and schedule updates reject 0, but a config built in code
+    // could still hold it and would schedule every run "now", busy-looping
+    let interval_seconds = if schedule.interval_seconds < MIN_INTERVAL_SECONDS {
+        tracing::debug!(
+            "Interval of {}s clamped to {}s",
+            schedule.interval_seconds,
+            MIN_INTERVAL_SECONDS
+        );
+        MIN_INTERVAL_SECONDS
+    } else {
+        schedule.interval_seconds
+    };
+
+    Utc::now() + Duration::seconds(interval_seconds.saturating_add(jitter_seconds) as i64)
+}
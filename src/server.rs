@@ -2,62 +2,205 @@
 //!
 //! This module defines the Axum HTTP server that exposes the `/metrics` endpoint.
 //! It serves the Prometheus metrics registry to be scraped by a Prometheus instance.
+use crate::config::ScheduleMode;
+use crate::config::ServerConfig;
 use crate::metrics::Metrics;
+use crate::scheduler::{is_stale, upcoming_cron_runs, Scheduler};
 use axum::{
-    extract::State,
-    http::StatusCode,
+    extract::{Query, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::{self, Next},
     response::{Html, IntoResponse, Response},
-    routing::get,
+    routing::{get, post, put},
     Json, Router,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::{TcpListener, TcpSocket};
+use tokio::sync::mpsc;
+
+/// Delay between attempts to bind the server address.
+const BIND_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Maximum number of upcoming run times returned by `/cron/validate` and
+/// `/schedule/upcoming`.
+const MAX_CRON_PREVIEW: usize = 100;
+
+/// Maximum number of runs returned by `/history`.
+const MAX_HISTORY_LIMIT: usize = 1000;
 
 #[derive(Clone)]
 struct AppState {
     metrics: Metrics,
+    config: ServerConfig,
+    started_at: Instant,
+    scheduler: Option<Arc<Scheduler>>,
+    /// Requests an immediate run from the scheduler loop (`POST /run`).
+    run_trigger: Option<mpsc::Sender<String>>,
+}
+
+impl AppState {
+    /// Whether JSON responses should be pretty-printed: `?pretty=` wins over the
+    /// configured default.
+    fn pretty(&self, format: &FormatQuery) -> bool {
+        format.pretty.unwrap_or(self.config.json_pretty)
+    }
+}
+
+/// Query parameters shared by all JSON endpoints.
+#[derive(Deserialize)]
+struct FormatQuery {
+    pretty: Option<bool>,
+}
+
+/// Serializes `value` as a JSON response, pretty-printed if requested.
+fn json_response<T: Serialize>(status: StatusCode, value: &T, pretty: bool) -> Response {
+    let body = if pretty {
+        serde_json::to_string_pretty(value)
+    } else {
+        serde_json::to_string(value)
+    };
+
+    match body {
+        Ok(body) => (status, [("Content-Type", "application/json")], body).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to serialize JSON response: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to serialize response",
+            )
+                .into_response()
+        }
+    }
 }
 
 /// Starts the HTTP server for exposing metrics and health endpoints.
 ///
 /// This function creates an Axum router with the following routes:
 /// - `GET /`: HTML landing page with links to endpoints
-/// - `GET /metrics`: Prometheus metrics in text format
+/// - `GET /metrics`: Prometheus metrics in text format (requires the token when `metrics_token` is set)
 /// - `GET /healthz`: JSON health check status
+/// - `GET /results`: Latest speed test results as JSON
+/// - `GET /history`: Recent runs from the run history database (only via `serve_with_scheduler`)
+/// - `GET /cron/validate`: Validates a cron expression and previews upcoming runs
+/// - `GET /schedule/upcoming`: Next scheduled run times (only via `serve_with_scheduler`)
+/// - `GET /annotations`: Failures and recoveries as Grafana annotations (only via `serve_with_scheduler`)
+/// - `POST /run`: Triggers an immediate speed test (only via `serve_with_scheduler`)
+/// - `PUT /admin/schedule`: Replaces the running schedule (only via `serve_with_scheduler`)
+/// - `POST /admin/test-notification`: Sends a test message to every notification channel (only via `serve_with_scheduler`)
+/// - `GET /debug/outputs`: Raw output of recent runs (only via `serve_with_scheduler`)
+/// - `GET /debug/last-output`: Raw output of the most recent run (only via `serve_with_scheduler`)
 ///
 /// The server runs indefinitely until an error occurs or it's shut down.
 ///
 /// # Arguments
 ///
-/// * `bind_address` - Address to bind the server to (e.g., "0.0.0.0:9109")
+/// * `config` - Server configuration (bind address, bind retries, health settings)
 /// * `metrics` - Metrics instance to expose via the `/metrics` endpoint
 ///
 /// # Returns
 ///
 /// Returns `Ok(())` if the server shuts down gracefully, or `Err` if:
-/// - The bind address is invalid or already in use
+/// - The bind address is invalid, or still in use after `bind_retries` retries
 /// - A critical server error occurs
 ///
 /// # Examples
 ///
 /// ```no_run
+/// use netspeed_lite::config::ServerConfig;
+/// use netspeed_lite::metrics::Metrics;
+/// use netspeed_lite::server;
+///
+/// # async {
+/// let config = ServerConfig {
+///     bind_address: "127.0.0.1:9109".to_string(),
+///     init_grace_seconds: 0,
+///     bind_retries: 5,
+///     max_rss_bytes: None,
+///     json_pretty: false,
+///     metrics_token: None,
+///     admin_token: None,
+///     stale_after_seconds: 7200,
+/// };
+/// let metrics = Metrics::new().unwrap();
+/// server::serve(config, metrics).await.unwrap();
+/// # };
+/// ```
+pub async fn serve(config: ServerConfig, metrics: Metrics) -> anyhow::Result<()> {
+    run_server(config, metrics, None).await
+}
+
+/// Starts the HTTP server with access to the running scheduler.
+///
+/// Same as `serve`, and additionally enables the admin endpoints that act on
+/// the scheduler (`PUT /admin/schedule`, `POST /admin/test-notification`)
+/// when `admin_token` is configured.
+///
+/// # Examples
+///
+/// ```no_run
+/// use netspeed_lite::config::Config;
 /// use netspeed_lite::metrics::Metrics;
+/// use netspeed_lite::scheduler::Scheduler;
 /// use netspeed_lite::server;
+/// use std::sync::Arc;
 ///
 /// # async {
+/// let config = Config::from_env().unwrap();
 /// let metrics = Metrics::new().unwrap();
-/// server::serve("127.0.0.1:9109".to_string(), metrics).await.unwrap();
+/// let scheduler = Arc::new(Scheduler::new(config.clone(), metrics.clone(), None));
+/// server::serve_with_scheduler(config.server, metrics, scheduler).await.unwrap();
 /// # };
 /// ```
-pub async fn serve(bind_address: String, metrics: Metrics) -> anyhow::Result<()> {
-    let state = AppState { metrics };
+pub async fn serve_with_scheduler(
+    config: ServerConfig,
+    metrics: Metrics,
+    scheduler: Arc<Scheduler>,
+) -> anyhow::Result<()> {
+    run_server(config, metrics, Some(scheduler)).await
+}
+
+async fn run_server(
+    config: ServerConfig,
+    metrics: Metrics,
+    scheduler: Option<Arc<Scheduler>>,
+) -> anyhow::Result<()> {
+    let bind_address = config.bind_address.clone();
+    let bind_retries = config.bind_retries;
+    let run_trigger = scheduler.as_ref().map(|scheduler| scheduler.run_trigger());
+    let state = AppState {
+        metrics,
+        config,
+        started_at: Instant::now(),
+        scheduler,
+        run_trigger,
+    };
 
     let app = Router::new()
         .route("/", get(root_handler))
-        .route("/metrics", get(metrics_handler))
+        .route(
+            "/metrics",
+            get(metrics_handler).layer(middleware::from_fn_with_state(
+                state.clone(),
+                require_metrics_token,
+            )),
+        )
         .route("/healthz", get(health_handler))
+        .route("/results", get(results_handler))
+        .route("/history", get(history_handler))
+        .route("/cron/validate", get(cron_validate_handler))
+        .route("/schedule/upcoming", get(upcoming_runs_handler))
+        .route("/annotations", get(annotations_handler))
+        .route("/run", post(run_handler))
+        .route("/admin/schedule", put(update_schedule_handler))
+        .route("/admin/test-notification", post(test_notification_handler))
+        .route("/debug/outputs", get(debug_outputs_handler))
+        .route("/debug/last-output", get(debug_last_output_handler))
         .with_state(state);
 
-    let listener = tokio::net::TcpListener::bind(&bind_address).await?;
+    let listener = bind_with_retry(&bind_address, bind_retries).await?;
     tracing::info!("HTTP server listening on {}", bind_address);
 
     axum::serve(listener, app).await?;
@@ -65,6 +208,50 @@ pub async fn serve(bind_address: String, metrics: Metrics) -> anyhow::Result<()>
     Ok(())
 }
 
+/// Binds the listener, retrying while the address is unavailable.
+///
+/// After a container restart the previous socket can linger in `TIME_WAIT`, so
+/// binding is retried up to `retries` times with a short delay in between.
+async fn bind_with_retry(bind_address: &str, retries: u32) -> anyhow::Result<TcpListener> {
+    let mut attempt = 0;
+    loop {
+        match bind_listener(bind_address).await {
+            Ok(listener) => return Ok(listener),
+            Err(e) if attempt < retries => {
+                attempt += 1;
+                tracing::warn!(
+                    "Failed to bind {}: {} (retry {}/{} in {:?})",
+                    bind_address,
+                    e,
+                    attempt,
+                    retries,
+                    BIND_RETRY_DELAY
+                );
+                tokio::time::sleep(BIND_RETRY_DELAY).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Binds a listener with `SO_REUSEADDR` set so sockets in `TIME_WAIT` don't block startup.
+async fn bind_listener(bind_address: &str) -> anyhow::Result<TcpListener> {
+    let addr = tokio::net::lookup_host(bind_address)
+        .await?
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("No address resolved for {}", bind_address))?;
+
+    let socket = if addr.is_ipv4() {
+        TcpSocket::new_v4()?
+    } else {
+        TcpSocket::new_v6()?
+    };
+    socket.set_reuseaddr(true)?;
+    socket.bind(addr)?;
+
+    Ok(socket.listen(1024)?)
+}
+
 async fn root_handler() -> Html<&'static str> {
     Html(
         r#"
@@ -89,14 +276,52 @@ async fn root_handler() -> Html<&'static str> {
             <div class="endpoint">
                 <strong>Health:</strong> <a href="/healthz">/healthz</a>
             </div>
+            <div class="endpoint">
+                <strong>Latest results:</strong> <a href="/results">/results</a>
+            </div>
         </body>
         </html>
         "#,
     )
 }
 
+#[derive(Deserialize)]
+struct MetricsQuery {
+    token: Option<String>,
+}
+
+/// Middleware guarding `/metrics` when `metrics_token` is set: the token must
+/// be sent as an `Authorization: Bearer <token>` header or as `?token=<token>`,
+/// otherwise the request is rejected with 401.
+async fn require_metrics_token(
+    State(state): State<AppState>,
+    Query(query): Query<MetricsQuery>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Some(expected) = &state.config.metrics_token {
+        let provided = bearer_token(request.headers())
+            .or(query.token.as_deref())
+            .unwrap_or_default();
+        if !constant_time_eq(provided.as_bytes(), expected.as_bytes()) {
+            return (StatusCode::UNAUTHORIZED, "Invalid or missing token").into_response();
+        }
+    }
+
+    next.run(request).await
+}
+
 async fn metrics_handler(State(state): State<AppState>) -> Response {
-    match state.metrics.render() {
+    // Timed here rather than in `render` so only scrapes are measured; the
+    // value therefore describes the previous scrape
+    let start = std::time::Instant::now();
+    let rendered = state.metrics.render();
+    state
+        .metrics
+        .scrape_duration_seconds
+        .set(start.elapsed().as_secs_f64());
+
+    match rendered {
         Ok(metrics) => (
             StatusCode::OK,
             [("Content-Type", "text/plain; version=0.0.4")],
@@ -114,19 +339,56 @@ async fn metrics_handler(State(state): State<AppState>) -> Response {
     }
 }
 
+/// Returns the token of an `Authorization: Bearer <token>` header, if present.
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+/// Compares two byte strings in time independent of where they first differ,
+/// so response timing doesn't leak how much of a guessed token was correct.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
 #[derive(Serialize)]
 struct HealthStatus {
     status: String,
     last_run_timestamp: f64,
     last_success_timestamp: f64,
+    /// Whether the last run is older than `stale_after_seconds`.
+    stale: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
 }
 
-async fn health_handler(State(state): State<AppState>) -> Response {
+async fn health_handler(
+    State(state): State<AppState>,
+    Query(format): Query<FormatQuery>,
+) -> Response {
     let last_run = state.metrics.run_timestamp_seconds.get();
     let last_success = state.metrics.last_success.get();
+    let memory_bytes = state.metrics.process_memory_bytes.get();
+
+    // Excessive memory usage (likely a leak) takes precedence over run status
+    let rss_exceeded = state
+        .config
+        .max_rss_bytes
+        .filter(|&limit| memory_bytes > limit as f64);
+
+    // A successful run with too much jitter is degraded too
+    let jitter_exceeded = last_success > 0.0 && state.metrics.jitter_exceeded.get() > 0.0;
 
     // Determine status based on whether we've had a successful run
-    let status = if last_success > 0.0 {
+    let status = if rss_exceeded.is_some() || jitter_exceeded {
+        "degraded"
+    } else if last_success > 0.0 {
         "healthy"
     } else if last_run > 0.0 {
         "unhealthy"
@@ -134,18 +396,468 @@ async fn health_handler(State(state): State<AppState>) -> Response {
         "initializing"
     };
 
+    // Nothing to flag before the first run
+    let now = chrono::Utc::now().timestamp() as f64;
+    let stale = is_stale(last_run, now, state.config.stale_after_seconds);
+
     let health = HealthStatus {
         status: status.to_string(),
         last_run_timestamp: last_run,
         last_success_timestamp: if last_success > 0.0 { last_run } else { 0.0 },
+        stale,
+        reason: match rss_exceeded {
+            Some(limit) => Some(format!(
+                "process memory {} bytes exceeds limit of {} bytes",
+                memory_bytes, limit
+            )),
+            None => jitter_exceeded
+                .then(|| "jitter of the last run exceeds NETSPEED_MAX_JITTER_MS".to_string()),
+        },
     };
 
-    // Return 503 if never successfully run or last run failed
-    let status_code = if status == "healthy" {
+    // Return 503 if never successfully run or last run failed, unless we are
+    // still within the startup grace period
+    let init_grace = Duration::from_secs(state.config.init_grace_seconds);
+    let in_grace = status == "initializing" && state.started_at.elapsed() < init_grace;
+    let status_code = if status == "healthy" || in_grace {
         StatusCode::OK
     } else {
         StatusCode::SERVICE_UNAVAILABLE
     };
 
-    (status_code, Json(health)).into_response()
+    json_response(status_code, &health, state.pretty(&format))
+}
+
+/// Latest results, read back from the metrics gauges.
+#[derive(Serialize)]
+struct LatestResults {
+    download_bps: f64,
+    upload_bps: f64,
+    latency_seconds: f64,
+    /// `null` until a run reports jitter.
+    jitter_seconds: Option<f64>,
+    /// `null` until a run reports packet loss.
+    packet_loss_ratio: Option<f64>,
+    last_run_timestamp: f64,
+    last_success: bool,
+}
+
+async fn results_handler(
+    State(state): State<AppState>,
+    Query(format): Query<FormatQuery>,
+) -> Response {
+    let metrics = &state.metrics;
+    let results = LatestResults {
+        download_bps: metrics.download_bps.get(),
+        upload_bps: metrics.upload_bps.get(),
+        latency_seconds: metrics.latency_seconds.get(),
+        jitter_seconds: metrics.jitter_seconds_value(),
+        packet_loss_ratio: metrics.packet_loss_ratio_value(),
+        last_run_timestamp: metrics.run_timestamp_seconds.get(),
+        last_success: metrics.last_success.get() > 0.0,
+    };
+
+    json_response(StatusCode::OK, &results, state.pretty(&format))
+}
+
+#[derive(Deserialize)]
+struct HistoryQuery {
+    limit: Option<usize>,
+}
+
+/// Serves the last `limit` runs (default 100) from the run history database,
+/// oldest first.
+async fn history_handler(
+    State(state): State<AppState>,
+    Query(query): Query<HistoryQuery>,
+    Query(format): Query<FormatQuery>,
+) -> Response {
+    let pretty = state.pretty(&format);
+    let Some(history) = state
+        .scheduler
+        .as_ref()
+        .and_then(|scheduler| scheduler.history())
+    else {
+        let body = ErrorBody {
+            error: "run history is disabled".to_string(),
+        };
+        return json_response(StatusCode::NOT_FOUND, &body, pretty);
+    };
+
+    let limit = query.limit.unwrap_or(100).min(MAX_HISTORY_LIMIT);
+    match history.recent(limit) {
+        Ok(records) => json_response(StatusCode::OK, &records, pretty),
+        Err(e) => {
+            tracing::error!("Failed to read run history: {:#}", e);
+            let body = ErrorBody {
+                error: "failed to read run history".to_string(),
+            };
+            json_response(StatusCode::INTERNAL_SERVER_ERROR, &body, pretty)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct CronValidateQuery {
+    expr: String,
+    tz: Option<String>,
+    count: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct CronPreview {
+    expression: String,
+    timezone: String,
+    upcoming: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String,
+}
+
+async fn cron_validate_handler(
+    State(state): State<AppState>,
+    Query(query): Query<CronValidateQuery>,
+    Query(format): Query<FormatQuery>,
+) -> Response {
+    let pretty = state.pretty(&format);
+    let timezone = query.tz.unwrap_or_else(|| "UTC".to_string());
+    let count = query.count.unwrap_or(5).min(MAX_CRON_PREVIEW);
+
+    match upcoming_cron_runs(&query.expr, &timezone, count) {
+        Ok(runs) => {
+            let preview = CronPreview {
+                expression: query.expr,
+                timezone,
+                upcoming: runs.iter().map(|dt| dt.to_rfc3339()).collect(),
+            };
+            json_response(StatusCode::OK, &preview, pretty)
+        }
+        Err(e) => json_response(
+            StatusCode::BAD_REQUEST,
+            &ErrorBody {
+                error: e.to_string(),
+            },
+            pretty,
+        ),
+    }
+}
+
+#[derive(Deserialize)]
+struct UpcomingQuery {
+    count: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct UpcomingRuns {
+    mode: ScheduleMode,
+    timezone: String,
+    upcoming: Vec<String>,
+}
+
+async fn upcoming_runs_handler(
+    State(state): State<AppState>,
+    Query(query): Query<UpcomingQuery>,
+    Query(format): Query<FormatQuery>,
+) -> Response {
+    let pretty = state.pretty(&format);
+    let Some(scheduler) = &state.scheduler else {
+        let body = ErrorBody {
+            error: "no scheduler attached".to_string(),
+        };
+        return json_response(StatusCode::NOT_FOUND, &body, pretty);
+    };
+
+    let count = query.count.unwrap_or(5).min(MAX_CRON_PREVIEW);
+    let schedule = scheduler.schedule();
+    let upcoming = UpcomingRuns {
+        mode: schedule.mode,
+        timezone: schedule.timezone,
+        upcoming: scheduler
+            .upcoming_runs(count)
+            .iter()
+            .map(|dt| dt.to_rfc3339())
+            .collect(),
+    };
+    json_response(StatusCode::OK, &upcoming, pretty)
+}
+
+/// A Grafana annotation in the Simple JSON datasource format.
+#[derive(Serialize)]
+struct Annotation {
+    /// Milliseconds since the Unix epoch.
+    time: i64,
+    title: &'static str,
+    text: String,
+    tags: [&'static str; 2],
+}
+
+async fn annotations_handler(
+    State(state): State<AppState>,
+    Query(format): Query<FormatQuery>,
+) -> Response {
+    let pretty = state.pretty(&format);
+    let Some(scheduler) = &state.scheduler else {
+        let body = ErrorBody {
+            error: "no scheduler attached".to_string(),
+        };
+        return json_response(StatusCode::NOT_FOUND, &body, pretty);
+    };
+
+    let annotations: Vec<Annotation> = scheduler
+        .health_events()
+        .into_iter()
+        .map(|event| {
+            let (title, tag) = if event.recovered {
+                ("Speed tests recovered", "recovery")
+            } else {
+                ("Speed test failing", "failure")
+            };
+            Annotation {
+                time: event.time.timestamp_millis(),
+                title,
+                text: event
+                    .error
+                    .unwrap_or_else(|| "Runs are succeeding again".to_string()),
+                tags: ["netspeed", tag],
+            }
+        })
+        .collect();
+    json_response(StatusCode::OK, &annotations, pretty)
+}
+
+#[derive(Serialize)]
+struct RunAccepted {
+    run_id: String,
+}
+
+async fn run_handler(State(state): State<AppState>, Query(format): Query<FormatQuery>) -> Response {
+    let pretty = state.pretty(&format);
+    let (Some(scheduler), Some(run_trigger)) = (&state.scheduler, &state.run_trigger) else {
+        let body = ErrorBody {
+            error: "no scheduler attached".to_string(),
+        };
+        return json_response(StatusCode::NOT_FOUND, &body, pretty);
+    };
+
+    let reject = |status: StatusCode, message: &str| {
+        let body = ErrorBody {
+            error: message.to_string(),
+        };
+        json_response(status, &body, pretty)
+    };
+
+    if let Some(remaining) = scheduler.cooldown_remaining() {
+        let mut response = reject(
+            StatusCode::TOO_MANY_REQUESTS,
+            "the minimum gap since the previous run has not elapsed",
+        );
+        let retry_after = remaining.as_secs_f64().ceil() as u64;
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, retry_after.into());
+        return response;
+    }
+
+    let run_id = format!("manual-{}", chrono::Utc::now().timestamp_millis());
+
+    // The scheduler loop runs triggered runs one at a time, so overlapping
+    // runs are started here instead, bounded by `max_concurrent_runs`
+    if scheduler.schedule().allow_overlap {
+        if scheduler.at_run_capacity() {
+            return reject(
+                StatusCode::TOO_MANY_REQUESTS,
+                "the maximum number of concurrent runs is in progress",
+            );
+        }
+        let scheduler = scheduler.clone();
+        let log_id = run_id.clone();
+        tokio::spawn(async move {
+            tracing::info!(run_id = %log_id, "Starting on-demand run");
+            scheduler.run_once().await;
+        });
+        return json_response(StatusCode::ACCEPTED, &RunAccepted { run_id }, pretty);
+    }
+
+    if scheduler.is_running() {
+        return reject(StatusCode::CONFLICT, "a speed test is already running");
+    }
+
+    match run_trigger.try_send(run_id.clone()) {
+        Ok(()) => json_response(StatusCode::ACCEPTED, &RunAccepted { run_id }, pretty),
+        // The scheduler owns the receiver, so the channel can only be full
+        Err(_) => reject(StatusCode::CONFLICT, "a speed test is already queued"),
+    }
+}
+
+/// Checks that the admin API is enabled and the request carries the admin
+/// bearer token, returning the scheduler to act on.
+///
+/// Fails with 404 when the admin API is disabled (no `admin_token` or no
+/// scheduler) and 401 when the token is missing or wrong.
+fn authorize_admin<'a>(
+    state: &'a AppState,
+    headers: &HeaderMap,
+) -> Result<&'a Arc<Scheduler>, (StatusCode, &'static str)> {
+    let (Some(expected), Some(scheduler)) = (&state.config.admin_token, &state.scheduler) else {
+        return Err((StatusCode::NOT_FOUND, "admin API is disabled"));
+    };
+
+    let provided = bearer_token(headers).unwrap_or_default();
+    if !constant_time_eq(provided.as_bytes(), expected.as_bytes()) {
+        return Err((StatusCode::UNAUTHORIZED, "invalid or missing bearer token"));
+    }
+
+    Ok(scheduler)
+}
+
+/// Request body for `PUT /admin/schedule`. Omitted fields keep their current value.
+#[derive(Deserialize)]
+struct ScheduleUpdate {
+    mode: ScheduleMode,
+    interval_seconds: Option<u64>,
+    cron_expression: Option<String>,
+    timezone: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ScheduleView {
+    mode: ScheduleMode,
+    interval_seconds: u64,
+    cron_expression: Option<String>,
+    timezone: String,
+    next_run: String,
+}
+
+async fn update_schedule_handler(
+    State(state): State<AppState>,
+    Query(format): Query<FormatQuery>,
+    headers: HeaderMap,
+    Json(update): Json<ScheduleUpdate>,
+) -> Response {
+    let pretty = state.pretty(&format);
+    let error = |status: StatusCode, message: &str| {
+        json_response(
+            status,
+            &ErrorBody {
+                error: message.to_string(),
+            },
+            pretty,
+        )
+    };
+
+    let scheduler = match authorize_admin(&state, &headers) {
+        Ok(scheduler) => scheduler,
+        Err((status, message)) => return error(status, message),
+    };
+
+    let mut schedule = scheduler.schedule();
+    schedule.mode = update.mode;
+    if let Some(interval_seconds) = update.interval_seconds {
+        schedule.interval_seconds = interval_seconds;
+    }
+    if let Some(cron_expression) = update.cron_expression {
+        schedule.cron_expression = Some(cron_expression);
+    }
+    if let Some(timezone) = update.timezone {
+        schedule.timezone = timezone;
+    }
+
+    if let Err(e) = scheduler.update_schedule(schedule.clone()) {
+        return error(StatusCode::BAD_REQUEST, &e.to_string());
+    }
+
+    let view = ScheduleView {
+        mode: schedule.mode,
+        interval_seconds: schedule.interval_seconds,
+        cron_expression: schedule.cron_expression,
+        timezone: schedule.timezone,
+        next_run: scheduler.next_run_time().to_rfc3339(),
+    };
+    json_response(StatusCode::OK, &view, pretty)
+}
+
+/// Outcome of sending the test notification through one channel.
+#[derive(Serialize)]
+struct ChannelResult {
+    success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+async fn test_notification_handler(
+    State(state): State<AppState>,
+    Query(format): Query<FormatQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let pretty = state.pretty(&format);
+    let scheduler = match authorize_admin(&state, &headers) {
+        Ok(scheduler) => scheduler,
+        Err((status, message)) => {
+            let body = ErrorBody {
+                error: message.to_string(),
+            };
+            return json_response(status, &body, pretty);
+        }
+    };
+
+    let results: BTreeMap<&str, ChannelResult> = scheduler
+        .send_test_notification()
+        .await
+        .into_iter()
+        .map(|(channel, result)| {
+            let result = ChannelResult {
+                success: result.is_ok(),
+                error: result.err().map(|e| e.to_string()),
+            };
+            (channel, result)
+        })
+        .collect();
+
+    json_response(StatusCode::OK, &results, pretty)
+}
+
+async fn debug_outputs_handler(
+    State(state): State<AppState>,
+    Query(format): Query<FormatQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let pretty = state.pretty(&format);
+    match authorize_admin(&state, &headers) {
+        Ok(scheduler) => json_response(StatusCode::OK, &scheduler.debug_outputs(), pretty),
+        Err((status, message)) => {
+            let body = ErrorBody {
+                error: message.to_string(),
+            };
+            json_response(status, &body, pretty)
+        }
+    }
+}
+
+async fn debug_last_output_handler(
+    State(state): State<AppState>,
+    Query(format): Query<FormatQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let pretty = state.pretty(&format);
+    let scheduler = match authorize_admin(&state, &headers) {
+        Ok(scheduler) => scheduler,
+        Err((status, message)) => {
+            let body = ErrorBody {
+                error: message.to_string(),
+            };
+            return json_response(status, &body, pretty);
+        }
+    };
+
+    match scheduler.last_output() {
+        Some(output) => json_response(StatusCode::OK, &output, pretty),
+        None => {
+            let body = ErrorBody {
+                error: "no output captured".to_string(),
+            };
+            json_response(StatusCode::NOT_FOUND, &body, pretty)
+        }
+    }
 }
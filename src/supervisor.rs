@@ -0,0 +1,66 @@
+//! # Task Supervisor
+//!
+//! This module keeps long-running background tasks (scheduler, resource monitor) alive.
+//! A task that panics is restarted with exponential backoff and counted in
+//! `netspeed_task_restarts_total{task}`; a task that returns normally is not restarted.
+use crate::metrics::Metrics;
+use std::future::Future;
+use std::time::Duration;
+
+/// Upper bound for the delay between restarts.
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// Runs a task produced by `make_task`, restarting it whenever it panics.
+///
+/// The delay before each restart starts at `backoff` and doubles after every
+/// panic, capped at 5 minutes. The function returns once the task exits normally
+/// or is cancelled.
+///
+/// # Arguments
+///
+/// * `name` - Task name, used in logs and as the `task` label
+/// * `metrics` - Metrics instance for counting restarts
+/// * `backoff` - Delay before the first restart
+/// * `make_task` - Factory creating a fresh instance of the task future
+///
+/// # Examples
+///
+/// ```no_run
+/// use netspeed_lite::metrics::Metrics;
+/// use netspeed_lite::supervisor::supervise;
+/// use std::time::Duration;
+///
+/// # async {
+/// let metrics = Metrics::new().unwrap();
+/// supervise("worker", metrics, Duration::from_secs(1), || async {
+///     // long-running work
+/// })
+/// .await;
+/// # };
+/// ```
+pub async fn supervise<F, Fut>(name: &str, metrics: Metrics, backoff: Duration, mut make_task: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let mut delay = backoff;
+
+    loop {
+        match tokio::spawn(make_task()).await {
+            Ok(()) => {
+                tracing::error!("{} task exited unexpectedly", name);
+                return;
+            }
+            Err(e) if e.is_panic() => {
+                tracing::error!("{} task panicked, restarting in {:?}", name, delay);
+                metrics.task_restarts_total.with_label_values(&[name]).inc();
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(MAX_BACKOFF);
+            }
+            Err(e) => {
+                tracing::error!("{} task was cancelled: {}", name, e);
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! # Health Webhook
+//!
+//! This module posts health-state transitions to a user-configured webhook.
+//! It handles:
+//! - The JSON payload describing a transition (`old_state`, `new_state`, `timestamp`).
+//! - Delivering the payload over HTTP.
+//!
+//! Unlike notifications, which report every run, the webhook only fires when the
+//! health state changes. Sending requires the `notify` cargo feature; without it,
+//! `HealthWebhook` is a no-op.
+use serde::Serialize;
+
+/// Body posted to the health webhook on each state transition.
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthTransition {
+    pub old_state: &'static str,
+    pub new_state: &'static str,
+    /// Time of the transition, RFC3339.
+    pub timestamp: String,
+}
+
+#[cfg(feature = "notify")]
+pub struct HealthWebhook {
+    url: String,
+    client: reqwest::Client,
+}
+
+/// No-op webhook used when the crate is built without the `notify` feature.
+#[cfg(not(feature = "notify"))]
+pub struct HealthWebhook;
+
+#[cfg(not(feature = "notify"))]
+impl HealthWebhook {
+    /// Creates a no-op webhook. Nothing is ever sent because the crate was built
+    /// without the `notify` feature.
+    pub fn new(url: String) -> Self {
+        tracing::warn!(
+            "Health webhook {} requested but the `notify` feature is disabled",
+            url
+        );
+        Self
+    }
+
+    /// Drops the transition without sending anything.
+    pub async fn send(&self, _transition: &HealthTransition) {
+        tracing::debug!("Health webhook skipped: built without the `notify` feature");
+    }
+}
+
+#[cfg(feature = "notify")]
+impl HealthWebhook {
+    /// Creates a webhook posting to `url` with a 30-second request timeout.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the HTTP client cannot be created (rare, indicates system issues).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use netspeed_lite::webhook::HealthWebhook;
+    ///
+    /// let webhook = HealthWebhook::new("https://example.com/hooks/netspeed".to_string());
+    /// ```
+    pub fn new(url: String) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(30))
+            .pool_max_idle_per_host(1)
+            .build()
+            .expect("Failed to create HTTP client");
+
+        Self { url, client }
+    }
+
+    /// Posts a transition as JSON. Failures are logged, never returned, so a
+    /// broken webhook can't affect the scheduler.
+    pub async fn send(&self, transition: &HealthTransition) {
+        let result = self
+            .client
+            .post(&self.url)
+            .json(transition)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+
+        match result {
+            Ok(_) => tracing::info!(
+                "Health webhook sent ({} -> {})",
+                transition.old_state,
+                transition.new_state
+            ),
+            Err(e) => tracing::error!("Failed to send health webhook: {}", e),
+        }
+    }
+}
@@ -0,0 +1,135 @@
+//! Shared helpers for integration tests.
+#![allow(dead_code)]
+
+use axum::{extract::State, http::HeaderMap, http::StatusCode, routing::post, Router};
+use netspeed_lite::config::{
+    Config, NotifyOn, NtfyConfig, OutputFormat, OutputStream, ScheduleConfig, ScheduleMode,
+    ServerConfig, SpeedUnit, SpeedtestConfig,
+};
+use std::sync::{Arc, Mutex};
+
+/// A notification captured by the mock ntfy server: request headers and body.
+pub type Received = Arc<Mutex<Vec<(HeaderMap, String)>>>;
+
+/// Starts a mock ntfy server on an ephemeral port that records every POST to
+/// `/topic` and answers with `status`.
+///
+/// Returns the topic URL and the list of received notifications.
+pub async fn spawn_mock_ntfy(status: StatusCode) -> (String, Received) {
+    spawn_mock_ntfy_sequence(vec![status]).await
+}
+
+/// Like `spawn_mock_ntfy`, but answers the n-th request with `statuses[n]`,
+/// repeating the last status once the list is exhausted.
+pub async fn spawn_mock_ntfy_sequence(statuses: Vec<StatusCode>) -> (String, Received) {
+    let received: Received = Arc::new(Mutex::new(Vec::new()));
+    let statuses = Arc::new(statuses);
+    let app = Router::new()
+        .route(
+            "/topic",
+            post(
+                move |State(received): State<Received>, headers: HeaderMap, body: String| async move {
+                    let mut received = received.lock().unwrap();
+                    let status = statuses[received.len().min(statuses.len() - 1)];
+                    received.push((headers, body));
+                    status
+                },
+            ),
+        )
+        .with_state(received.clone());
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("Failed to bind mock ntfy server");
+    let addr = listener.local_addr().expect("Failed to read local address");
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    (format!("http://{}/topic", addr), received)
+}
+
+/// Builds an ntfy configuration pointing at `url` with default settings.
+pub fn ntfy_config(url: &str) -> NtfyConfig {
+    NtfyConfig {
+        url: url.to_string(),
+        token: None,
+        title: "netspeed-lite".to_string(),
+        tags: "speedtest,isp".to_string(),
+        priority: 3,
+        click_url: None,
+        markdown: false,
+        speed_unit: SpeedUnit::Mbps,
+        quality_tags: false,
+        max_retries: 0,
+        max_chars: None,
+    }
+}
+
+/// Builds a complete configuration for the given schedule mode with test defaults.
+pub fn create_test_config(mode: ScheduleMode) -> Config {
+    Config {
+        server: ServerConfig {
+            bind_address: "127.0.0.1:9109".to_string(),
+            init_grace_seconds: 0,
+            bind_retries: 0,
+            max_rss_bytes: None,
+            json_pretty: false,
+            metrics_token: None,
+            admin_token: None,
+            stale_after_seconds: 7200,
+        },
+        schedule: ScheduleConfig {
+            mode,
+            interval_seconds: 3600,
+            cron_expression: Some("0 * * * *".to_string()),
+            timezone: "UTC".to_string(),
+            allow_overlap: false,
+            max_concurrent_runs: 1,
+            min_gap_seconds: 0,
+            jitter_seconds: 0,
+            adaptive_min_seconds: 900,
+            adaptive_max_seconds: 14400,
+            adaptive_tolerance: 0.1,
+        },
+        speedtest: SpeedtestConfig {
+            command: "speedtest".to_string(),
+            args: vec!["--format=json".to_string()],
+            timeout_seconds: 120,
+            first_output_timeout_seconds: None,
+            selection_timeout_seconds: None,
+            max_attempts: 1,
+            allow_partial: false,
+            parse_last_line: false,
+            max_latency_ms: None,
+            output_stream: OutputStream::Stdout,
+            output_format: OutputFormat::Ookla,
+        },
+        ntfy: None,
+        notify_on: NotifyOn {
+            success: true,
+            failure: true,
+        },
+        notify_success_streak: 0,
+        server_failover_threshold: 0,
+        single_connection: false,
+        failure_verbose: false,
+        notify_on_start: false,
+        resource_interval_seconds: 15,
+        cpu_smoothing_alpha: 1.0,
+        notify_on_high_cpu: None,
+        notify_on_high_memory: None,
+        high_usage_samples: 3,
+        high_usage_cooldown_seconds: 3600,
+        suppress_initial_gauges: false,
+        expose_external_ip: false,
+        expose_interface: false,
+        success_criterion_cmd: None,
+        source_label: None,
+        textfile_path: None,
+        db_path: None,
+        health_webhook_url: None,
+        max_jitter_ms: None,
+        anomaly_sigma: 3.0,
+        anomaly_notify: false,
+        debug_capture_count: 0,
+    }
+}
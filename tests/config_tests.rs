@@ -1,25 +1,81 @@
-use netspeed_lite::config::Config;
+use netspeed_lite::config::{
+    detect_system_timezone, ignored_schedule_vars, normalize_ookla_args, Config, ConfigError,
+    OutputFormat, OutputStream, ScheduleMode, SpeedUnit,
+};
 use serial_test::serial;
 use std::env;
+use std::path::Path;
 
 // Helper to clear all netspeed env vars before each test
 fn clear_env_vars() {
     let keys = [
+        "NETSPEED_CONFIG_FILE",
         "NETSPEED_BIND",
+        "NETSPEED_INIT_GRACE_SECONDS",
+        "NETSPEED_BIND_RETRIES",
+        "NETSPEED_MAX_RSS_BYTES",
+        "NETSPEED_JSON_PRETTY",
+        "NETSPEED_METRICS_TOKEN",
+        "NETSPEED_ADMIN_TOKEN",
+        "NETSPEED_STALE_AFTER_SECONDS",
         "NETSPEED_SCHEDULE_MODE",
         "NETSPEED_INTERVAL_SECONDS",
         "NETSPEED_SCHEDULE",
         "NETSPEED_TIMEZONE",
         "NETSPEED_ALLOW_OVERLAP",
+        "NETSPEED_MIN_GAP_SECONDS",
+        "NETSPEED_SCHEDULE_JITTER_SECONDS",
+        "NETSPEED_MAX_CONCURRENT_RUNS",
+        "NETSPEED_ADAPTIVE_MIN_SECONDS",
+        "NETSPEED_ADAPTIVE_MAX_SECONDS",
+        "NETSPEED_ADAPTIVE_TOLERANCE",
         "NETSPEED_TIMEOUT_SECONDS",
+        "NETSPEED_FIRST_OUTPUT_TIMEOUT_SECONDS",
+        "NETSPEED_SELECTION_TIMEOUT_SECONDS",
+        "NETSPEED_MAX_ATTEMPTS",
+        "NETSPEED_OUTPUT_STREAM",
+        "NETSPEED_OUTPUT_FORMAT",
+        "NETSPEED_OOKLA_PROGRESS",
+        "NETSPEED_ALLOW_PARTIAL",
+        "NETSPEED_PARSE_LAST_LINE",
+        "NETSPEED_MAX_LATENCY_MS",
         "NETSPEED_NTFY_URL",
         "NETSPEED_NTFY_TOKEN",
         "NETSPEED_NTFY_TITLE",
         "NETSPEED_NTFY_TAGS",
         "NETSPEED_NTFY_PRIORITY",
         "NETSPEED_NTFY_CLICK",
+        "NETSPEED_NTFY_MARKDOWN",
+        "NETSPEED_NTFY_QUALITY_TAGS",
+        "NETSPEED_NTFY_MAX_RETRIES",
+        "NETSPEED_NOTIFY_MAX_CHARS",
+        "NETSPEED_SPEED_UNIT",
         "NETSPEED_NOTIFY_ON",
+        "NETSPEED_NOTIFY_SUCCESS_STREAK",
+        "NETSPEED_SERVER_ID",
+        "NETSPEED_SERVER_FAILOVER_THRESHOLD",
+        "NETSPEED_SINGLE_CONNECTION",
+        "NETSPEED_FAILURE_VERBOSE",
+        "NETSPEED_NOTIFY_ON_START",
         "NETSPEED_RESOURCE_INTERVAL_SECONDS",
+        "NETSPEED_CPU_SMOOTHING_ALPHA",
+        "NETSPEED_NOTIFY_ON_HIGH_CPU",
+        "NETSPEED_NOTIFY_ON_HIGH_MEMORY",
+        "NETSPEED_HIGH_USAGE_SAMPLES",
+        "NETSPEED_HIGH_USAGE_COOLDOWN_SECONDS",
+        "NETSPEED_SUPPRESS_INITIAL_GAUGES",
+        "NETSPEED_EXPOSE_EXTERNAL_IP",
+        "NETSPEED_EXPOSE_INTERFACE",
+        "NETSPEED_SUCCESS_CRITERION_CMD",
+        "NETSPEED_SOURCE_LABEL",
+        "NETSPEED_TEXTFILE_PATH",
+        "NETSPEED_DB_PATH",
+        "NETSPEED_HEALTH_WEBHOOK_URL",
+        "NETSPEED_STRICT_CONFIG",
+        "NETSPEED_MAX_JITTER_MS",
+        "NETSPEED_ANOMALY_SIGMA",
+        "NETSPEED_ANOMALY_NOTIFY",
+        "NETSPEED_DEBUG_CAPTURE_COUNT",
     ];
     for key in &keys {
         env::remove_var(key);
@@ -37,13 +93,90 @@ fn test_default_configuration() {
 
     // Then: Should use all default values
     assert_eq!(config.server.bind_address, "0.0.0.0:9109");
+    assert_eq!(config.server.init_grace_seconds, 0);
+    assert_eq!(config.server.bind_retries, 5);
+    assert_eq!(config.server.max_rss_bytes, None);
+    assert!(!config.server.json_pretty);
+    assert_eq!(config.server.metrics_token, None);
+    assert_eq!(config.server.admin_token, None);
+    assert_eq!(config.server.stale_after_seconds, 7200);
     assert_eq!(config.schedule.interval_seconds, 3600);
-    assert_eq!(config.schedule.timezone, "Europe/Brussels");
+    let expected_timezone =
+        detect_system_timezone(Path::new("/etc/localtime")).unwrap_or_else(|| "UTC".to_string());
+    assert_eq!(config.schedule.timezone, expected_timezone);
     assert!(!config.schedule.allow_overlap);
+    assert_eq!(config.schedule.max_concurrent_runs, 1);
+    assert_eq!(config.schedule.min_gap_seconds, 0);
+    assert_eq!(config.schedule.jitter_seconds, 0);
+    assert_eq!(config.schedule.adaptive_min_seconds, 900);
+    assert_eq!(config.schedule.adaptive_max_seconds, 14400);
+    assert_eq!(config.schedule.adaptive_tolerance, 0.1);
     assert_eq!(config.speedtest.timeout_seconds, 120);
-    assert!(config.notify_on.success);
-    assert!(config.notify_on.failure);
+    assert_eq!(config.speedtest.first_output_timeout_seconds, None);
+    assert_eq!(config.speedtest.selection_timeout_seconds, None);
+    assert_eq!(config.speedtest.max_attempts, 1);
+    assert_eq!(config.speedtest.output_stream, OutputStream::Stdout);
+    assert_eq!(config.speedtest.output_format, OutputFormat::Ookla);
+    assert!(!config.speedtest.allow_partial);
+    assert!(!config.speedtest.parse_last_line);
+    assert_eq!(config.speedtest.max_latency_ms, None);
+    assert!(!config.notify_on.success);
+    assert!(!config.notify_on.failure);
+    assert_eq!(config.notify_success_streak, 0);
+    assert_eq!(config.server_failover_threshold, 0);
+    assert!(!config.single_connection);
+    assert!(!config.failure_verbose);
+    assert!(!config.notify_on_start);
     assert_eq!(config.resource_interval_seconds, 15);
+    assert_eq!(config.cpu_smoothing_alpha, 1.0);
+    assert_eq!(config.notify_on_high_cpu, None);
+    assert_eq!(config.notify_on_high_memory, None);
+    assert_eq!(config.high_usage_samples, 3);
+    assert_eq!(config.high_usage_cooldown_seconds, 3600);
+    assert!(!config.suppress_initial_gauges);
+    assert!(!config.expose_external_ip);
+    assert!(!config.expose_interface);
+    assert_eq!(config.success_criterion_cmd, None);
+    assert_eq!(config.source_label, None);
+    assert_eq!(config.textfile_path, None);
+    assert_eq!(config.db_path, None);
+    assert_eq!(config.health_webhook_url, None);
+    assert_eq!(config.max_jitter_ms, None);
+    assert_eq!(config.anomaly_sigma, 3.0);
+    assert_eq!(config.debug_capture_count, 0);
+    assert!(!config.anomaly_notify);
+}
+
+#[test]
+fn test_detect_system_timezone_from_symlink() {
+    // Given: A localtime symlink pointing into a zoneinfo database
+    let dir = env::temp_dir().join(format!("netspeed-tz-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let link = dir.join("localtime");
+    let _ = std::fs::remove_file(&link);
+    std::os::unix::fs::symlink("/usr/share/zoneinfo/America/New_York", &link).unwrap();
+
+    // When: Detecting the timezone
+    let detected = detect_system_timezone(&link);
+
+    // Then: Should return the IANA name from the symlink target
+    assert_eq!(detected.as_deref(), Some("America/New_York"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_detect_system_timezone_falls_back() {
+    // Given: A localtime path that is missing or not a zoneinfo symlink
+    let dir = env::temp_dir().join(format!("netspeed-tz-bad-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let link = dir.join("localtime");
+    let _ = std::fs::remove_file(&link);
+    std::os::unix::fs::symlink("/somewhere/else/Not_A_Zone", &link).unwrap();
+
+    // When/Then: Detection yields nothing so the caller can fall back to UTC
+    assert_eq!(detect_system_timezone(&link), None);
+    assert_eq!(detect_system_timezone(&dir.join("missing")), None);
+    std::fs::remove_dir_all(&dir).unwrap();
 }
 
 #[test]
@@ -57,8 +190,10 @@ fn test_invalid_timezone() {
     let result = Config::from_env();
 
     // Then: Should fail with timezone error
-    assert!(result.is_err());
-    assert!(result.unwrap_err().to_string().contains("Invalid timezone"));
+    assert!(matches!(
+        result,
+        Err(ConfigError::InvalidTimezone(ref tz)) if tz == "Invalid/Timezone"
+    ));
 }
 
 #[test]
@@ -72,11 +207,98 @@ fn test_zero_timeout_rejection() {
     let result = Config::from_env();
 
     // Then: Should reject zero timeout with error
-    assert!(result.is_err());
-    assert!(result
-        .unwrap_err()
-        .to_string()
-        .contains("must be greater than 0"));
+    assert!(matches!(
+        result,
+        Err(ConfigError::ZeroTimeout("NETSPEED_TIMEOUT_SECONDS"))
+    ));
+}
+
+#[test]
+#[serial]
+fn test_adaptive_schedule_configuration() {
+    // Given: Adaptive mode with custom bounds
+    clear_env_vars();
+    env::set_var("NETSPEED_SCHEDULE_MODE", "adaptive");
+    env::set_var("NETSPEED_ADAPTIVE_MIN_SECONDS", "600");
+    env::set_var("NETSPEED_ADAPTIVE_MAX_SECONDS", "7200");
+    env::set_var("NETSPEED_ADAPTIVE_TOLERANCE", "0.2");
+
+    // When: Loading configuration
+    let config = Config::from_env().expect("Failed to load config");
+
+    // Then: Should use adaptive mode with the specified bounds
+    assert_eq!(config.schedule.mode, ScheduleMode::Adaptive);
+    assert_eq!(config.schedule.adaptive_min_seconds, 600);
+    assert_eq!(config.schedule.adaptive_max_seconds, 7200);
+    assert_eq!(config.schedule.adaptive_tolerance, 0.2);
+}
+
+#[test]
+#[serial]
+fn test_adaptive_min_above_max_rejected() {
+    // Given: An adaptive minimum larger than the maximum
+    clear_env_vars();
+    env::set_var("NETSPEED_ADAPTIVE_MIN_SECONDS", "7200");
+    env::set_var("NETSPEED_ADAPTIVE_MAX_SECONDS", "600");
+
+    // When: Loading configuration
+    let result = Config::from_env();
+
+    // Then: Should fail validation
+    assert!(matches!(
+        result,
+        Err(ConfigError::OutOfRange {
+            field: "NETSPEED_ADAPTIVE_MIN_SECONDS",
+            ..
+        })
+    ));
+}
+
+#[test]
+#[serial]
+fn test_ignored_schedule_vars_warn() {
+    // Given: Cron mode with an interval that cron mode never uses
+    clear_env_vars();
+    env::set_var("NETSPEED_SCHEDULE_MODE", "cron");
+    env::set_var("NETSPEED_SCHEDULE", "0 */30 * * * *");
+    env::set_var("NETSPEED_INTERVAL_SECONDS", "600");
+
+    // When: Checking for ignored variables and loading configuration
+    let ignored = ignored_schedule_vars(&ScheduleMode::Cron);
+    let result = Config::from_env();
+
+    // Then: The interval is reported as ignored, but loading still succeeds
+    assert_eq!(ignored, vec!["NETSPEED_INTERVAL_SECONDS"]);
+    assert!(result.is_ok());
+}
+
+#[test]
+#[serial]
+fn test_ignored_schedule_vars_strict() {
+    // Given: The same contradiction with strict configuration enabled
+    clear_env_vars();
+    env::set_var("NETSPEED_SCHEDULE_MODE", "cron");
+    env::set_var("NETSPEED_SCHEDULE", "0 */30 * * * *");
+    env::set_var("NETSPEED_INTERVAL_SECONDS", "600");
+    env::set_var("NETSPEED_STRICT_CONFIG", "true");
+
+    // When: Loading configuration
+    let result = Config::from_env();
+
+    // Then: Should fail naming the ignored variable
+    match result {
+        Err(ConfigError::IgnoredScheduleVars { vars, mode }) => {
+            assert_eq!(vars, vec!["NETSPEED_INTERVAL_SECONDS"]);
+            assert_eq!(mode, ScheduleMode::Cron);
+        }
+        other => panic!("Expected IgnoredScheduleVars, got {:?}", other),
+    }
+
+    // When: Only variables relevant to cron mode are set
+    env::remove_var("NETSPEED_INTERVAL_SECONDS");
+
+    // Then: Strict loading succeeds
+    assert!(Config::from_env().is_ok());
 }
 
 #[test]
@@ -90,11 +312,10 @@ fn test_invalid_schedule_mode() {
     let result = Config::from_env();
 
     // Then: Should fail with schedule mode error
-    assert!(result.is_err());
-    assert!(result
-        .unwrap_err()
-        .to_string()
-        .contains("Invalid schedule mode"));
+    assert!(matches!(
+        result,
+        Err(ConfigError::InvalidScheduleMode(ref mode)) if mode == "invalid_mode"
+    ));
 }
 
 #[test]
@@ -151,6 +372,11 @@ fn test_ntfy_configuration() {
     assert_eq!(ntfy.title, "Test Title");
     assert_eq!(ntfy.tags, "test,tags");
     assert_eq!(ntfy.priority, 5);
+    assert!(!ntfy.markdown);
+    assert_eq!(ntfy.speed_unit, SpeedUnit::Mbps);
+    assert!(!ntfy.quality_tags);
+    assert_eq!(ntfy.max_retries, 3);
+    assert_eq!(ntfy.max_chars, None);
 }
 
 #[test]
@@ -187,6 +413,7 @@ fn test_ntfy_priority_clamping() {
 fn test_notify_on_success_only() {
     // Given: Notify on is set to success only
     clear_env_vars();
+    env::set_var("NETSPEED_NTFY_URL", "https://ntfy.sh/test");
     env::set_var("NETSPEED_NOTIFY_ON", "success");
 
     // When: Loading configuration
@@ -197,11 +424,27 @@ fn test_notify_on_success_only() {
     assert!(!config.notify_on.failure);
 }
 
+#[test]
+#[serial]
+fn test_notify_on_without_channel() {
+    // Given: Notify on is set but no notification channel is configured
+    clear_env_vars();
+    env::set_var("NETSPEED_NOTIFY_ON", "success,failure");
+
+    // When: Loading configuration
+    let config = Config::from_env().expect("Failed to load config");
+
+    // Then: Loading succeeds (with a warning) and nothing is set to notify
+    assert!(!config.notify_on.success);
+    assert!(!config.notify_on.failure);
+}
+
 #[test]
 #[serial]
 fn test_notify_on_failure_only() {
     // Given: Notify on is set to failure only
     clear_env_vars();
+    env::set_var("NETSPEED_NTFY_URL", "https://ntfy.sh/test");
     env::set_var("NETSPEED_NOTIFY_ON", "failure");
 
     // When: Loading configuration
@@ -250,8 +493,52 @@ fn test_invalid_interval_seconds() {
     // When: Loading configuration
     let result = Config::from_env();
 
-    // Then: Should fail with parse error
-    assert!(result.is_err());
+    // Then: Should fail with parse error naming the variable
+    assert!(matches!(
+        result,
+        Err(ConfigError::ParseError {
+            field: "NETSPEED_INTERVAL_SECONDS",
+            ..
+        })
+    ));
+}
+
+#[test]
+#[serial]
+fn test_zero_interval_seconds_rejected() {
+    // Given: An interval of 0 seconds
+    clear_env_vars();
+    env::set_var("NETSPEED_SCHEDULE_MODE", "interval");
+    env::set_var("NETSPEED_INTERVAL_SECONDS", "0");
+
+    // When: Loading configuration
+    let result = Config::from_env();
+
+    // Then: Should fail instead of running a speed test every second
+    assert!(matches!(
+        result,
+        Err(ConfigError::OutOfRange {
+            field: "NETSPEED_INTERVAL_SECONDS",
+            ..
+        })
+    ));
+}
+
+#[test]
+#[serial]
+fn test_zero_interval_seconds_ignored_in_cron_mode() {
+    // Given: Cron mode with a leftover interval of 0 seconds
+    clear_env_vars();
+    env::set_var("NETSPEED_SCHEDULE_MODE", "cron");
+    env::set_var("NETSPEED_SCHEDULE", "0 */30 * * * *");
+    env::set_var("NETSPEED_INTERVAL_SECONDS", "0");
+
+    // When: Loading configuration
+    let config = Config::from_env().expect("Failed to load config");
+
+    // Then: The unused interval doesn't stop startup, as with PUT /admin/schedule
+    assert_eq!(config.schedule.mode, ScheduleMode::Cron);
+    assert_eq!(config.schedule.interval_seconds, 0);
 }
 
 #[test]
@@ -268,6 +555,39 @@ fn test_invalid_timeout_seconds() {
     assert!(result.is_err());
 }
 
+#[test]
+#[serial]
+fn test_first_output_timeout_configuration() {
+    // Given: A first-output deadline shorter than the total timeout
+    clear_env_vars();
+    env::set_var("NETSPEED_FIRST_OUTPUT_TIMEOUT_SECONDS", "20");
+
+    // When: Loading configuration
+    let config = Config::from_env().expect("Failed to load config");
+
+    // Then: Should use the specified deadline
+    assert_eq!(config.speedtest.first_output_timeout_seconds, Some(20));
+}
+
+#[test]
+#[serial]
+fn test_zero_first_output_timeout_rejected() {
+    // Given: A first-output deadline of zero
+    clear_env_vars();
+    env::set_var("NETSPEED_FIRST_OUTPUT_TIMEOUT_SECONDS", "0");
+
+    // When: Loading configuration
+    let result = Config::from_env();
+
+    // Then: Should fail validation
+    assert!(matches!(
+        result,
+        Err(ConfigError::ZeroTimeout(
+            "NETSPEED_FIRST_OUTPUT_TIMEOUT_SECONDS"
+        ))
+    ));
+}
+
 #[test]
 #[serial]
 fn test_resource_interval_configuration() {
@@ -282,6 +602,20 @@ fn test_resource_interval_configuration() {
     assert_eq!(config.resource_interval_seconds, 30);
 }
 
+#[test]
+#[serial]
+fn test_cpu_smoothing_alpha_out_of_range() {
+    // Given: A smoothing factor of 0, which would freeze the reading
+    clear_env_vars();
+    env::set_var("NETSPEED_CPU_SMOOTHING_ALPHA", "0");
+
+    // When: Loading configuration
+    let result = Config::from_env();
+
+    // Then: Should fail validation
+    assert!(matches!(result, Err(ConfigError::OutOfRange { .. })));
+}
+
 #[test]
 #[serial]
 fn test_invalid_resource_interval() {
@@ -295,3 +629,267 @@ fn test_invalid_resource_interval() {
     // Then: Should fail with parse error
     assert!(result.is_err());
 }
+
+#[test]
+#[serial]
+fn test_init_grace_configuration() {
+    // Given: A startup grace period of 60 seconds
+    clear_env_vars();
+    env::set_var("NETSPEED_INIT_GRACE_SECONDS", "60");
+
+    // When: Loading configuration
+    let config = Config::from_env().expect("Failed to load config");
+
+    // Then: Should use the specified grace period
+    assert_eq!(config.server.init_grace_seconds, 60);
+}
+
+#[test]
+#[serial]
+fn test_max_rss_bytes_configuration() {
+    // Given: A memory limit for the health check
+    clear_env_vars();
+    env::set_var("NETSPEED_MAX_RSS_BYTES", "104857600");
+
+    // When: Loading configuration
+    let config = Config::from_env().expect("Failed to load config");
+
+    // Then: Should use the specified limit
+    assert_eq!(config.server.max_rss_bytes, Some(104_857_600));
+}
+
+#[test]
+#[serial]
+fn test_ookla_progress_disabled() {
+    // Given: Ookla progress output is disabled
+    clear_env_vars();
+    env::set_var("NETSPEED_OOKLA_PROGRESS", "false");
+
+    // When: Loading configuration
+    let config = Config::from_env().expect("Failed to load config");
+
+    // Then: Should append --progress=no and keep JSON output
+    assert!(config.speedtest.args.contains(&"--progress=no".to_string()));
+    assert!(config.speedtest.args.contains(&"--format=json".to_string()));
+}
+
+#[test]
+#[serial]
+fn test_selection_timeout_enables_progress_stream() {
+    // Given: A server selection deadline
+    clear_env_vars();
+    env::set_var("NETSPEED_SELECTION_TIMEOUT_SECONDS", "20");
+
+    // When: Loading configuration
+    let config = Config::from_env().expect("Failed to load config");
+
+    // Then: The CLI is asked for its progress stream, and the result is read
+    // from the last line after it
+    assert_eq!(config.speedtest.selection_timeout_seconds, Some(20));
+    assert!(config
+        .speedtest
+        .args
+        .contains(&"--progress=yes".to_string()));
+    assert!(config.speedtest.parse_last_line);
+
+    // When: The progress stream is disabled
+    env::set_var("NETSPEED_OOKLA_PROGRESS", "false");
+
+    // Then: Server selection can't be observed, so loading fails
+    assert!(matches!(
+        Config::from_env(),
+        Err(ConfigError::Requires {
+            field: "NETSPEED_SELECTION_TIMEOUT_SECONDS",
+            requirement: "NETSPEED_OOKLA_PROGRESS=true",
+        })
+    ));
+
+    // When: librespeed-cli is selected instead
+    env::remove_var("NETSPEED_OOKLA_PROGRESS");
+    env::set_var("NETSPEED_OUTPUT_FORMAT", "librespeed");
+
+    // Then: Loading fails, as librespeed-cli reports no progress
+    assert!(matches!(
+        Config::from_env(),
+        Err(ConfigError::Requires {
+            field: "NETSPEED_SELECTION_TIMEOUT_SECONDS",
+            ..
+        })
+    ));
+}
+
+#[test]
+#[serial]
+fn test_server_id_pinning() {
+    // Given: A pinned Ookla server
+    clear_env_vars();
+    env::set_var("NETSPEED_SERVER_ID", "12345");
+
+    // When: Loading configuration
+    let config = Config::from_env().expect("Failed to load config");
+
+    // Then: Should append --server-id to the default arguments
+    assert_eq!(
+        config.speedtest.args,
+        vec![
+            "--format=json",
+            "--accept-license",
+            "--accept-gdpr",
+            "--server-id=12345"
+        ]
+    );
+
+    // When: The server ID is not a number
+    env::set_var("NETSPEED_SERVER_ID", "closest");
+
+    // Then: Loading fails naming the variable
+    assert!(matches!(
+        Config::from_env(),
+        Err(ConfigError::ParseError {
+            field: "NETSPEED_SERVER_ID",
+            ..
+        })
+    ));
+}
+
+#[test]
+#[serial]
+fn test_single_connection() {
+    // Given: Single-connection testing is enabled
+    clear_env_vars();
+    env::set_var("NETSPEED_SINGLE_CONNECTION", "true");
+
+    // When: Loading configuration
+    let config = Config::from_env().expect("Failed to load config");
+
+    // Then: Should append --single and keep JSON output
+    assert!(config.single_connection);
+    assert!(config.speedtest.args.contains(&"--single".to_string()));
+    assert!(config.speedtest.args.contains(&"--format=json".to_string()));
+}
+
+#[test]
+#[serial]
+fn test_ookla_progress_default() {
+    // Given: No progress setting
+    clear_env_vars();
+
+    // When: Loading configuration
+    let config = Config::from_env().expect("Failed to load config");
+
+    // Then: Should leave the default arguments untouched
+    assert_eq!(
+        config.speedtest.args,
+        vec!["--format=json", "--accept-license", "--accept-gdpr"]
+    );
+}
+
+#[test]
+#[serial]
+fn test_librespeed_output_format() {
+    // Given: librespeed-cli selected, along with an Ookla-only option
+    clear_env_vars();
+    env::set_var("NETSPEED_OUTPUT_FORMAT", "librespeed");
+    env::set_var("NETSPEED_OOKLA_PROGRESS", "false");
+
+    // When: Loading configuration
+    let config = Config::from_env().expect("Failed to load config");
+
+    // Then: librespeed-cli runs with JSON output and no Ookla flags
+    assert_eq!(config.speedtest.output_format, OutputFormat::Librespeed);
+    assert_eq!(config.speedtest.command, "librespeed-cli");
+    assert_eq!(config.speedtest.args, vec!["--json"]);
+}
+
+#[test]
+#[serial]
+fn test_invalid_output_format() {
+    // Given: An unknown output format
+    clear_env_vars();
+    env::set_var("NETSPEED_OUTPUT_FORMAT", "fast");
+
+    // When/Then: Loading configuration fails
+    assert!(matches!(
+        Config::from_env(),
+        Err(ConfigError::ParseError {
+            field: "NETSPEED_OUTPUT_FORMAT",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_format_json_injected_when_missing() {
+    // Given: Arguments without --format=json and with a different format
+    let args = vec!["--format=csv".to_string(), "--accept-license".to_string()];
+
+    // When: Normalizing the arguments
+    let normalized = normalize_ookla_args(args);
+
+    // Then: Should inject --format=json and drop the conflicting format
+    assert_eq!(normalized, vec!["--format=json", "--accept-license"]);
+}
+
+#[test]
+#[serial]
+fn test_config_file_round_trip() {
+    // Given: A TOML config file covering top-level keys, a table and a list
+    clear_env_vars();
+    let path = env::temp_dir().join(format!("netspeed-config-{}.toml", std::process::id()));
+    std::fs::write(
+        &path,
+        r#"
+bind = "127.0.0.1:9200"
+schedule_mode = "cron"
+schedule = "0 */30 * * * *"
+timezone = "Asia/Tokyo"
+timeout_seconds = 90
+allow_partial = true
+notify_on = ["failure"]
+
+[ntfy]
+url = "https://ntfy.sh/from-file"
+priority = 4
+"#,
+    )
+    .expect("Failed to write config file");
+
+    // When: Loading it through NETSPEED_CONFIG_FILE
+    env::set_var("NETSPEED_CONFIG_FILE", &path);
+    let config = Config::from_env().expect("Failed to load config file");
+
+    // Then: Every value lands in the same fields as its environment variable
+    assert_eq!(config.server.bind_address, "127.0.0.1:9200");
+    assert_eq!(config.schedule.mode, ScheduleMode::Cron);
+    assert_eq!(
+        config.schedule.cron_expression.as_deref(),
+        Some("0 */30 * * * *")
+    );
+    assert_eq!(config.schedule.timezone, "Asia/Tokyo");
+    assert_eq!(config.speedtest.timeout_seconds, 90);
+    assert!(config.speedtest.allow_partial);
+    assert!(!config.notify_on.success);
+    assert!(config.notify_on.failure);
+    let ntfy = config.ntfy.expect("Ntfy config should come from the file");
+    assert_eq!(ntfy.url, "https://ntfy.sh/from-file");
+    assert_eq!(ntfy.priority, 4);
+
+    // When: An environment variable sets the same value
+    env::set_var("NETSPEED_TIMEOUT_SECONDS", "30");
+    let config = Config::from_file(&path).expect("Failed to load config file");
+
+    // Then: The environment wins
+    assert_eq!(config.speedtest.timeout_seconds, 30);
+
+    // When: The file holds an invalid value
+    clear_env_vars();
+    std::fs::write(&path, "timeout_seconds = 0").expect("Failed to write config file");
+
+    // Then: It is rejected by the same validation as the environment
+    assert!(matches!(
+        Config::from_file(&path),
+        Err(ConfigError::ZeroTimeout("NETSPEED_TIMEOUT_SECONDS"))
+    ));
+
+    std::fs::remove_file(&path).expect("Failed to remove config file");
+}
@@ -0,0 +1,101 @@
+use netspeed_lite::history::RunRecord;
+use netspeed_lite::runner::{Direction, RunOutcome, SpeedtestResult};
+use std::time::Duration;
+
+mod common;
+
+#[test]
+fn test_record_from_partial_success() {
+    // Given: A successful run that only measured the download
+    let outcome = RunOutcome::Success(SpeedtestResult {
+        download_bps: 812_300_000.0,
+        upload_bps: 0.0,
+        latency_seconds: 0.0184,
+        jitter_seconds: Some(0.0021),
+        packet_loss_ratio: None,
+        missing: Some(Direction::Upload),
+        external_ip: None,
+        download_latency_seconds: None,
+        upload_latency_seconds: None,
+        interface_name: None,
+        server_id: None,
+        server_name: None,
+        server_location: None,
+        measured_at: None,
+    });
+
+    // When: Building its history record
+    let record = RunRecord::from_outcome(&outcome, Duration::from_secs(30));
+
+    // Then: The missing upload is stored as NULL rather than 0
+    assert_eq!(record.outcome, "success");
+    assert_eq!(record.download_bps, Some(812_300_000.0));
+    assert_eq!(record.upload_bps, None);
+    assert_eq!(record.latency_seconds, Some(0.0184));
+    assert_eq!(record.duration_seconds, 30.0);
+    assert_eq!(record.error_category, None);
+}
+
+#[cfg(feature = "history")]
+#[test]
+fn test_history_insert_and_query() {
+    use netspeed_lite::history::RunHistory;
+    use netspeed_lite::runner::ErrorCategory;
+    use std::path::Path;
+
+    // Given: An in-memory history holding three failed runs
+    let history = RunHistory::open(Path::new(":memory:")).expect("Failed to open history");
+    let errors = [
+        ErrorCategory::CommandFailed {
+            code: 1,
+            stderr: String::new(),
+        },
+        ErrorCategory::Timeout(120),
+        ErrorCategory::InvalidOutput("eof".to_string()),
+    ];
+    for (timestamp, error) in [100, 200, 300].into_iter().zip(errors) {
+        let record = RunRecord {
+            timestamp,
+            ..RunRecord::from_outcome(&RunOutcome::Failure(error), Duration::from_secs(5))
+        };
+        history.record(&record).expect("Failed to record run");
+    }
+
+    // When: Querying the last two runs
+    let records = history.recent(2).expect("Failed to query history");
+
+    // Then: The two newest runs come back, oldest first, with all their fields
+    let timestamps: Vec<i64> = records.iter().map(|record| record.timestamp).collect();
+    assert_eq!(timestamps, vec![200, 300]);
+    assert_eq!(records[0].error_category.as_deref(), Some("execute"));
+    assert_eq!(records[1].error_category.as_deref(), Some("parse"));
+    assert_eq!(records[1].outcome, "failure");
+    assert_eq!(records[1].duration_seconds, 5.0);
+}
+
+#[cfg(feature = "history")]
+#[tokio::test]
+async fn test_scheduler_records_runs_in_history() {
+    use netspeed_lite::config::ScheduleMode;
+    use netspeed_lite::metrics::Metrics;
+    use netspeed_lite::scheduler::Scheduler;
+
+    // Given: A scheduler with an in-memory history and the sample speedtest output
+    let mut config = common::create_test_config(ScheduleMode::Interval);
+    config.speedtest.command = "cat".to_string();
+    config.speedtest.args = vec!["tests/fixtures/speedtest_sample.json".to_string()];
+    config.db_path = Some(":memory:".into());
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let scheduler = Scheduler::new(config, metrics, None);
+
+    // When: A successful run completes
+    scheduler.run_once().await;
+
+    // Then: The run is stored with its measurements
+    let history = scheduler.history().expect("History should be enabled");
+    let records = history.recent(10).expect("Failed to query history");
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].outcome, "success");
+    assert_eq!(records[0].download_bps, Some(812_300_000.0));
+    assert_eq!(records[0].upload_bps, Some(42_100_000.0));
+}
@@ -1,15 +1,34 @@
+use netspeed_lite::config::{ScheduleMode, ServerConfig};
 use netspeed_lite::metrics::Metrics;
+use netspeed_lite::scheduler::{upcoming_cron_runs, Scheduler};
 use netspeed_lite::server;
+use prometheus::{Encoder, IntCounter, Registry, TextEncoder};
 use std::env;
+use std::sync::Arc;
 use tokio::time::{sleep, Duration};
 
+mod common;
+
+fn server_config(bind_address: &str) -> ServerConfig {
+    ServerConfig {
+        bind_address: bind_address.to_string(),
+        init_grace_seconds: 0,
+        bind_retries: 0,
+        max_rss_bytes: None,
+        json_pretty: false,
+        metrics_token: None,
+        admin_token: None,
+        stale_after_seconds: 7200,
+    }
+}
+
 #[tokio::test]
 async fn test_server_starts_and_responds() {
     // Given: A running HTTP server with metrics
     env::set_var("PROMETHEUS_REGISTRY_PREFIX", "test_integration_server");
     let metrics = Metrics::new().expect("Failed to create metrics");
-    let bind_address = "127.0.0.1:19109".to_string();
-    let server_handle = tokio::spawn(async move { server::serve(bind_address, metrics).await });
+    let config = server_config("127.0.0.1:19109");
+    let server_handle = tokio::spawn(async move { server::serve(config, metrics).await });
     sleep(Duration::from_millis(100)).await;
 
     // When: Making requests to root endpoint
@@ -74,14 +93,77 @@ async fn test_metrics_format() {
     env::remove_var("PROMETHEUS_REGISTRY_PREFIX");
 }
 
+#[test]
+fn test_metrics_in_caller_registry() {
+    // Given: A caller-owned registry that already holds an application metric
+    let registry = Arc::new(Registry::new());
+    let app_requests = IntCounter::new("app_requests_total", "Application requests").unwrap();
+    registry.register(Box::new(app_requests.clone())).unwrap();
+    app_requests.inc();
+
+    // When: Registering netspeed metrics into it and rendering the registry
+    let metrics = Metrics::from_registry(registry.clone()).expect("Failed to register metrics");
+    metrics.download_bps.set(100_000_000.0);
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&registry.gather(), &mut buffer)
+        .unwrap();
+    let rendered = String::from_utf8(buffer).unwrap();
+
+    // Then: Both the application's and netspeed's metrics are exposed together
+    assert!(rendered.contains("app_requests_total 1"));
+    assert!(rendered.contains("netspeed_download_bps 100000000"));
+    assert!(Arc::ptr_eq(&metrics.registry(), &registry));
+
+    // Then: Registering a second time into the same registry is rejected
+    assert!(Metrics::from_registry(registry).is_err());
+}
+
+#[test]
+fn test_metric_registration_error_names_metric() {
+    // Given: A caller registry that already holds a metric netspeed also registers
+    let registry = Arc::new(Registry::new());
+    let clashing = IntCounter::new("netspeed_runs_total", "Clashing counter").unwrap();
+    registry.register(Box::new(clashing)).unwrap();
+
+    // When: Registering netspeed metrics into it
+    let error = Metrics::from_registry(registry)
+        .err()
+        .expect("Registration should fail");
+
+    // Then: The error names the metric that failed to register
+    assert!(error.to_string().contains("netspeed_runs_total"));
+}
+
+#[test]
+fn test_metrics_source_label() {
+    // Given: Metrics created with a source label and a labeled run recorded
+    let metrics =
+        Metrics::with_source_label(false, Some("collector-a")).expect("Failed to create metrics");
+    metrics.runs_total.with_label_values(&["success"]).inc();
+
+    // When: Rendering the metrics
+    let rendered = metrics.render().expect("Failed to render metrics");
+
+    // Then: Every sample carries the source label next to its own labels
+    assert!(rendered.contains(r#"netspeed_runs_total{outcome="success",source="collector-a"} 1"#));
+    assert!(rendered
+        .lines()
+        .filter(|line| line.starts_with("netspeed_"))
+        .all(|line| line.contains(r#"source="collector-a""#)));
+
+    // Then: The instance label is left for Prometheus to attach at scrape time
+    assert!(!rendered.contains("instance="));
+}
+
 #[tokio::test]
 async fn test_health_check_states() {
     // Given: A running server with modifiable metrics
     env::set_var("PROMETHEUS_REGISTRY_PREFIX", "test_health_states");
     let metrics = Metrics::new().expect("Failed to create metrics");
-    let bind_address = "127.0.0.1:19110".to_string();
+    let config = server_config("127.0.0.1:19110");
     let test_metrics = metrics.clone();
-    let server_handle = tokio::spawn(async move { server::serve(bind_address, metrics).await });
+    let server_handle = tokio::spawn(async move { server::serve(config, metrics).await });
     sleep(Duration::from_millis(100)).await;
 
     // When: Checking health before any runs
@@ -130,8 +212,8 @@ async fn test_metrics_content_type() {
     // Given: A running server
     env::set_var("PROMETHEUS_REGISTRY_PREFIX", "test_content_type");
     let metrics = Metrics::new().expect("Failed to create metrics");
-    let bind_address = "127.0.0.1:19111".to_string();
-    let server_handle = tokio::spawn(async move { server::serve(bind_address, metrics).await });
+    let config = server_config("127.0.0.1:19111");
+    let server_handle = tokio::spawn(async move { server::serve(config, metrics).await });
     sleep(Duration::from_millis(100)).await;
 
     // When: Requesting metrics endpoint
@@ -157,8 +239,8 @@ async fn test_concurrent_requests() {
     // Given: A running server
     env::set_var("PROMETHEUS_REGISTRY_PREFIX", "test_concurrent");
     let metrics = Metrics::new().expect("Failed to create metrics");
-    let bind_address = "127.0.0.1:19112".to_string();
-    let server_handle = tokio::spawn(async move { server::serve(bind_address, metrics).await });
+    let config = server_config("127.0.0.1:19112");
+    let server_handle = tokio::spawn(async move { server::serve(config, metrics).await });
     sleep(Duration::from_millis(100)).await;
 
     // When: Making 10 concurrent requests to metrics endpoint
@@ -182,3 +264,870 @@ async fn test_concurrent_requests() {
     server_handle.abort();
     env::remove_var("PROMETHEUS_REGISTRY_PREFIX");
 }
+
+#[tokio::test]
+async fn test_health_init_grace_period() {
+    // Given: A running server with a 1 second startup grace period
+    env::set_var("PROMETHEUS_REGISTRY_PREFIX", "test_init_grace");
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let mut config = server_config("127.0.0.1:19113");
+    config.init_grace_seconds = 1;
+    let server_handle = tokio::spawn(async move { server::serve(config, metrics).await });
+    sleep(Duration::from_millis(100)).await;
+
+    // When: Checking health during the grace period
+    let response = reqwest::get("http://127.0.0.1:19113/healthz")
+        .await
+        .expect("Failed to request health");
+
+    // Then: Should report initializing with 200
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await.expect("Failed to parse JSON");
+    assert_eq!(body["status"], "initializing");
+
+    // When: Checking health after the grace period has elapsed
+    sleep(Duration::from_millis(1000)).await;
+    let response = reqwest::get("http://127.0.0.1:19113/healthz")
+        .await
+        .expect("Failed to request health");
+
+    // Then: Should fall back to the normal 503
+    assert_eq!(response.status(), 503);
+    let body: serde_json::Value = response.json().await.expect("Failed to parse JSON");
+    assert_eq!(body["status"], "initializing");
+
+    // Cleanup
+    server_handle.abort();
+    env::remove_var("PROMETHEUS_REGISTRY_PREFIX");
+}
+
+#[tokio::test]
+async fn test_server_bind_retry() {
+    // Given: The port is already taken by another listener
+    env::set_var("PROMETHEUS_REGISTRY_PREFIX", "test_bind_retry");
+    let blocker = std::net::TcpListener::bind("127.0.0.1:19114").expect("Failed to bind blocker");
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let mut config = server_config("127.0.0.1:19114");
+    config.bind_retries = 5;
+    let server_handle = tokio::spawn(async move { server::serve(config, metrics).await });
+
+    // When: The port is released while the server is retrying
+    sleep(Duration::from_millis(200)).await;
+    assert!(!server_handle.is_finished());
+    drop(blocker);
+    sleep(Duration::from_millis(800)).await;
+
+    // Then: The server should have bound on retry and respond
+    let response = reqwest::get("http://127.0.0.1:19114/metrics")
+        .await
+        .expect("Failed to request metrics");
+    assert_eq!(response.status(), 200);
+
+    // Cleanup
+    server_handle.abort();
+    env::remove_var("PROMETHEUS_REGISTRY_PREFIX");
+}
+
+#[tokio::test]
+async fn test_server_bind_gives_up() {
+    // Given: The port stays taken and no retries are allowed
+    env::set_var("PROMETHEUS_REGISTRY_PREFIX", "test_bind_gives_up");
+    let _blocker = std::net::TcpListener::bind("127.0.0.1:19115").expect("Failed to bind blocker");
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let config = server_config("127.0.0.1:19115");
+
+    // When: Starting the server
+    let result = server::serve(config, metrics).await;
+
+    // Then: Should fail with the bind error
+    assert!(result.is_err());
+    env::remove_var("PROMETHEUS_REGISTRY_PREFIX");
+}
+
+#[tokio::test]
+async fn test_health_degraded_on_memory_limit() {
+    // Given: A running server with a 100 MiB memory limit and a healthy run
+    env::set_var("PROMETHEUS_REGISTRY_PREFIX", "test_rss_limit");
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let mut config = server_config("127.0.0.1:19116");
+    config.max_rss_bytes = Some(100 * 1024 * 1024);
+    let test_metrics = metrics.clone();
+    let server_handle = tokio::spawn(async move { server::serve(config, metrics).await });
+    sleep(Duration::from_millis(100)).await;
+    test_metrics.last_success.set(1.0);
+    test_metrics.run_timestamp_seconds.set(1234567890.0);
+
+    // When: Process memory exceeds the limit
+    test_metrics
+        .process_memory_bytes
+        .set(200.0 * 1024.0 * 1024.0);
+    let response = reqwest::get("http://127.0.0.1:19116/healthz")
+        .await
+        .expect("Failed to request health");
+
+    // Then: Should report degraded with 503 and a reason
+    assert_eq!(response.status(), 503);
+    let body: serde_json::Value = response.json().await.expect("Failed to parse JSON");
+    assert_eq!(body["status"], "degraded");
+    assert!(body["reason"].as_str().unwrap().contains("exceeds limit"));
+
+    // When: Memory drops back below the limit
+    test_metrics
+        .process_memory_bytes
+        .set(50.0 * 1024.0 * 1024.0);
+    let response = reqwest::get("http://127.0.0.1:19116/healthz")
+        .await
+        .expect("Failed to request health");
+
+    // Then: Should be healthy again without a reason
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await.expect("Failed to parse JSON");
+    assert_eq!(body["status"], "healthy");
+    assert!(body.get("reason").is_none());
+
+    // Cleanup
+    server_handle.abort();
+    env::remove_var("PROMETHEUS_REGISTRY_PREFIX");
+}
+
+#[tokio::test]
+async fn test_health_degraded_on_jitter_exceeded() {
+    // Given: A running server after a successful run
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let config = server_config("127.0.0.1:19132");
+    let test_metrics = metrics.clone();
+    let server_handle = tokio::spawn(async move { server::serve(config, metrics).await });
+    sleep(Duration::from_millis(100)).await;
+    test_metrics.last_success.set(1.0);
+    test_metrics
+        .run_timestamp_seconds
+        .set(chrono::Utc::now().timestamp() as f64);
+
+    // When: The run's jitter exceeded NETSPEED_MAX_JITTER_MS
+    test_metrics.jitter_exceeded.set(1.0);
+    let response = reqwest::get("http://127.0.0.1:19132/healthz")
+        .await
+        .expect("Failed to request health");
+
+    // Then: Should report degraded with 503 and a reason
+    assert_eq!(response.status(), 503);
+    let body: serde_json::Value = response.json().await.expect("Failed to parse JSON");
+    assert_eq!(body["status"], "degraded");
+    assert!(body["reason"].as_str().unwrap().contains("jitter"));
+
+    // When: A later run's jitter is back under the limit
+    test_metrics.jitter_exceeded.set(0.0);
+    let response = reqwest::get("http://127.0.0.1:19132/healthz")
+        .await
+        .expect("Failed to request health");
+
+    // Then: Should be healthy again
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await.expect("Failed to parse JSON");
+    assert_eq!(body["status"], "healthy");
+
+    // Cleanup
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_cron_validate_endpoint() {
+    // Given: A running server
+    env::set_var("PROMETHEUS_REGISTRY_PREFIX", "test_cron_validate");
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let config = server_config("127.0.0.1:19117");
+    let server_handle = tokio::spawn(async move { server::serve(config, metrics).await });
+    sleep(Duration::from_millis(100)).await;
+
+    // When: Validating a valid expression
+    let response = reqwest::get(
+        "http://127.0.0.1:19117/cron/validate?expr=0%20*/15%20*%20*%20*%20*&tz=Europe/Brussels&count=5",
+    )
+    .await
+    .expect("Failed to request cron validation");
+
+    // Then: Should return 5 increasing future run times
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await.expect("Failed to parse JSON");
+    assert_eq!(body["timezone"], "Europe/Brussels");
+    let upcoming: Vec<chrono::DateTime<chrono::FixedOffset>> = body["upcoming"]
+        .as_array()
+        .expect("upcoming should be an array")
+        .iter()
+        .map(|v| chrono::DateTime::parse_from_rfc3339(v.as_str().unwrap()).unwrap())
+        .collect();
+    assert_eq!(upcoming.len(), 5);
+    assert!(upcoming[0] > chrono::Utc::now());
+    assert!(upcoming.windows(2).all(|w| w[0] < w[1]));
+
+    // When: Validating an invalid expression
+    let response = reqwest::get("http://127.0.0.1:19117/cron/validate?expr=not%20a%20cron")
+        .await
+        .expect("Failed to request cron validation");
+
+    // Then: Should return 400 with an error message
+    assert_eq!(response.status(), 400);
+    let body: serde_json::Value = response.json().await.expect("Failed to parse JSON");
+    assert!(body["error"]
+        .as_str()
+        .unwrap()
+        .contains("Invalid cron expression"));
+
+    // Cleanup
+    server_handle.abort();
+    env::remove_var("PROMETHEUS_REGISTRY_PREFIX");
+}
+
+#[tokio::test]
+async fn test_json_pretty_printing() {
+    // Given: A running server with compact JSON by default
+    env::set_var("PROMETHEUS_REGISTRY_PREFIX", "test_json_pretty");
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let config = server_config("127.0.0.1:19118");
+    let server_handle = tokio::spawn(async move { server::serve(config, metrics).await });
+    sleep(Duration::from_millis(100)).await;
+
+    // When: Requesting health without the pretty flag
+    let body = reqwest::get("http://127.0.0.1:19118/healthz")
+        .await
+        .expect("Failed to request health")
+        .text()
+        .await
+        .expect("Failed to read body");
+
+    // Then: Should be compact JSON on a single line
+    assert!(!body.contains('\n'));
+
+    // When: Requesting health with ?pretty=true
+    let response = reqwest::get("http://127.0.0.1:19118/healthz?pretty=true")
+        .await
+        .expect("Failed to request health");
+    assert_eq!(
+        response.headers().get("content-type").unwrap(),
+        "application/json"
+    );
+    let body = response.text().await.expect("Failed to read body");
+
+    // Then: Should be pretty-printed, still valid JSON
+    assert!(body.contains('\n'));
+    let parsed: serde_json::Value = serde_json::from_str(&body).expect("Failed to parse JSON");
+    assert_eq!(parsed["status"], "initializing");
+
+    // Cleanup
+    server_handle.abort();
+    env::remove_var("PROMETHEUS_REGISTRY_PREFIX");
+}
+
+#[tokio::test]
+async fn test_metrics_token_required() {
+    // Given: A running server that requires a scrape token
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let mut config = server_config("127.0.0.1:19119");
+    config.metrics_token = Some("s3cret".to_string());
+    let server_handle = tokio::spawn(async move { server::serve(config, metrics).await });
+    sleep(Duration::from_millis(100)).await;
+
+    // When/Then: The correct token is accepted
+    let response = reqwest::get("http://127.0.0.1:19119/metrics?token=s3cret")
+        .await
+        .expect("Failed to request metrics");
+    assert_eq!(response.status(), 200);
+    let body = response.text().await.expect("Failed to read body");
+    assert!(body.contains("netspeed_last_success"));
+
+    // When/Then: A wrong token is rejected
+    let response = reqwest::get("http://127.0.0.1:19119/metrics?token=wrong")
+        .await
+        .expect("Failed to request metrics");
+    assert_eq!(response.status(), 401);
+
+    // When/Then: A missing token is rejected
+    let response = reqwest::get("http://127.0.0.1:19119/metrics")
+        .await
+        .expect("Failed to request metrics");
+    assert_eq!(response.status(), 401);
+
+    // Then: Other endpoints stay open
+    let response = reqwest::get("http://127.0.0.1:19119/healthz")
+        .await
+        .expect("Failed to request health");
+    assert_ne!(response.status(), 401);
+
+    // Cleanup
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_metrics_bearer_token() {
+    // Given: A running server that requires a scrape token
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let mut config = server_config("127.0.0.1:19129");
+    config.metrics_token = Some("s3cret".to_string());
+    let server_handle = tokio::spawn(async move { server::serve(config, metrics).await });
+    sleep(Duration::from_millis(100)).await;
+    let client = reqwest::Client::new();
+
+    // When/Then: The correct bearer token is accepted
+    let response = client
+        .get("http://127.0.0.1:19129/metrics")
+        .bearer_auth("s3cret")
+        .send()
+        .await
+        .expect("Failed to request metrics");
+    assert_eq!(response.status(), 200);
+    let body = response.text().await.expect("Failed to read body");
+    assert!(body.contains("netspeed_last_success"));
+
+    // When/Then: A wrong bearer token is rejected
+    let response = client
+        .get("http://127.0.0.1:19129/metrics")
+        .bearer_auth("wrong")
+        .send()
+        .await
+        .expect("Failed to request metrics");
+    assert_eq!(response.status(), 401);
+
+    // Then: The landing page stays open
+    let response = client
+        .get("http://127.0.0.1:19129/")
+        .send()
+        .await
+        .expect("Failed to request landing page");
+    assert_eq!(response.status(), 200);
+
+    // Cleanup
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_admin_schedule_update() {
+    // Given: A server with the admin API enabled and an interval scheduler
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let scheduler = Arc::new(Scheduler::new(
+        common::create_test_config(ScheduleMode::Interval),
+        metrics.clone(),
+        None,
+    ));
+    let mut config = server_config("127.0.0.1:19120");
+    config.admin_token = Some("admin-secret".to_string());
+    let server_scheduler = scheduler.clone();
+    let server_handle = tokio::spawn(async move {
+        server::serve_with_scheduler(config, metrics, server_scheduler).await
+    });
+    sleep(Duration::from_millis(100)).await;
+    let client = reqwest::Client::new();
+    let body = r#"{"mode": "cron", "cron_expression": "0 0 0 1 1 *", "timezone": "UTC"}"#;
+
+    // When: Updating without the admin token
+    let response = client
+        .put("http://127.0.0.1:19120/admin/schedule")
+        .header("Content-Type", "application/json")
+        .body(body)
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    // Then: Should be rejected and leave the schedule unchanged
+    assert_eq!(response.status(), 401);
+    assert_eq!(scheduler.schedule().mode, ScheduleMode::Interval);
+
+    // When: Switching to a yearly cron schedule with the token
+    let response = client
+        .put("http://127.0.0.1:19120/admin/schedule")
+        .header("Authorization", "Bearer admin-secret")
+        .header("Content-Type", "application/json")
+        .body(body)
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    // Then: The next computed run follows the cron expression
+    assert_eq!(response.status(), 200);
+    let json: serde_json::Value = response.json().await.expect("Failed to parse JSON");
+    assert_eq!(json["mode"], "cron");
+    let expected = upcoming_cron_runs("0 0 0 1 1 *", "UTC", 1).unwrap()[0];
+    assert_eq!(scheduler.next_run_time(), expected);
+    assert_eq!(json["next_run"], expected.to_rfc3339());
+
+    // When: Sending an invalid cron expression
+    let response = client
+        .put("http://127.0.0.1:19120/admin/schedule")
+        .header("Authorization", "Bearer admin-secret")
+        .header("Content-Type", "application/json")
+        .body(r#"{"mode": "cron", "cron_expression": "not a cron"}"#)
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    // Then: Should be rejected and keep the previous schedule
+    assert_eq!(response.status(), 400);
+    assert_eq!(
+        scheduler.schedule().cron_expression.as_deref(),
+        Some("0 0 0 1 1 *")
+    );
+
+    // Cleanup
+    server_handle.abort();
+}
+
+#[cfg(feature = "notify")]
+#[tokio::test]
+async fn test_admin_test_notification() {
+    use netspeed_lite::notifier::{Notifier, TEST_NOTIFICATION_MESSAGE};
+
+    // Given: A server with the admin API enabled and a mock ntfy channel
+    let (url, received) = common::spawn_mock_ntfy(axum::http::StatusCode::OK).await;
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let notifier = Notifier::new(common::ntfy_config(&url), metrics.clone());
+    let scheduler = Arc::new(Scheduler::new(
+        common::create_test_config(ScheduleMode::Interval),
+        metrics.clone(),
+        Some(notifier),
+    ));
+    let mut config = server_config("127.0.0.1:19121");
+    config.admin_token = Some("admin-secret".to_string());
+    let server_handle =
+        tokio::spawn(async move { server::serve_with_scheduler(config, metrics, scheduler).await });
+    sleep(Duration::from_millis(100)).await;
+    let client = reqwest::Client::new();
+
+    // When: Requesting a test notification without the admin token
+    let response = client
+        .post("http://127.0.0.1:19121/admin/test-notification")
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    // Then: Should be rejected without notifying
+    assert_eq!(response.status(), 401);
+    assert!(received.lock().unwrap().is_empty());
+
+    // When: Requesting it with the token
+    let response = client
+        .post("http://127.0.0.1:19121/admin/test-notification")
+        .header("Authorization", "Bearer admin-secret")
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    // Then: The channel receives the test message and reports success
+    assert_eq!(response.status(), 200);
+    let json: serde_json::Value = response.json().await.expect("Failed to parse JSON");
+    assert_eq!(json["ntfy"]["success"], true);
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].1, TEST_NOTIFICATION_MESSAGE);
+
+    // Cleanup
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_schedule_upcoming_endpoint() {
+    // Given: A server attached to a scheduler running every 10 minutes via cron
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let mut schedule_config = common::create_test_config(ScheduleMode::Cron);
+    schedule_config.schedule.cron_expression = Some("0 */10 * * * *".to_string());
+    let scheduler = Arc::new(Scheduler::new(schedule_config, metrics.clone(), None));
+    let config = server_config("127.0.0.1:19122");
+    let server_handle =
+        tokio::spawn(async move { server::serve_with_scheduler(config, metrics, scheduler).await });
+    sleep(Duration::from_millis(100)).await;
+
+    // When: Requesting the next 5 run times
+    let response = reqwest::get("http://127.0.0.1:19122/schedule/upcoming?count=5")
+        .await
+        .expect("Failed to send request");
+
+    // Then: Five strictly increasing times, ten minutes apart
+    assert_eq!(response.status(), 200);
+    let json: serde_json::Value = response.json().await.expect("Failed to parse JSON");
+    assert_eq!(json["mode"], "cron");
+    let runs: Vec<chrono::DateTime<chrono::FixedOffset>> = json["upcoming"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|run| chrono::DateTime::parse_from_rfc3339(run.as_str().unwrap()).unwrap())
+        .collect();
+    assert_eq!(runs.len(), 5);
+    for pair in runs.windows(2) {
+        assert_eq!(pair[1] - pair[0], chrono::Duration::minutes(10));
+    }
+
+    // Cleanup
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_debug_outputs_ring_buffer() {
+    // Given: A scheduler keeping 2 outputs, whose command prints a run counter
+    let counter = env::temp_dir().join(format!("netspeed-debug-{}", std::process::id()));
+    let _ = std::fs::remove_file(&counter);
+    let script = format!(
+        "n=$(cat {0} 2>/dev/null || echo 0); n=$((n+1)); echo $n > {0}; echo run-$n",
+        counter.display()
+    );
+    let mut scheduler_config = common::create_test_config(ScheduleMode::Interval);
+    scheduler_config.speedtest.command = "sh".to_string();
+    scheduler_config.speedtest.args = vec!["-c".to_string(), script];
+    scheduler_config.debug_capture_count = 2;
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let scheduler = Arc::new(Scheduler::new(scheduler_config, metrics.clone(), None));
+    let mut config = server_config("127.0.0.1:19123");
+    config.admin_token = Some("admin-secret".to_string());
+    let server_scheduler = scheduler.clone();
+    let server_handle = tokio::spawn(async move {
+        server::serve_with_scheduler(config, metrics, server_scheduler).await
+    });
+    sleep(Duration::from_millis(100)).await;
+
+    // When: Three runs complete
+    for _ in 0..3 {
+        assert!(scheduler.run_once().await);
+    }
+    let response = reqwest::Client::new()
+        .get("http://127.0.0.1:19123/debug/outputs")
+        .header("Authorization", "Bearer admin-secret")
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    // Then: Only the last two outputs are kept, oldest first
+    assert_eq!(response.status(), 200);
+    let json: serde_json::Value = response.json().await.expect("Failed to parse JSON");
+    let outputs: Vec<&str> = json
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["output"].as_str().unwrap())
+        .collect();
+    assert_eq!(outputs, vec!["run-2\n", "run-3\n"]);
+    assert_eq!(json[0]["success"], false);
+
+    // Cleanup
+    server_handle.abort();
+    let _ = std::fs::remove_file(&counter);
+}
+
+#[tokio::test]
+async fn test_debug_last_output_after_failed_parse() {
+    // Given: A capturing scheduler whose command prints something unparseable
+    let mut scheduler_config = common::create_test_config(ScheduleMode::Interval);
+    scheduler_config.speedtest.command = "echo".to_string();
+    scheduler_config.speedtest.args = vec!["not json".to_string()];
+    scheduler_config.debug_capture_count = 1;
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let scheduler = Arc::new(Scheduler::new(scheduler_config, metrics.clone(), None));
+    let mut config = server_config("127.0.0.1:19133");
+    config.admin_token = Some("admin-secret".to_string());
+    let server_scheduler = scheduler.clone();
+    let server_handle = tokio::spawn(async move {
+        server::serve_with_scheduler(config, metrics, server_scheduler).await
+    });
+    sleep(Duration::from_millis(100)).await;
+    let client = reqwest::Client::new();
+    let request = || {
+        client
+            .get("http://127.0.0.1:19133/debug/last-output")
+            .header("Authorization", "Bearer admin-secret")
+    };
+
+    // When: Asking before and after a run, and without the token
+    let before = request().send().await.expect("Failed to send request");
+    assert!(scheduler.run_once().await);
+    let after = request().send().await.expect("Failed to send request");
+    let unauthorized = client
+        .get("http://127.0.0.1:19133/debug/last-output")
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    // Then: The raw output of the failed run is returned, to admins only
+    assert_eq!(before.status(), 404);
+    assert_eq!(after.status(), 200);
+    let json: serde_json::Value = after.json().await.expect("Failed to parse JSON");
+    assert_eq!(json["output"], "not json\n");
+    assert_eq!(json["success"], false);
+    assert_eq!(unauthorized.status(), 401);
+
+    // Cleanup
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_scrape_duration_metric() {
+    // Given: A running server
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let config = server_config("127.0.0.1:19124");
+    let server_handle = tokio::spawn(async move { server::serve(config, metrics).await });
+    sleep(Duration::from_millis(100)).await;
+
+    // When: Scraping twice, so the second scrape reports the first one's duration
+    reqwest::get("http://127.0.0.1:19124/metrics")
+        .await
+        .expect("Failed to request metrics");
+    let body = reqwest::get("http://127.0.0.1:19124/metrics")
+        .await
+        .expect("Failed to request metrics")
+        .text()
+        .await
+        .expect("Failed to read body");
+
+    // Then: The scrape duration is exposed with a non-negative value
+    let value: f64 = body
+        .lines()
+        .find_map(|line| line.strip_prefix("netspeed_scrape_duration_seconds "))
+        .expect("Scrape duration metric missing")
+        .parse()
+        .expect("Scrape duration is not a number");
+    assert!(value >= 0.0);
+
+    // Cleanup
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_annotations_after_failure() {
+    // Given: A server attached to a scheduler whose speedtest always fails
+    let mut scheduler_config = common::create_test_config(ScheduleMode::Interval);
+    scheduler_config.speedtest.command = "false".to_string();
+    scheduler_config.speedtest.args = Vec::new();
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let scheduler = Arc::new(Scheduler::new(scheduler_config, metrics.clone(), None));
+    let config = server_config("127.0.0.1:19125");
+    let server_scheduler = scheduler.clone();
+    let server_handle = tokio::spawn(async move {
+        server::serve_with_scheduler(config, metrics, server_scheduler).await
+    });
+    sleep(Duration::from_millis(100)).await;
+
+    // When: A run fails and the annotations are fetched
+    assert!(scheduler.run_once().await);
+    let response = reqwest::get("http://127.0.0.1:19125/annotations")
+        .await
+        .expect("Failed to send request");
+
+    // Then: The failure is returned as a Grafana annotation
+    assert_eq!(response.status(), 200);
+    let json: serde_json::Value = response.json().await.expect("Failed to parse JSON");
+    let annotations = json.as_array().expect("Annotations should be a list");
+    assert_eq!(annotations.len(), 1);
+    assert_eq!(annotations[0]["tags"][1], "failure");
+    assert_eq!(annotations[0]["text"], "Command failed with exit code 1");
+    assert!(annotations[0]["time"].as_i64().unwrap() > 0);
+
+    // Cleanup
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_run_endpoint_triggers_run() {
+    // Given: A running scheduler far from its next run, with a slow speedtest
+    let mut scheduler_config = common::create_test_config(ScheduleMode::Cron);
+    scheduler_config.schedule.cron_expression = Some("0 0 0 1 1 *".to_string());
+    scheduler_config.speedtest.command = "sleep".to_string();
+    scheduler_config.speedtest.args = vec!["2".to_string()];
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let scheduler = Arc::new(Scheduler::new(scheduler_config, metrics.clone(), None));
+    let loop_scheduler = scheduler.clone();
+    let scheduler_handle = tokio::spawn(async move { loop_scheduler.run().await });
+    let config = server_config("127.0.0.1:19126");
+    let server_handle =
+        tokio::spawn(async move { server::serve_with_scheduler(config, metrics, scheduler).await });
+    sleep(Duration::from_millis(100)).await;
+    let client = reqwest::Client::new();
+
+    // When: Requesting a run
+    let response = client
+        .post("http://127.0.0.1:19126/run")
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    // Then: It is accepted with a run id
+    assert_eq!(response.status(), 202);
+    let json: serde_json::Value = response.json().await.expect("Failed to parse JSON");
+    assert!(json["run_id"].as_str().unwrap().starts_with("manual-"));
+
+    // When: Requesting another run while the first is executing
+    sleep(Duration::from_millis(300)).await;
+    let response = client
+        .post("http://127.0.0.1:19126/run")
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    // Then: It conflicts with the run in progress (overlap is disabled)
+    assert_eq!(response.status(), 409);
+
+    // Cleanup
+    server_handle.abort();
+    scheduler_handle.abort();
+}
+
+#[tokio::test]
+async fn test_run_endpoint_rejects_runs_within_min_gap() {
+    // Given: A running scheduler with a 60 second minimum gap and a fast speedtest
+    let mut scheduler_config = common::create_test_config(ScheduleMode::Cron);
+    scheduler_config.schedule.cron_expression = Some("0 0 0 1 1 *".to_string());
+    scheduler_config.schedule.min_gap_seconds = 60;
+    scheduler_config.speedtest.command = "cat".to_string();
+    scheduler_config.speedtest.args = vec!["tests/fixtures/speedtest_sample.json".to_string()];
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let scheduler = Arc::new(Scheduler::new(scheduler_config, metrics.clone(), None));
+    let loop_scheduler = scheduler.clone();
+    let scheduler_handle = tokio::spawn(async move { loop_scheduler.run().await });
+    let config = server_config("127.0.0.1:19130");
+    let server_metrics = metrics.clone();
+    let server_handle = tokio::spawn(async move {
+        server::serve_with_scheduler(config, server_metrics, scheduler).await
+    });
+    sleep(Duration::from_millis(100)).await;
+    let client = reqwest::Client::new();
+
+    // When: Requesting a run and waiting for it to finish
+    let response = client
+        .post("http://127.0.0.1:19130/run")
+        .send()
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status(), 202);
+    for _ in 0..50 {
+        if metrics.runs_total.with_label_values(&["success"]).get() == 1 {
+            break;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+
+    // When: Requesting another run inside the gap
+    let response = client
+        .post("http://127.0.0.1:19130/run")
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    // Then: It is rejected with the time left before a run may start
+    assert_eq!(response.status(), 429);
+    let retry_after: u64 = response.headers()["retry-after"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=60).contains(&retry_after));
+    assert_eq!(metrics.runs_total.with_label_values(&["success"]).get(), 1);
+    assert_eq!(metrics.runs_total.with_label_values(&["skipped"]).get(), 0);
+
+    // Cleanup
+    server_handle.abort();
+    scheduler_handle.abort();
+}
+
+#[tokio::test]
+async fn test_run_endpoint_rejects_runs_beyond_concurrency_cap() {
+    // Given: A running scheduler allowing overlap, capped at 2 runs, with a slow speedtest
+    let mut scheduler_config = common::create_test_config(ScheduleMode::Cron);
+    scheduler_config.schedule.cron_expression = Some("0 0 0 1 1 *".to_string());
+    scheduler_config.schedule.allow_overlap = true;
+    scheduler_config.schedule.max_concurrent_runs = 2;
+    scheduler_config.speedtest.command = "sleep".to_string();
+    scheduler_config.speedtest.args = vec!["2".to_string()];
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let scheduler = Arc::new(Scheduler::new(scheduler_config, metrics.clone(), None));
+    let loop_scheduler = scheduler.clone();
+    let scheduler_handle = tokio::spawn(async move { loop_scheduler.run().await });
+    let config = server_config("127.0.0.1:19131");
+    let server_handle =
+        tokio::spawn(async move { server::serve_with_scheduler(config, metrics, scheduler).await });
+    sleep(Duration::from_millis(100)).await;
+    let client = reqwest::Client::new();
+
+    // When: Requesting three runs while the first ones are executing
+    let mut statuses = Vec::new();
+    for _ in 0..3 {
+        let response = client
+            .post("http://127.0.0.1:19131/run")
+            .send()
+            .await
+            .expect("Failed to send request");
+        statuses.push(response.status().as_u16());
+        sleep(Duration::from_millis(200)).await;
+    }
+
+    // Then: Two runs start concurrently and the one beyond the cap is rejected
+    assert_eq!(statuses, vec![202, 202, 429]);
+
+    // Cleanup
+    server_handle.abort();
+    scheduler_handle.abort();
+}
+
+#[tokio::test]
+async fn test_health_reports_stale_run() {
+    // Given: A running server whose last successful run is far in the past
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let config = server_config("127.0.0.1:19127");
+    let test_metrics = metrics.clone();
+    let server_handle = tokio::spawn(async move { server::serve(config, metrics).await });
+    sleep(Duration::from_millis(100)).await;
+    test_metrics.last_success.set(1.0);
+    test_metrics.run_timestamp_seconds.set(1234567890.0);
+
+    // When: Checking health
+    let response = reqwest::get("http://127.0.0.1:19127/healthz")
+        .await
+        .expect("Failed to request health");
+
+    // Then: The run is flagged as stale
+    let body: serde_json::Value = response.json().await.expect("Failed to parse JSON");
+    assert_eq!(body["stale"], true);
+
+    // When: A run just happened
+    test_metrics
+        .run_timestamp_seconds
+        .set(chrono::Utc::now().timestamp() as f64);
+    let response = reqwest::get("http://127.0.0.1:19127/healthz")
+        .await
+        .expect("Failed to request health");
+
+    // Then: The data is fresh
+    let body: serde_json::Value = response.json().await.expect("Failed to parse JSON");
+    assert_eq!(body["stale"], false);
+
+    // Cleanup
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_results_endpoint() {
+    // Given: A running server with results from a run that reported jitter only
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let config = server_config("127.0.0.1:19128");
+    let test_metrics = metrics.clone();
+    let server_handle = tokio::spawn(async move { server::serve(config, metrics).await });
+    sleep(Duration::from_millis(100)).await;
+    test_metrics.download_bps.set(812_300_000.0);
+    test_metrics.upload_bps.set(42_100_000.0);
+    test_metrics.latency_seconds.set(0.0184);
+    test_metrics.set_jitter_seconds(0.0);
+    test_metrics.run_timestamp_seconds.set(1234567890.0);
+    test_metrics.last_success.set(1.0);
+
+    // When: Fetching the latest results
+    let response = reqwest::get("http://127.0.0.1:19128/results")
+        .await
+        .expect("Failed to request results");
+
+    // Then: The gauges are returned as JSON, with unreported values as null
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await.expect("Failed to parse JSON");
+    assert_eq!(body["download_bps"], 812_300_000.0);
+    assert_eq!(body["upload_bps"], 42_100_000.0);
+    assert_eq!(body["latency_seconds"], 0.0184);
+    assert_eq!(body["jitter_seconds"], 0.0);
+    assert!(body["packet_loss_ratio"].is_null());
+    assert_eq!(body["last_run_timestamp"], 1234567890.0);
+    assert_eq!(body["last_success"], true);
+
+    // Cleanup
+    server_handle.abort();
+}
@@ -1,7 +1,13 @@
-use netspeed_lite::notifier::{format_failure_message, format_success_message};
+use netspeed_lite::config::SpeedUnit;
+use netspeed_lite::notifier::{
+    format_failure_message, format_success_message, format_success_message_with, status_class,
+    truncate_message,
+};
 use netspeed_lite::runner::{ErrorCategory, SpeedtestResult};
 use std::time::Duration;
 
+mod common;
+
 #[test]
 fn test_format_success_message() {
     // Given: A successful speedtest result with all metrics
@@ -11,6 +17,15 @@ fn test_format_success_message() {
         latency_seconds: 0.0184,
         jitter_seconds: Some(0.0021),
         packet_loss_ratio: None,
+        missing: None,
+        external_ip: None,
+        download_latency_seconds: None,
+        upload_latency_seconds: None,
+        interface_name: None,
+        server_id: None,
+        server_name: None,
+        server_location: None,
+        measured_at: None,
     };
     let duration = Duration::from_secs(30);
 
@@ -25,6 +40,70 @@ fn test_format_success_message() {
     assert!(message.contains("📊 Jitter: 2.1 ms"));
 }
 
+#[test]
+fn test_format_success_message_speed_units() {
+    // Given: The same speedtest result
+    let result = SpeedtestResult {
+        download_bps: 812_300_000.0,
+        upload_bps: 42_100_000.0,
+        latency_seconds: 0.0184,
+        jitter_seconds: None,
+        packet_loss_ratio: None,
+        missing: None,
+        external_ip: None,
+        download_latency_seconds: None,
+        upload_latency_seconds: None,
+        interface_name: None,
+        server_id: None,
+        server_name: None,
+        server_location: None,
+        measured_at: None,
+    };
+    let duration = Duration::from_secs(30);
+
+    // When: Formatting it in megabits and megabytes per second
+    let mbps = format_success_message_with(&result, duration, SpeedUnit::Mbps);
+    let mbyteps = format_success_message_with(&result, duration, SpeedUnit::MBytePerSec);
+
+    // Then: Speeds should be converted and labelled with the chosen unit
+    assert!(mbps.contains("⬇️ Download: 812.3 Mbps"));
+    assert!(mbps.contains("⬆️ Upload: 42.1 Mbps"));
+    assert!(mbyteps.contains("⬇️ Download: 101.5 MB/s"));
+    assert!(mbyteps.contains("⬆️ Upload: 5.3 MB/s"));
+    assert!(!mbyteps.contains("Mbps"));
+}
+
+#[test]
+fn test_format_success_message_bufferbloat() {
+    // Given: A result whose upload latency balloons under load
+    let mut result = SpeedtestResult {
+        download_bps: 812_300_000.0,
+        upload_bps: 42_100_000.0,
+        latency_seconds: 0.0184,
+        jitter_seconds: None,
+        packet_loss_ratio: None,
+        missing: None,
+        external_ip: None,
+        download_latency_seconds: Some(0.0192),
+        upload_latency_seconds: Some(0.1207),
+        interface_name: None,
+        server_id: None,
+        server_name: None,
+        server_location: None,
+        measured_at: None,
+    };
+    let duration = Duration::from_secs(30);
+
+    // When: Formatting the message, then again with a well-behaved upload
+    let bloated = format_success_message(&result, duration);
+    result.upload_latency_seconds = Some(0.0195);
+    let healthy = format_success_message(&result, duration);
+
+    // Then: Only the bloated result calls out the worst loaded latency
+    assert!(bloated.contains("🐢 Bufferbloat: 120.7 ms under load"));
+    assert!(!healthy.contains("Bufferbloat"));
+}
+
 #[test]
 fn test_format_failure_timeout() {
     // Given: A timeout error after 120 seconds
@@ -40,7 +119,10 @@ fn test_format_failure_timeout() {
 #[test]
 fn test_format_failure_command_failed() {
     // Given: A command failure with exit code 1
-    let error = ErrorCategory::CommandFailed(1);
+    let error = ErrorCategory::CommandFailed {
+        code: 1,
+        stderr: String::new(),
+    };
 
     // When: Formatting the failure message
     let message = format_failure_message(&error);
@@ -48,3 +130,378 @@ fn test_format_failure_command_failed() {
     // Then: Should show exit code
     assert_eq!(message, "exit=1");
 }
+
+#[test]
+fn test_format_failure_command_failed_with_stderr() {
+    // Given: A command failure that printed an error on stderr
+    let error = ErrorCategory::CommandFailed {
+        code: 2,
+        stderr: "[error] Limit reached".to_string(),
+    };
+
+    // When: Formatting the failure message
+    let message = format_failure_message(&error);
+
+    // Then: The stderr tail follows the exit code
+    assert_eq!(message, "exit=2: [error] Limit reached");
+}
+
+#[test]
+fn test_status_class_buckets() {
+    // Given/When/Then: Status codes map to their class, anything else to "error"
+    assert_eq!(status_class(200), "2xx");
+    assert_eq!(status_class(204), "2xx");
+    assert_eq!(status_class(301), "3xx");
+    assert_eq!(status_class(429), "4xx");
+    assert_eq!(status_class(502), "5xx");
+    assert_eq!(status_class(999), "error");
+}
+
+#[test]
+fn test_truncate_message_at_max_chars() {
+    // Given: A message longer than a 20-character limit
+    let message = "⬇️ 812.3 Mbps ⬆️ 42.1 Mbps 📶 18.4 ms (took 30s)";
+
+    // When: Truncating it, and a message that already fits
+    let truncated = truncate_message(message, 20);
+    let short = truncate_message("Speed test OK", 20);
+
+    // Then: The long one is cut to exactly 20 characters ending in an ellipsis
+    assert!(message.chars().count() > 20);
+    assert_eq!(truncated.chars().count(), 20);
+    assert!(truncated.ends_with('…'));
+    assert!(message.starts_with(truncated.trim_end_matches('…')));
+    assert_eq!(short, "Speed test OK");
+}
+
+#[cfg(feature = "notify")]
+#[tokio::test]
+async fn test_notify_responses_counted_by_status_class() {
+    use axum::http::StatusCode;
+    use netspeed_lite::metrics::Metrics;
+    use netspeed_lite::notifier::Notifier;
+
+    // Given: Mock ntfy servers answering 200, 404 and 503, plus an unreachable one
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let mut urls = Vec::new();
+    for status in [
+        StatusCode::OK,
+        StatusCode::NOT_FOUND,
+        StatusCode::SERVICE_UNAVAILABLE,
+    ] {
+        let (url, _received) = common::spawn_mock_ntfy(status).await;
+        urls.push(url);
+    }
+    urls.push("http://127.0.0.1:1/topic".to_string());
+
+    // When: Sending one notification to each
+    for url in &urls {
+        let notifier = Notifier::new(common::ntfy_config(url), metrics.clone());
+        notifier
+            .notify_message("ℹ️", "status check".to_string())
+            .await;
+    }
+
+    // Then: Each response lands in its own bucket
+    let responses = &metrics.notify_responses_total;
+    assert_eq!(responses.with_label_values(&["2xx"]).get(), 1);
+    assert_eq!(responses.with_label_values(&["4xx"]).get(), 1);
+    assert_eq!(responses.with_label_values(&["5xx"]).get(), 1);
+    assert_eq!(responses.with_label_values(&["error"]).get(), 1);
+    assert_eq!(
+        metrics.notify_total.with_label_values(&["failure"]).get(),
+        3
+    );
+}
+
+#[cfg(feature = "notify")]
+#[tokio::test]
+async fn test_notification_retried_after_server_errors() {
+    use axum::http::StatusCode;
+    use netspeed_lite::metrics::Metrics;
+    use netspeed_lite::notifier::Notifier;
+
+    // Given: A mock ntfy server failing twice with 503 before accepting
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let (url, received) = common::spawn_mock_ntfy_sequence(vec![
+        StatusCode::SERVICE_UNAVAILABLE,
+        StatusCode::SERVICE_UNAVAILABLE,
+        StatusCode::OK,
+    ])
+    .await;
+    let mut config = common::ntfy_config(&url);
+    config.max_retries = 3;
+
+    // When: Sending a notification
+    Notifier::new(config, metrics.clone())
+        .notify_message("ℹ️", "status check".to_string())
+        .await;
+
+    // Then: The third attempt delivers it, and no failure is counted
+    assert_eq!(received.lock().unwrap().len(), 3);
+    let notify_total = &metrics.notify_total;
+    assert_eq!(notify_total.with_label_values(&["success"]).get(), 1);
+    assert_eq!(notify_total.with_label_values(&["failure"]).get(), 0);
+}
+
+#[cfg(feature = "notify")]
+#[tokio::test]
+async fn test_notification_not_retried_after_client_error() {
+    use axum::http::StatusCode;
+    use netspeed_lite::metrics::Metrics;
+    use netspeed_lite::notifier::Notifier;
+
+    // Given: A mock ntfy server rejecting the request with 403
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let (url, received) =
+        common::spawn_mock_ntfy_sequence(vec![StatusCode::FORBIDDEN, StatusCode::OK]).await;
+    let mut config = common::ntfy_config(&url);
+    config.max_retries = 3;
+
+    // When: Sending a notification
+    Notifier::new(config, metrics.clone())
+        .notify_message("ℹ️", "status check".to_string())
+        .await;
+
+    // Then: It fails after a single attempt
+    assert_eq!(received.lock().unwrap().len(), 1);
+    assert_eq!(
+        metrics.notify_total.with_label_values(&["failure"]).get(),
+        1
+    );
+}
+
+#[cfg(feature = "notify")]
+#[tokio::test]
+async fn test_last_notification_info_tracks_latest() {
+    use axum::http::StatusCode;
+    use netspeed_lite::metrics::Metrics;
+    use netspeed_lite::notifier::Notifier;
+
+    // Given: One notification delivered, then one rejected
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let (ok_url, _received) = common::spawn_mock_ntfy(StatusCode::OK).await;
+    let (bad_url, _received) = common::spawn_mock_ntfy(StatusCode::FORBIDDEN).await;
+    for url in [&ok_url, &bad_url] {
+        Notifier::new(common::ntfy_config(url), metrics.clone())
+            .notify_message("ℹ️", "status check".to_string())
+            .await;
+    }
+
+    // When: Rendering the metrics
+    let rendered = metrics.render().expect("Failed to render metrics");
+
+    // Then: Only the most recent channel/outcome is exposed, with its timestamp
+    let info = &metrics.last_notification_info;
+    assert!(info.with_label_values(&["ntfy", "failure"]).get() > 0.0);
+    assert!(
+        rendered.contains(r#"netspeed_last_notification_info{channel="ntfy",outcome="failure"}"#)
+    );
+    assert!(
+        !rendered.contains(r#"netspeed_last_notification_info{channel="ntfy",outcome="success"}"#)
+    );
+}
+
+#[cfg(feature = "notify")]
+#[tokio::test]
+async fn test_markdown_notification() {
+    use axum::http::StatusCode;
+    use netspeed_lite::metrics::Metrics;
+    use netspeed_lite::notifier::Notifier;
+    use netspeed_lite::runner::RunOutcome;
+
+    // Given: A notifier with Markdown formatting enabled
+    let (url, received) = common::spawn_mock_ntfy(StatusCode::OK).await;
+    let mut config = common::ntfy_config(&url);
+    config.markdown = true;
+    let notifier = Notifier::new(config, Metrics::new().unwrap());
+    let result = SpeedtestResult {
+        download_bps: 812_300_000.0,
+        upload_bps: 42_100_000.0,
+        latency_seconds: 0.0184,
+        jitter_seconds: None,
+        packet_loss_ratio: None,
+        missing: None,
+        external_ip: None,
+        download_latency_seconds: None,
+        upload_latency_seconds: None,
+        interface_name: None,
+        server_id: None,
+        server_name: None,
+        server_location: None,
+        measured_at: None,
+    };
+
+    // When: Sending a success notification
+    notifier
+        .notify(&RunOutcome::Success(result), Duration::from_secs(30))
+        .await;
+
+    // Then: The Markdown header is set and the body is a Markdown table
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 1);
+    let (headers, body) = &received[0];
+    assert_eq!(headers["markdown"], "yes");
+    assert!(body.starts_with("| Metric | Value |\n| --- | --- |"));
+    assert!(body.contains("| **Download** | 812.3 Mbps |"));
+}
+
+#[test]
+fn test_format_failure_terse_vs_verbose() {
+    use chrono::{TimeZone, Utc};
+    use netspeed_lite::config::{OutputFormat, OutputStream, SpeedtestConfig};
+    use netspeed_lite::notifier::format_failure_verbose;
+
+    // Given: A command failure and the speedtest configuration that produced it
+    let error = ErrorCategory::CommandFailed {
+        code: 1,
+        stderr: String::new(),
+    };
+    let speedtest = SpeedtestConfig {
+        command: "/usr/bin/speedtest".to_string(),
+        args: vec!["--format=json".to_string(), "--accept-license".to_string()],
+        timeout_seconds: 120,
+        first_output_timeout_seconds: None,
+        selection_timeout_seconds: None,
+        max_attempts: 1,
+        allow_partial: false,
+        parse_last_line: false,
+        max_latency_ms: None,
+        output_stream: OutputStream::Stdout,
+        output_format: OutputFormat::Ookla,
+    };
+    let at = Utc.with_ymd_and_hms(2024, 5, 1, 12, 30, 0).unwrap();
+
+    // When: Formatting tersely and verbosely
+    let terse = format_failure_message(&error);
+    let verbose = format_failure_verbose(&error, &speedtest, at);
+
+    // Then: Terse stays short; verbose carries the error, command and timestamp
+    assert_eq!(terse, "exit=1");
+    assert_eq!(
+        verbose,
+        "Command failed with exit code 1\n\
+         command: /usr/bin/speedtest --format=json --accept-license\n\
+         time: 2024-05-01T12:30:00+00:00"
+    );
+}
+
+#[cfg(feature = "notify")]
+#[tokio::test]
+async fn test_seconds_since_last_notify_success_resets() {
+    use axum::http::StatusCode;
+    use netspeed_lite::metrics::Metrics;
+    use netspeed_lite::notifier::Notifier;
+
+    // Given: Metrics that have gone over a second without a delivered notification
+    let metrics = Metrics::new().unwrap();
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    metrics.update_seconds_since_notify_success();
+    assert!(metrics.seconds_since_last_notify_success.get() >= 1.0);
+
+    // When: A notification is delivered and a failing one is attempted
+    let (url, _received) = common::spawn_mock_ntfy(StatusCode::OK).await;
+    Notifier::new(common::ntfy_config(&url), metrics.clone())
+        .notify_message("ℹ️", "status check".to_string())
+        .await;
+    Notifier::new(
+        common::ntfy_config("http://127.0.0.1:1/topic"),
+        metrics.clone(),
+    )
+    .notify_message("ℹ️", "status check".to_string())
+    .await;
+    metrics.update_seconds_since_notify_success();
+
+    // Then: The gauge counts from the successful delivery again
+    assert!(metrics.seconds_since_last_notify_success.get() < 1.0);
+}
+
+#[cfg(feature = "notify")]
+#[tokio::test]
+async fn test_startup_notification() {
+    use axum::http::StatusCode;
+    use netspeed_lite::metrics::Metrics;
+    use netspeed_lite::notifier::{startup_message, Notifier};
+
+    // Given: A notifier pointing at a mock ntfy server
+    let (url, received) = common::spawn_mock_ntfy(StatusCode::OK).await;
+    let notifier = Notifier::new(common::ntfy_config(&url), Metrics::new().unwrap());
+
+    // When: Announcing startup
+    notifier.notify_start().await;
+
+    // Then: The channel receives the message with the running version
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].1, startup_message());
+    assert!(received[0].1.contains(env!("CARGO_PKG_VERSION")));
+}
+
+#[cfg(feature = "notify")]
+#[tokio::test]
+async fn test_run_message_carries_quality_tag() {
+    use axum::http::StatusCode;
+    use netspeed_lite::metrics::Metrics;
+    use netspeed_lite::notifier::Notifier;
+    use netspeed_lite::runner::RunOutcome;
+
+    // Given: A notifier with quality tags, as used for verbose failure reports
+    let (url, received) = common::spawn_mock_ntfy(StatusCode::OK).await;
+    let mut config = common::ntfy_config(&url);
+    config.quality_tags = true;
+    let notifier = Notifier::new(config, Metrics::new().unwrap());
+    let outcome = RunOutcome::Failure(ErrorCategory::Timeout(120));
+
+    // When: Sending a custom message about a failed run
+    notifier
+        .notify_run_message(&outcome, "❌", "verbose report".to_string())
+        .await;
+
+    // Then: The run's quality tag is appended to the configured tags
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].0["Tags"], "speedtest,isp,red");
+}
+
+#[test]
+fn test_quality_tag_against_thresholds() {
+    use netspeed_lite::notifier::quality_tag;
+    use netspeed_lite::runner::RunOutcome;
+
+    // Given: A full result whose jitter is above a 10ms bound
+    let mut result = SpeedtestResult {
+        download_bps: 812_300_000.0,
+        upload_bps: 42_100_000.0,
+        latency_seconds: 0.0184,
+        jitter_seconds: Some(0.025),
+        packet_loss_ratio: None,
+        missing: None,
+        external_ip: None,
+        download_latency_seconds: None,
+        upload_latency_seconds: None,
+        interface_name: None,
+        server_id: None,
+        server_name: None,
+        server_location: None,
+        measured_at: None,
+    };
+
+    // When: Rating it with and without the bound
+    let over_bound = quality_tag(&RunOutcome::Success(result.clone()), Some(10.0));
+    let unbounded = quality_tag(&RunOutcome::Success(result.clone()), None);
+
+    // Then: It is yellow only against the bound; partial results are yellow and failures red
+    assert_eq!(over_bound, "yellow");
+    assert_eq!(unbounded, "green");
+    result.jitter_seconds = Some(0.002);
+    assert_eq!(
+        quality_tag(&RunOutcome::Success(result.clone()), Some(10.0)),
+        "green"
+    );
+    result.missing = Some(netspeed_lite::runner::Direction::Upload);
+    assert_eq!(quality_tag(&RunOutcome::Success(result), None), "yellow");
+    let rejected = RunOutcome::Failure(ErrorCategory::InvalidOutput(
+        "latency 950.0ms exceeds maximum 500.0ms".to_string(),
+    ));
+    assert_eq!(quality_tag(&rejected, Some(10.0)), "red");
+}
@@ -0,0 +1,118 @@
+use netspeed_lite::metrics::Metrics;
+use netspeed_lite::resources::{parse_memory_rss, update_memory_gauges, CpuSmoother, UsageAlert};
+use std::time::Duration;
+
+mod common;
+
+#[test]
+fn test_memory_gauges_in_bytes_and_mb() {
+    // Given: A fresh metrics instance
+    let metrics = Metrics::new().expect("Failed to create metrics");
+
+    // When: Recording a 48 MiB RSS reading
+    update_memory_gauges(&metrics, 48 * 1024 * 1024);
+
+    // Then: Bytes stay authoritative and the MB gauge mirrors them
+    assert_eq!(metrics.process_memory_bytes.get(), 50_331_648.0);
+    assert_eq!(metrics.process_memory_mb.get(), 48.0);
+}
+
+#[test]
+fn test_parse_memory_rss() {
+    // Given: A /proc/self/status excerpt with a VmRSS line
+    let status = "Name:\tnetspeed-lite\nVmPeak:\t   20480 kB\nVmRSS:\t    5632 kB\nThreads:\t1\n";
+
+    // When: Parsing the RSS
+    let bytes = parse_memory_rss(status).expect("VmRSS should parse");
+
+    // Then: Should convert kB to bytes
+    assert_eq!(bytes, 5632 * 1024);
+}
+
+#[test]
+fn test_parse_memory_rss_missing_is_error() {
+    // Given: A status file without a VmRSS line
+    let status = "Name:\tkthreadd\nState:\tS (sleeping)\n";
+
+    // When: Parsing the RSS
+    let result = parse_memory_rss(status);
+
+    // Then: Should fail instead of reporting 0 bytes
+    assert!(result.is_err());
+    assert!(result.unwrap_err().to_string().contains("VmRSS"));
+}
+
+#[test]
+fn test_cpu_smoothing_sequence() {
+    // Given: A smoother with alpha 0.25
+    let mut smoother = CpuSmoother::new(0.25);
+
+    // When: Feeding a spiky sequence of per-interval CPU readings
+    let smoothed = [10.0, 90.0, 10.0, 10.0].map(|usage| smoother.update(usage));
+
+    // Then: The first sample seeds the average and the spike is damped
+    assert_eq!(smoothed, [10.0, 30.0, 25.0, 21.25]);
+}
+
+#[test]
+fn test_usage_alert_needs_consecutive_samples() {
+    // Given: An alert above 80% requiring 3 consecutive samples
+    let mut alert = UsageAlert::new(80.0, 3, Duration::from_secs(3600));
+
+    // When: A dip interrupts the streak before it reaches 3
+    let interrupted = [90.0, 90.0, 50.0, 90.0, 90.0].map(|cpu| alert.record(cpu));
+
+    // Then: Nothing fires until the third consecutive high sample
+    assert!(interrupted.iter().all(|fired| !fired));
+    assert!(alert.record(90.0));
+}
+
+#[cfg(feature = "notify")]
+#[tokio::test]
+async fn test_high_cpu_notification() {
+    use axum::http::StatusCode;
+    use common::create_test_config;
+    use netspeed_lite::config::ScheduleMode;
+    use netspeed_lite::notifier::Notifier;
+    use netspeed_lite::resources::HighUsageAlerts;
+
+    // Given: A 50% CPU threshold over 2 samples and a mock ntfy server
+    let (url, received) = common::spawn_mock_ntfy(StatusCode::OK).await;
+    let mut config = create_test_config(ScheduleMode::Interval);
+    config.notify_on_high_cpu = Some(50.0);
+    config.high_usage_samples = 2;
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let notifier = Notifier::new(common::ntfy_config(&url), metrics.clone());
+    let mut alerts = HighUsageAlerts::from_config(&config);
+
+    // When: The CPU gauge stays at 95% for four samples
+    metrics.process_cpu_usage.set(95.0);
+    for _ in 0..4 {
+        alerts.check(&metrics, Some(&notifier)).await;
+    }
+
+    // Then: A single notification is sent, then the cooldown holds
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 1);
+    assert!(received[0].1.starts_with("High CPU usage: 95.0%"));
+    assert_eq!(received[0].0["title"], "netspeed-lite 🔥");
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+#[test]
+fn test_platform_sampler_samples_own_process() {
+    use netspeed_lite::resources::{PlatformSampler, ResourceSampler};
+
+    // Given: The sampler for the host platform
+    let mut sampler = PlatformSampler::new();
+
+    // When: Sampling memory and CPU twice
+    let memory = sampler.memory_rss();
+    let first_cpu = sampler.cpu_usage();
+    let second_cpu = sampler.cpu_usage();
+
+    // Then: Samples succeed, with a nonzero RSS and a non-negative CPU usage
+    assert!(memory.expect("Memory sample should succeed") > 0);
+    assert!(first_cpu.expect("CPU sample should succeed") >= 0.0);
+    assert!(second_cpu.expect("CPU sample should succeed") >= 0.0);
+}
@@ -1,4 +1,9 @@
-use netspeed_lite::runner::{parse_speedtest_output, ErrorCategory};
+use netspeed_lite::config::{OutputFormat, OutputStream, SpeedtestConfig};
+use netspeed_lite::runner::{
+    parse_librespeed_output, parse_librespeed_output_with, parse_speedtest_output,
+    parse_speedtest_output_with, resolve_command, run_speedtest, selection_complete, Direction,
+    ErrorCategory, ParseOptions, RunOutcome,
+};
 
 #[test]
 fn test_parse_valid_output() {
@@ -46,3 +51,604 @@ fn test_parse_invalid_json() {
     // Then: Should fail with InvalidOutput error
     assert!(matches!(result, Err(ErrorCategory::InvalidOutput(_))));
 }
+
+#[test]
+fn test_parse_download_only_with_allow_partial() {
+    // Given: JSON output missing the upload field and partial results allowed
+    let json = r#"{
+        "download": {"bandwidth": 101537500},
+        "ping": {"latency": 18.4}
+    }"#;
+    let options = ParseOptions {
+        allow_partial: true,
+        ..Default::default()
+    };
+
+    // When: Parsing the output
+    let result = parse_speedtest_output_with(json, &options).unwrap();
+
+    // Then: Should succeed with download set and upload marked missing
+    assert_eq!(result.download_bps, 812300000.0);
+    assert_eq!(result.upload_bps, 0.0);
+    assert_eq!(result.missing, Some(Direction::Upload));
+}
+
+#[test]
+fn test_parse_download_only_strict() {
+    // Given: JSON output missing the upload field with default options
+    let json = r#"{
+        "download": {"bandwidth": 101537500},
+        "ping": {"latency": 18.4}
+    }"#;
+
+    // When: Parsing the output
+    let result = parse_speedtest_output(json);
+
+    // Then: Should fail with MissingFields error
+    assert!(matches!(result, Err(ErrorCategory::MissingFields(_))));
+}
+
+#[test]
+fn test_resolve_command_from_path() {
+    // Given: A bare command name available on every Unix system
+    let command = "sh";
+
+    // When: Resolving it via PATH
+    let resolved = resolve_command(command).expect("sh should be in PATH");
+
+    // Then: Should return an absolute path to an existing file
+    let path = std::path::Path::new(&resolved);
+    assert!(path.is_absolute());
+    assert!(path.exists());
+}
+
+#[test]
+fn test_resolve_command_keeps_explicit_path() {
+    // Given: A command given as a path
+    let command = "/usr/local/bin/speedtest";
+
+    // When: Resolving it
+    let resolved = resolve_command(command).unwrap();
+
+    // Then: Should be returned unchanged
+    assert_eq!(resolved, command);
+}
+
+#[test]
+fn test_resolve_command_missing() {
+    // Given: A command that does not exist
+    let command = "netspeed-lite-no-such-command";
+
+    // When: Resolving it
+    let result = resolve_command(command);
+
+    // Then: Should fail with a clear error
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .contains("not found in PATH"));
+}
+
+fn speedtest_config(script: &str, first_output_timeout_seconds: Option<u64>) -> SpeedtestConfig {
+    SpeedtestConfig {
+        command: "sh".to_string(),
+        args: vec!["-c".to_string(), script.to_string()],
+        timeout_seconds: 30,
+        first_output_timeout_seconds,
+        selection_timeout_seconds: None,
+        max_attempts: 1,
+        allow_partial: false,
+        parse_last_line: false,
+        max_latency_ms: None,
+        output_stream: OutputStream::Stdout,
+        output_format: OutputFormat::Ookla,
+    }
+}
+
+#[tokio::test]
+async fn test_command_failure_captures_stderr_tail() {
+    // Given: A command that floods stderr, ends with an error and exits nonzero
+    let config = speedtest_config(
+        "head -c 2000 /dev/zero | tr '\\0' x >&2; echo >&2; echo '[error] Limit reached' >&2; exit 2",
+        None,
+    );
+
+    // When: Running the speedtest
+    let result = run_speedtest(&config).await;
+
+    // Then: The failure carries the exit code and only the tail of stderr
+    match result.outcome {
+        RunOutcome::Failure(ErrorCategory::CommandFailed { code, stderr }) => {
+            assert_eq!(code, 2);
+            assert!(stderr.ends_with("[error] Limit reached"));
+            assert!(stderr.len() <= netspeed_lite::runner::STDERR_TAIL_BYTES);
+        }
+        other => panic!("Expected command failure, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_first_output_timeout_kills_silent_command() {
+    // Given: A command that stays silent longer than the first-output deadline
+    let config = speedtest_config("sleep 5; cat tests/fixtures/speedtest_sample.json", Some(1));
+
+    // When: Running the speedtest
+    let result = run_speedtest(&config).await;
+
+    // Then: Should time out on the first-output deadline, not the total timeout
+    match result.outcome {
+        RunOutcome::Failure(ErrorCategory::Timeout(seconds)) => assert_eq!(seconds, 1),
+        other => panic!("Expected first-output timeout, got {:?}", other),
+    }
+    assert!(result.duration < std::time::Duration::from_secs(3));
+}
+
+#[tokio::test]
+async fn test_first_output_timeout_allows_prompt_output() {
+    // Given: A command that starts writing immediately and then keeps going
+    let config = speedtest_config(
+        "printf '{'; sleep 2; tail -c +2 tests/fixtures/speedtest_sample.json",
+        Some(1),
+    );
+
+    // When: Running the speedtest
+    let result = run_speedtest(&config).await;
+
+    // Then: Only the first byte is bounded, so the run should succeed
+    match result.outcome {
+        RunOutcome::Success(r) => assert_eq!(r.download_bps, 812_300_000.0),
+        other => panic!("Expected success, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_first_output_timeout_watches_output_stream() {
+    // Given: A wrapper that prints its result on stderr only, starting
+    // promptly and finishing after the first-output deadline
+    let mut config = speedtest_config(
+        "printf '{' >&2; sleep 2; tail -c +2 tests/fixtures/speedtest_sample.json >&2",
+        Some(1),
+    );
+    config.output_stream = OutputStream::Stderr;
+
+    // When: Running the speedtest
+    let result = run_speedtest(&config).await;
+
+    // Then: The prompt stderr output satisfies the deadline
+    match result.outcome {
+        RunOutcome::Success(r) => assert_eq!(r.download_bps, 812_300_000.0),
+        other => panic!("Expected success, got {:?}", other),
+    }
+
+    // When: The command only talks on stdout while the result is read from stderr
+    config.args[1] = "echo 'Retrieving server list...'; sleep 5".to_string();
+    let result = run_speedtest(&config).await;
+
+    // Then: The silent result stream times out on the first-output deadline
+    match result.outcome {
+        RunOutcome::Failure(ErrorCategory::Timeout(seconds)) => assert_eq!(seconds, 1),
+        other => panic!("Expected first-output timeout, got {:?}", other),
+    }
+    assert!(result.duration < std::time::Duration::from_secs(3));
+}
+
+#[tokio::test]
+async fn test_selection_timeout_aborts_stalled_selection() {
+    // Given: A CLI that keeps reporting progress but never picks a server
+    let mut config = speedtest_config(
+        "echo 'Retrieving server list...' >&2; sleep 5; cat tests/fixtures/speedtest_sample.json",
+        None,
+    );
+    config.selection_timeout_seconds = Some(1);
+
+    // When: Running the speedtest
+    let result = run_speedtest(&config).await;
+
+    // Then: Should fail fast with a server-selection timeout
+    match result.outcome {
+        RunOutcome::Failure(ErrorCategory::SelectionTimeout(seconds)) => assert_eq!(seconds, 1),
+        other => panic!("Expected selection timeout, got {:?}", other),
+    }
+    assert!(result.duration < std::time::Duration::from_secs(3));
+}
+
+#[tokio::test]
+async fn test_selection_timeout_allows_slow_measurement() {
+    // Given: A CLI that selects a server promptly, then measures for longer
+    // than the selection deadline
+    let mut config = speedtest_config(
+        "echo '   Server: Example ISP - Amsterdam (id: 1234)' >&2; sleep 2; cat tests/fixtures/speedtest_sample.json",
+        None,
+    );
+    config.selection_timeout_seconds = Some(1);
+
+    // When: Running the speedtest
+    let result = run_speedtest(&config).await;
+
+    // Then: Selection completed in time, so the run should succeed
+    match result.outcome {
+        RunOutcome::Success(r) => assert_eq!(r.download_bps, 812_300_000.0),
+        other => panic!("Expected success, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_selection_timeout_with_ookla_progress_stream() {
+    // Given: Real Ookla progress output, whose testStart event arrives
+    // promptly while the rest of the test takes longer than the deadline
+    let mut config = speedtest_config(
+        "head -n 1 tests/fixtures/speedtest_progress.jsonl; sleep 2; tail -n +2 tests/fixtures/speedtest_progress.jsonl",
+        None,
+    );
+    config.selection_timeout_seconds = Some(1);
+    config.parse_last_line = true;
+
+    // When: Running the speedtest
+    let result = run_speedtest(&config).await;
+
+    // Then: Selection was seen in time and the final result line is parsed
+    match result.outcome {
+        RunOutcome::Success(r) => {
+            assert_eq!(r.download_bps, 812_300_000.0);
+            assert_eq!(r.server_id, Some(12345));
+        }
+        other => panic!("Expected success, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_selection_complete_on_ookla_progress_stream() {
+    // Given: Real Ookla progress output
+    let output = std::fs::read_to_string("tests/fixtures/speedtest_progress.jsonl").unwrap();
+    let (test_start, _) = output.split_once('\n').unwrap();
+
+    // Then: Selection is complete once the testStart event is printed, but
+    // not while it is only partially written
+    assert!(selection_complete(test_start.as_bytes()));
+    assert!(!selection_complete(&test_start.as_bytes()[..40]));
+    assert!(!selection_complete(
+        br#"{"type":"ping","ping":{"progress":0.5}}"#
+    ));
+}
+
+#[tokio::test]
+async fn test_run_librespeed_format() {
+    // Given: A command printing librespeed-cli output
+    let mut config = speedtest_config("cat tests/fixtures/librespeed_sample.json", None);
+    config.output_format = OutputFormat::Librespeed;
+
+    // When: Running the speedtest
+    let result = run_speedtest(&config).await;
+
+    // Then: The output is parsed as librespeed JSON
+    match result.outcome {
+        RunOutcome::Success(r) => assert_eq!(r.upload_bps, 42_100_000.0),
+        other => panic!("Expected success, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_output_stream_stderr() {
+    // Given: A wrapper that prints its JSON result on stderr
+    let mut config = speedtest_config("cat tests/fixtures/speedtest_sample.json >&2", None);
+
+    // When: Parsing stdout (the default)
+    let stdout_result = run_speedtest(&config).await;
+
+    // Then: There is nothing to parse
+    assert!(matches!(stdout_result.outcome, RunOutcome::Failure(_)));
+
+    // When: Parsing stderr, or stdout falling back to stderr
+    for stream in [OutputStream::Stderr, OutputStream::Both] {
+        config.output_stream = stream;
+        let result = run_speedtest(&config).await;
+
+        // Then: The result on stderr is used
+        match result.outcome {
+            RunOutcome::Success(r) => assert_eq!(r.download_bps, 812_300_000.0),
+            other => panic!("Expected success for {:?}, got {:?}", stream, other),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_retry_after_command_failure() {
+    // Given: A command that fails on its first attempt only, and two attempts allowed
+    let marker = std::env::temp_dir().join(format!("netspeed-retry-{}", std::process::id()));
+    let _ = std::fs::remove_file(&marker);
+    let mut config = speedtest_config(
+        &format!(
+            "if [ -e {0} ]; then cat tests/fixtures/speedtest_sample.json; else touch {0}; exit 1; fi",
+            marker.display()
+        ),
+        None,
+    );
+    config.max_attempts = 2;
+
+    // When: Running the speedtest
+    let result = run_speedtest(&config).await;
+    let _ = std::fs::remove_file(&marker);
+
+    // Then: The failed attempt is retried once and the second attempt succeeds
+    assert_eq!(result.retries, 1);
+    assert!(matches!(result.outcome, RunOutcome::Success(_)));
+}
+
+#[tokio::test]
+async fn test_retry_gives_up_after_max_attempts() {
+    // Given: A command that always fails, and two attempts allowed
+    let mut config = speedtest_config("exit 1", None);
+    config.max_attempts = 2;
+
+    // When: Running the speedtest
+    let result = run_speedtest(&config).await;
+
+    // Then: Only one retry is made and the last failure is reported
+    assert_eq!(result.retries, 1);
+    assert!(matches!(
+        result.outcome,
+        RunOutcome::Failure(ErrorCategory::CommandFailed { code: 1, .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_retry_skips_command_not_found() {
+    // Given: A command that doesn't exist, and three attempts allowed
+    let mut config = speedtest_config("", None);
+    config.command = "netspeed-nonexistent-command".to_string();
+    config.max_attempts = 3;
+
+    // When: Running the speedtest
+    let result = run_speedtest(&config).await;
+
+    // Then: The failure isn't retried, as it would fail the same way again
+    assert_eq!(result.retries, 0);
+    assert!(matches!(
+        result.outcome,
+        RunOutcome::Failure(ErrorCategory::CommandNotFound(_))
+    ));
+}
+
+#[test]
+fn test_error_stage_mapping() {
+    // Given: Errors raised while interpreting output and while running the command
+    let parse_errors = [
+        ErrorCategory::InvalidOutput("unexpected EOF".to_string()),
+        ErrorCategory::MissingFields("download.bandwidth".to_string()),
+    ];
+    let execute_errors = [
+        ErrorCategory::CommandFailed {
+            code: 2,
+            stderr: String::new(),
+        },
+        ErrorCategory::Timeout(120),
+    ];
+
+    // When/Then: Output problems map to "parse", command problems to "execute"
+    for error in &parse_errors {
+        assert_eq!(error.stage(), "parse", "{:?}", error);
+    }
+    for error in &execute_errors {
+        assert_eq!(error.stage(), "execute", "{:?}", error);
+    }
+}
+
+#[test]
+fn test_parse_packet_loss() {
+    // Given: Output from a CLI version reporting packet loss as a percentage
+    let json = r#"{"download": {"bandwidth": 1}, "upload": {"bandwidth": 1},
+        "ping": {"latency": 1.0}, "packetLoss": 2.5}"#;
+    let out_of_range = r#"{"download": {"bandwidth": 1}, "upload": {"bandwidth": 1},
+        "ping": {"latency": 1.0}, "packetLoss": 250}"#;
+
+    // When: Parsing them
+    let result = parse_speedtest_output(json).expect("Should parse");
+    let invalid = parse_speedtest_output(out_of_range);
+
+    // Then: The percentage becomes a ratio, and an impossible one is rejected
+    assert_eq!(result.packet_loss_ratio, Some(0.025));
+    assert!(matches!(invalid, Err(ErrorCategory::InvalidOutput(_))));
+}
+
+#[test]
+fn test_parse_external_ip() {
+    // Given: Output with a valid and an invalid external IP
+    let valid = r#"{"download": {"bandwidth": 1}, "upload": {"bandwidth": 1},
+        "ping": {"latency": 1.0}, "interface": {"externalIp": "203.0.113.7"}}"#;
+    let invalid = r#"{"download": {"bandwidth": 1}, "upload": {"bandwidth": 1},
+        "ping": {"latency": 1.0}, "interface": {"externalIp": "not-an-ip"}}"#;
+
+    // When/Then: Only a well-formed address is kept
+    let ip = parse_speedtest_output(valid).unwrap().external_ip;
+    assert_eq!(ip, Some("203.0.113.7".parse().unwrap()));
+    assert_eq!(parse_speedtest_output(invalid).unwrap().external_ip, None);
+}
+
+#[test]
+fn test_parse_interface_name() {
+    // Given: Sample Ookla output with an interface block
+    let json = std::fs::read_to_string("tests/fixtures/speedtest_sample.json").unwrap();
+
+    // When: Parsing the output
+    let result = parse_speedtest_output(&json).unwrap();
+
+    // Then: The interface name is kept
+    assert_eq!(result.interface_name.as_deref(), Some("eth0"));
+}
+
+#[test]
+fn test_parse_server_details() {
+    // Given: Sample Ookla output with a server block
+    let json = std::fs::read_to_string("tests/fixtures/speedtest_sample.json").unwrap();
+
+    // When: Parsing the output
+    let result = parse_speedtest_output(&json).unwrap();
+
+    // Then: The server ID, name and location are kept
+    assert_eq!(result.server_id, Some(12345));
+    assert_eq!(
+        result.server_name.as_deref(),
+        Some("Example Speedtest Server")
+    );
+    assert_eq!(result.server_location.as_deref(), Some("Brussels"));
+}
+
+#[test]
+fn test_parse_loaded_latency() {
+    // Given: Sample Ookla output with download/upload latency blocks
+    let json = std::fs::read_to_string("tests/fixtures/speedtest_sample.json").unwrap();
+
+    // When: Parsing the output
+    let result = parse_speedtest_output(&json).unwrap();
+
+    // Then: The interquartile mean of each block is kept, in seconds
+    assert!((result.download_latency_seconds.unwrap() - 0.018234).abs() < 1e-9);
+    assert!((result.upload_latency_seconds.unwrap() - 0.019456).abs() < 1e-9);
+}
+
+#[test]
+fn test_parse_without_loaded_latency() {
+    // Given: Older output with no latency under load
+    let json = r#"{
+        "download": {"bandwidth": 101537500},
+        "upload": {"bandwidth": 5262500},
+        "ping": {"latency": 18.4}
+    }"#;
+
+    // When: Parsing the output
+    let result = parse_speedtest_output(json).unwrap();
+
+    // Then: Loaded latency is absent rather than an error
+    assert_eq!(result.download_latency_seconds, None);
+    assert_eq!(result.upload_latency_seconds, None);
+}
+
+#[test]
+fn test_parse_rejects_latency_above_max() {
+    // Given: Output with an absurd 50000ms latency and a 2000ms sanity bound
+    let json = r#"{
+        "download": {"bandwidth": 101537500},
+        "upload": {"bandwidth": 5262500},
+        "ping": {"latency": 50000.0}
+    }"#;
+    let options = ParseOptions {
+        max_latency_ms: Some(2000.0),
+        ..Default::default()
+    };
+
+    // When: Parsing with and without the bound
+    let bounded = parse_speedtest_output_with(json, &options);
+    let unbounded = parse_speedtest_output(json);
+
+    // Then: Only the bounded parse rejects it as invalid output
+    assert!(matches!(bounded, Err(ErrorCategory::InvalidOutput(_))));
+    assert!(unbounded.is_ok());
+}
+
+#[test]
+fn test_parse_last_line_after_progress_lines() {
+    // Given: Progress objects printed line by line before the final summary
+    let output = concat!(
+        r#"{"type":"ping","ping":{"latency":18.0}}"#,
+        "\n",
+        r#"{"type":"download","download":{"bandwidth":90000000}}"#,
+        "\n",
+        r#"{"type":"result","download":{"bandwidth":101537500},"upload":{"bandwidth":5262500},"ping":{"latency":18.4}}"#,
+        "\n\n",
+    );
+    let options = ParseOptions {
+        parse_last_line: true,
+        ..Default::default()
+    };
+
+    // When: Parsing strictly and with parse_last_line
+    let strict = parse_speedtest_output(output);
+    let result = parse_speedtest_output_with(output, &options).unwrap();
+
+    // Then: Only the last-line parse succeeds, using the summary's values
+    assert!(matches!(strict, Err(ErrorCategory::InvalidOutput(_))));
+    assert_eq!(result.download_bps, 812_300_000.0);
+    assert_eq!(result.upload_bps, 42_100_000.0);
+}
+
+#[test]
+fn test_parse_measured_at() {
+    // Given: Output with a valid timestamp, and one with an unparsable timestamp
+    let json = r#"{
+        "timestamp": "2026-01-15T10:00:00Z",
+        "download": {"bandwidth": 101537500},
+        "upload": {"bandwidth": 5262500},
+        "ping": {"latency": 18.4}
+    }"#;
+    let invalid = json.replace("2026-01-15T10:00:00Z", "yesterday");
+
+    // When: Parsing both
+    let result = parse_speedtest_output(json).unwrap();
+    let invalid_result = parse_speedtest_output(&invalid).unwrap();
+
+    // Then: The valid timestamp is kept and the invalid one is dropped
+    assert_eq!(
+        result.measured_at.map(|time| time.timestamp()),
+        Some(1_768_471_200)
+    );
+    assert!(invalid_result.measured_at.is_none());
+}
+
+#[test]
+fn test_parse_librespeed_output() {
+    // Given: Real librespeed-cli --json output
+    let json = std::fs::read_to_string("tests/fixtures/librespeed_sample.json").unwrap();
+
+    // When: Parsing the output
+    let result = parse_librespeed_output(&json).unwrap();
+
+    // Then: Mbps and milliseconds are normalized to bits/s and seconds
+    assert_eq!(result.download_bps, 812_300_000.0);
+    assert_eq!(result.upload_bps, 42_100_000.0);
+    assert!((result.latency_seconds - 0.0184).abs() < 1e-9);
+    assert!((result.jitter_seconds.unwrap() - 0.0021).abs() < 1e-9);
+    assert_eq!(result.packet_loss_ratio, None);
+    assert_eq!(result.external_ip, Some("203.0.113.7".parse().unwrap()));
+    assert_eq!(
+        result.server_name.as_deref(),
+        Some("Amsterdam, Netherlands (Clouvider)")
+    );
+}
+
+#[test]
+fn test_parse_librespeed_single_object() {
+    // Given: Older librespeed-cli output with a bare object
+    let json = r#"{"ping": 12.0, "jitter": 1.5, "upload": 20.0, "download": 100.0}"#;
+
+    // When: Parsing the output
+    let result = parse_librespeed_output(json).unwrap();
+
+    // Then: It is parsed like a single-element list
+    assert_eq!(result.download_bps, 100_000_000.0);
+    assert_eq!(result.external_ip, None);
+}
+
+#[test]
+fn test_parse_librespeed_errors() {
+    // Given: An empty result list and a result without upload
+    let empty = "[]";
+    let no_upload = r#"[{"ping": 12.0, "download": 100.0}]"#;
+
+    // When/Then: Both are rejected by default
+    assert!(matches!(
+        parse_librespeed_output(empty),
+        Err(ErrorCategory::InvalidOutput(_))
+    ));
+    assert!(matches!(
+        parse_librespeed_output(no_upload),
+        Err(ErrorCategory::MissingFields(ref f)) if f == "upload"
+    ));
+
+    // When/Then: Partial results allow the missing upload
+    let options = ParseOptions {
+        allow_partial: true,
+        ..Default::default()
+    };
+    let result = parse_librespeed_output_with(no_upload, &options).unwrap();
+    assert_eq!(result.missing, Some(Direction::Upload));
+}
@@ -1,35 +1,13 @@
-use netspeed_lite::config::{
-    Config, NotifyOn, ScheduleConfig, ScheduleMode, ServerConfig, SpeedtestConfig,
-};
+use common::create_test_config;
+use netspeed_lite::config::ScheduleMode;
 use netspeed_lite::metrics::Metrics;
-use netspeed_lite::scheduler::Scheduler;
+use netspeed_lite::scheduler::{
+    detect_clock_skew, duty_cycle, AdaptiveInterval, AnomalyDetector, ScheduleJitter, Scheduler,
+};
 use std::env;
+use std::sync::Arc;
 
-fn create_test_config(mode: ScheduleMode) -> Config {
-    Config {
-        server: ServerConfig {
-            bind_address: "127.0.0.1:9109".to_string(),
-        },
-        schedule: ScheduleConfig {
-            mode,
-            interval_seconds: 3600,
-            cron_expression: Some("0 * * * *".to_string()),
-            timezone: "UTC".to_string(),
-            allow_overlap: false,
-        },
-        speedtest: SpeedtestConfig {
-            command: "speedtest".to_string(),
-            args: vec!["--format=json".to_string()],
-            timeout_seconds: 120,
-        },
-        ntfy: None,
-        notify_on: NotifyOn {
-            success: true,
-            failure: true,
-        },
-        resource_interval_seconds: 15,
-    }
-}
+mod common;
 
 #[test]
 fn test_scheduler_creation() {
@@ -64,6 +42,117 @@ fn test_schedule_mode_interval() {
     env::remove_var("PROMETHEUS_REGISTRY_PREFIX");
 }
 
+#[test]
+fn test_zero_interval_clamped() {
+    // Given: A programmatically built interval schedule of 0 seconds
+    let mut config = create_test_config(ScheduleMode::Interval);
+    config.schedule.interval_seconds = 0;
+    let scheduler = Scheduler::new(config, Metrics::new().unwrap(), None);
+
+    // When: Computing the next run
+    let before = chrono::Utc::now();
+    let next_run = scheduler.next_run_time();
+
+    // Then: It is at least a second out instead of immediately
+    assert!(next_run >= before + chrono::Duration::seconds(1));
+}
+
+#[test]
+fn test_interval_jitter_within_window() {
+    for _ in 0..20 {
+        // Given: A 600s interval schedule with up to 300s of jitter
+        let mut config = create_test_config(ScheduleMode::Interval);
+        config.schedule.interval_seconds = 600;
+        config.schedule.jitter_seconds = 300;
+        let scheduler = Scheduler::new(config, Metrics::new().unwrap(), None);
+
+        // When: Computing the next run
+        let before = chrono::Utc::now();
+        let next_run = scheduler.next_run_time();
+        let after = chrono::Utc::now();
+
+        // Then: It falls within [interval, interval + jitter] from now
+        assert!(next_run >= before + chrono::Duration::seconds(600));
+        assert!(next_run <= after + chrono::Duration::seconds(900));
+    }
+}
+
+#[tokio::test]
+async fn test_next_run_time_is_the_planned_deadline() {
+    // Given: A running interval scheduler with up to 300s of jitter
+    let mut config = create_test_config(ScheduleMode::Interval);
+    config.schedule.interval_seconds = 600;
+    config.schedule.jitter_seconds = 300;
+    let scheduler = Arc::new(Scheduler::new(config, Metrics::new().unwrap(), None));
+    let loop_scheduler = scheduler.clone();
+    let handle = tokio::spawn(async move { loop_scheduler.run().await });
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+    // When: Asking for the next run repeatedly while the loop sleeps
+    let runs: Vec<_> = (0..10).map(|_| scheduler.next_run_time()).collect();
+
+    // Then: Every answer is the single deadline the loop is sleeping toward,
+    // with its jitter drawn once
+    assert!(runs.iter().all(|run| *run == runs[0]));
+
+    // When: The schedule changes
+    let mut schedule = scheduler.schedule();
+    schedule.interval_seconds = 1200;
+    scheduler.update_schedule(schedule).unwrap();
+
+    // Then: A new deadline is planned under the new schedule and kept
+    let replanned = scheduler.next_run_time();
+    assert!(replanned >= runs[0] + chrono::Duration::seconds(300));
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    assert_eq!(scheduler.next_run_time(), replanned);
+
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_upcoming_runs_start_from_pending_interval_run() {
+    // Given: A running interval scheduler with up to 300s of jitter
+    let mut config = create_test_config(ScheduleMode::Interval);
+    config.schedule.interval_seconds = 600;
+    config.schedule.jitter_seconds = 300;
+    let scheduler = Arc::new(Scheduler::new(config, Metrics::new().unwrap(), None));
+    let loop_scheduler = scheduler.clone();
+    let handle = tokio::spawn(async move { loop_scheduler.run().await });
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+    // When: Listing the upcoming runs twice
+    let upcoming = scheduler.upcoming_runs(3);
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    let again = scheduler.upcoming_runs(3);
+
+    // Then: The list starts at the scheduled run and steps by the interval,
+    // without drifting between calls
+    assert_eq!(upcoming[0], scheduler.next_run_time());
+    assert_eq!(upcoming[1] - upcoming[0], chrono::Duration::seconds(600));
+    assert_eq!(upcoming[2] - upcoming[1], chrono::Duration::seconds(600));
+    assert_eq!(upcoming, again);
+
+    handle.abort();
+}
+
+#[test]
+fn test_schedule_jitter_is_seeded() {
+    // Given: Two jitter sources with the same seed and one with another seed
+    let mut first = ScheduleJitter::new(42);
+    let mut second = ScheduleJitter::new(42);
+    let mut other = ScheduleJitter::new(7);
+
+    // When: Drawing offsets of up to 300s from each
+    let first: Vec<u64> = (0..20).map(|_| first.offset_seconds(300)).collect();
+    let second: Vec<u64> = (0..20).map(|_| second.offset_seconds(300)).collect();
+    let other: Vec<u64> = (0..20).map(|_| other.offset_seconds(300)).collect();
+
+    // Then: The same seed reproduces the sequence, and every offset is in range
+    assert_eq!(first, second);
+    assert_ne!(first, other);
+    assert!(first.iter().chain(&other).all(|offset| *offset <= 300));
+}
+
 #[test]
 fn test_schedule_mode_hourly_aligned() {
     // Given: Configuration with hourly aligned mode
@@ -135,3 +224,616 @@ fn test_allow_overlap_flag() {
     drop(scheduler);
     env::remove_var("PROMETHEUS_REGISTRY_PREFIX");
 }
+
+#[cfg(not(feature = "notify"))]
+#[tokio::test]
+async fn test_scheduler_runs_with_notifier_disabled() {
+    use netspeed_lite::notifier::Notifier;
+
+    // Given: A build without the notify feature and an ntfy config present
+    let mut config = create_test_config(ScheduleMode::Interval);
+    config.speedtest.command = "cat".to_string();
+    config.speedtest.args = vec!["tests/fixtures/speedtest_sample.json".to_string()];
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let ntfy = common::ntfy_config("http://127.0.0.1:1/unused");
+    let notifier = Notifier::new(ntfy, metrics.clone());
+    let scheduler = Scheduler::new(config, metrics.clone(), Some(notifier));
+
+    // When: Executing a run
+    scheduler.run_once().await;
+
+    // Then: The run is recorded and no notification is attempted
+    assert_eq!(metrics.runs_total.with_label_values(&["success"]).get(), 1);
+    assert_eq!(metrics.download_bps.get(), 812_300_000.0);
+    assert_eq!(
+        metrics.notify_total.with_label_values(&["success"]).get(),
+        0
+    );
+    assert_eq!(
+        metrics.notify_total.with_label_values(&["failure"]).get(),
+        0
+    );
+}
+
+#[tokio::test]
+async fn test_suppressed_gauges_appear_after_first_run() {
+    // Given: Metrics with measurement gauges deferred until the first run
+    let mut config = create_test_config(ScheduleMode::Interval);
+    config.speedtest.command = "cat".to_string();
+    config.speedtest.args = vec!["tests/fixtures/speedtest_sample.json".to_string()];
+    let metrics = Metrics::with_deferred_measurements(true).expect("Failed to create metrics");
+    let scheduler = Scheduler::new(config, metrics.clone(), None);
+
+    // When: Rendering before any run
+    let rendered = metrics.render().expect("Failed to render metrics");
+
+    // Then: The download gauge should be absent
+    assert!(!rendered.contains("# TYPE netspeed_download_bps gauge"));
+    assert!(rendered.contains("netspeed_last_success"));
+
+    // When: A successful run completes
+    scheduler.run_once().await;
+    let rendered = metrics.render().expect("Failed to render metrics");
+
+    // Then: The download gauge should be rendered with the measured value
+    assert!(rendered.contains("netspeed_download_bps 812300000"));
+}
+
+#[tokio::test]
+async fn test_run_gap_observed() {
+    // Given: A scheduler with a quick speedtest
+    let mut config = create_test_config(ScheduleMode::Interval);
+    config.speedtest.command = "true".to_string();
+    config.speedtest.args = Vec::new();
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let scheduler = Scheduler::new(config, metrics.clone(), None);
+
+    // When: Two runs start about 300ms apart
+    scheduler.run_once().await;
+    assert_eq!(metrics.run_gap_seconds.get_sample_count(), 0);
+    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+    scheduler.run_once().await;
+
+    // Then: The single gap between them is recorded
+    assert_eq!(metrics.run_gap_seconds.get_sample_count(), 1);
+    let gap = metrics.run_gap_seconds.get_sample_sum();
+    assert!((0.3..5.0).contains(&gap), "unexpected gap {}", gap);
+    let rendered = metrics.render().expect("Failed to render metrics");
+    assert!(rendered.contains(r#"netspeed_run_gap_seconds_bucket{le="60"} 1"#));
+}
+
+#[tokio::test]
+async fn test_results_observed_into_histograms() {
+    // Given: A scheduler whose speedtest prints the sample output
+    let mut config = create_test_config(ScheduleMode::Interval);
+    config.speedtest.command = "cat".to_string();
+    config.speedtest.args = vec!["tests/fixtures/speedtest_sample.json".to_string()];
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let scheduler = Scheduler::new(config, metrics.clone(), None);
+
+    // When: A successful run completes and the metrics are rendered
+    scheduler.run_once().await;
+    let rendered = metrics.render().expect("Failed to render metrics");
+
+    // Then: Each result lands in its histogram, next to the unchanged gauge
+    assert!(rendered.contains(r#"netspeed_download_bps_histogram_bucket{le="1000000000"} 1"#));
+    assert!(rendered.contains(r#"netspeed_download_bps_histogram_bucket{le="750000000"} 0"#));
+    assert!(rendered.contains("netspeed_download_bps_histogram_sum 812300000"));
+    assert!(rendered.contains("netspeed_upload_bps_histogram_count 1"));
+    assert!(rendered.contains(r#"netspeed_latency_seconds_histogram_bucket{le="0.02"} 1"#));
+    assert!(rendered.contains("netspeed_latency_seconds_histogram_count 1"));
+    assert!(rendered.contains("netspeed_download_bps 812300000"));
+
+    // Then: The server the run used is exposed as an info gauge
+    assert!(rendered.contains(
+        r#"netspeed_server_info{id="12345",location="Brussels",name="Example Speedtest Server"} 1"#
+    ));
+}
+
+#[tokio::test]
+async fn test_measured_timestamp_used_for_run_timestamp() {
+    // Given: A scheduler whose speedtest reports when the test ran
+    let mut config = create_test_config(ScheduleMode::Interval);
+    config.speedtest.command = "echo".to_string();
+    config.speedtest.args = vec![r#"{"timestamp":"2026-01-15T10:00:00Z","download":{"bandwidth":101537500},"upload":{"bandwidth":5262500},"ping":{"latency":18.4}}"#.to_string()];
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let scheduler = Scheduler::new(config, metrics.clone(), None);
+
+    // When: A successful run completes
+    scheduler.run_once().await;
+
+    // Then: The run timestamp is the measured time, not the completion time
+    assert_eq!(metrics.last_success.get(), 1.0);
+    assert_eq!(metrics.run_timestamp_seconds.get(), 1_768_471_200.0);
+}
+
+#[cfg(feature = "notify")]
+#[tokio::test]
+async fn test_success_streak_notification() {
+    use axum::http::StatusCode;
+    use netspeed_lite::notifier::Notifier;
+
+    // Given: A streak milestone of 2 and per-run success notifications disabled
+    let (url, received) = common::spawn_mock_ntfy(StatusCode::OK).await;
+    let mut config = create_test_config(ScheduleMode::Interval);
+    config.speedtest.command = "cat".to_string();
+    config.speedtest.args = vec!["tests/fixtures/speedtest_sample.json".to_string()];
+    config.notify_on.success = false;
+    config.notify_success_streak = 2;
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let notifier = Notifier::new(common::ntfy_config(&url), metrics.clone());
+    let scheduler = Scheduler::new(config, metrics, Some(notifier));
+
+    // When: Running once
+    scheduler.run_once().await;
+
+    // Then: No milestone has been reached yet
+    assert!(received.lock().unwrap().is_empty());
+
+    // When: Running three more times
+    for _ in 0..3 {
+        scheduler.run_once().await;
+    }
+
+    // Then: Milestones fire at 2 and 4 consecutive successes
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 2);
+    assert_eq!(received[0].1, "2 consecutive successful runs");
+    assert_eq!(received[1].1, "4 consecutive successful runs");
+    assert_eq!(received[0].0["title"], "netspeed-lite 🎉");
+}
+
+#[cfg(feature = "notify")]
+#[tokio::test]
+async fn test_health_webhook_fires_on_transition() {
+    use axum::http::StatusCode;
+
+    // Given: A health webhook and a speedtest that keeps succeeding
+    let (url, received) = common::spawn_mock_ntfy(StatusCode::OK).await;
+    let mut config = create_test_config(ScheduleMode::Interval);
+    config.speedtest.command = "cat".to_string();
+    config.speedtest.args = vec!["tests/fixtures/speedtest_sample.json".to_string()];
+    config.health_webhook_url = Some(url);
+    let scheduler = Scheduler::new(config, Metrics::new().unwrap(), None);
+
+    // When: Running twice
+    scheduler.run_once().await;
+    scheduler.run_once().await;
+
+    // Then: Only the initializing -> healthy transition is posted
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 1);
+    let body: serde_json::Value = serde_json::from_str(&received[0].1).unwrap();
+    assert_eq!(body["old_state"], "initializing");
+    assert_eq!(body["new_state"], "healthy");
+    assert!(body["timestamp"].is_string());
+}
+
+#[cfg(feature = "notify")]
+#[tokio::test]
+async fn test_health_webhook_fires_when_run_becomes_stale() {
+    use axum::http::StatusCode;
+
+    // Given: A health webhook, runs considered stale after 1 second, and a
+    // running scheduler whose next run is months away
+    let (url, received) = common::spawn_mock_ntfy(StatusCode::OK).await;
+    let mut config = create_test_config(ScheduleMode::Cron);
+    config.schedule.cron_expression = Some("0 0 0 1 1 *".to_string());
+    config.speedtest.command = "cat".to_string();
+    config.speedtest.args = vec!["tests/fixtures/speedtest_sample.json".to_string()];
+    config.server.stale_after_seconds = 1;
+    config.health_webhook_url = Some(url);
+    let scheduler = Arc::new(Scheduler::new(config, Metrics::new().unwrap(), None));
+
+    // When: A run succeeds and no other run follows
+    scheduler.run_once().await;
+    let loop_scheduler = scheduler.clone();
+    let handle = tokio::spawn(async move { loop_scheduler.run().await });
+    for _ in 0..50 {
+        if received.lock().unwrap().len() >= 2 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    handle.abort();
+
+    // Then: The healthy -> stale transition is posted after the first run's
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 2);
+    let body: serde_json::Value = serde_json::from_str(&received[1].1).unwrap();
+    assert_eq!(body["old_state"], "healthy");
+    assert_eq!(body["new_state"], "stale");
+}
+
+#[tokio::test]
+async fn test_success_criterion_rejects_result() {
+    // Given: A criterion script that rejects results with an 812.3 Mbps download
+    let mut config = create_test_config(ScheduleMode::Interval);
+    config.speedtest.command = "cat".to_string();
+    config.speedtest.args = vec!["tests/fixtures/speedtest_sample.json".to_string()];
+    config.success_criterion_cmd =
+        Some(r#"grep -q '"download_bps":812300000' && exit 3"#.to_string());
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let scheduler = Scheduler::new(config, metrics.clone(), None);
+
+    // When: A run parses successfully but the script rejects it
+    scheduler.run_once().await;
+
+    // Then: The run counts as a failure at the criterion stage
+    assert_eq!(metrics.runs_total.with_label_values(&["success"]).get(), 0);
+    assert_eq!(metrics.runs_total.with_label_values(&["failure"]).get(), 1);
+    assert_eq!(
+        metrics.errors_total.with_label_values(&["criterion"]).get(),
+        1
+    );
+    assert_eq!(metrics.last_success.get(), 0.0);
+}
+
+#[tokio::test]
+async fn test_min_gap_rejects_quick_second_run() {
+    // Given: A 60 second minimum gap between runs
+    let mut config = create_test_config(ScheduleMode::Interval);
+    config.speedtest.command = "cat".to_string();
+    config.speedtest.args = vec!["tests/fixtures/speedtest_sample.json".to_string()];
+    config.schedule.min_gap_seconds = 60;
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let scheduler = Scheduler::new(config, metrics.clone(), None);
+
+    // When: Triggering two runs back to back
+    let first = scheduler.run_once().await;
+    let second = scheduler.run_once().await;
+
+    // Then: The first runs and the second is rejected as skipped
+    assert!(first);
+    assert!(!second);
+    assert!(scheduler.cooldown_remaining().is_some());
+    assert_eq!(metrics.runs_total.with_label_values(&["success"]).get(), 1);
+    assert_eq!(metrics.runs_total.with_label_values(&["skipped"]).get(), 1);
+}
+
+#[tokio::test]
+async fn test_max_concurrent_runs_caps_overlap() {
+    // Given: Overlapping runs allowed but capped at 2, with a slow speedtest
+    let mut config = create_test_config(ScheduleMode::Interval);
+    config.speedtest.command = "sh".to_string();
+    config.speedtest.args = vec![
+        "-c".to_string(),
+        "sleep 1; cat tests/fixtures/speedtest_sample.json".to_string(),
+    ];
+    config.schedule.allow_overlap = true;
+    config.schedule.max_concurrent_runs = 2;
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let scheduler = Scheduler::new(config, metrics.clone(), None);
+
+    // When: Triggering three runs at once
+    let (first, second, third) = tokio::join!(
+        scheduler.run_once(),
+        scheduler.run_once(),
+        scheduler.run_once()
+    );
+
+    // Then: Two run and the one beyond the cap is rejected as skipped
+    let executed = [first, second, third].iter().filter(|ran| **ran).count();
+    assert_eq!(executed, 2);
+    assert_eq!(metrics.runs_total.with_label_values(&["success"]).get(), 2);
+    assert_eq!(metrics.runs_total.with_label_values(&["skipped"]).get(), 1);
+}
+
+#[tokio::test]
+async fn test_textfile_written_after_run() {
+    // Given: A textfile path in a fresh temporary directory
+    let dir = env::temp_dir().join(format!("netspeed-textfile-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("netspeed.prom");
+    let mut config = create_test_config(ScheduleMode::Interval);
+    config.speedtest.command = "cat".to_string();
+    config.speedtest.args = vec!["tests/fixtures/speedtest_sample.json".to_string()];
+    config.textfile_path = Some(path.clone());
+    let scheduler = Scheduler::new(config, Metrics::new().unwrap(), None);
+
+    // When: A run completes
+    scheduler.run_once().await;
+
+    // Then: The file holds the rendered metrics and no temporary file is left
+    let contents = std::fs::read_to_string(&path).unwrap();
+    assert!(contents.contains("netspeed_download_bps 812300000"));
+    assert!(!dir.join("netspeed.prom.tmp").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_duty_cycle_calculation() {
+    // Given: A 30 second run in a 10 minute interval
+    let run = std::time::Duration::from_secs(30);
+    let interval = std::time::Duration::from_secs(600);
+
+    // When: Computing the duty cycle
+    let ratio = duty_cycle(run, interval);
+
+    // Then: Should be the run's fraction of the interval, clamped to 1
+    assert!((ratio - 0.05).abs() < 1e-9);
+    assert_eq!(duty_cycle(interval * 2, interval), 1.0);
+}
+
+#[tokio::test]
+async fn test_duty_cycle_set_after_run() {
+    // Given: A run taking ~200ms with a 1 second interval
+    let mut config = create_test_config(ScheduleMode::Interval);
+    config.schedule.interval_seconds = 1;
+    config.speedtest.command = "sh".to_string();
+    config.speedtest.args = vec![
+        "-c".to_string(),
+        "sleep 0.2; cat tests/fixtures/speedtest_sample.json".to_string(),
+    ];
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let scheduler = Scheduler::new(config, metrics.clone(), None);
+
+    // When: Executing a run
+    scheduler.run_once().await;
+
+    // Then: The duty cycle should reflect run duration / interval
+    let ratio = metrics.duty_cycle.get();
+    let expected = metrics.run_duration_seconds.get();
+    assert!((0.2..1.0).contains(&ratio));
+    assert!((ratio - expected).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn test_has_run_set_after_first_run() {
+    // Given: A scheduler whose speedtest command fails
+    let mut config = create_test_config(ScheduleMode::Interval);
+    config.speedtest.command = "false".to_string();
+    config.speedtest.args = vec![];
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let scheduler = Scheduler::new(config, metrics.clone(), None);
+
+    // Then: No run has happened yet
+    assert_eq!(metrics.has_run.get(), 0.0);
+
+    // When: A (failing) run completes
+    scheduler.run_once().await;
+
+    // Then: has_run flips to 1 even though last_success stays 0
+    assert_eq!(metrics.has_run.get(), 1.0);
+    assert_eq!(metrics.last_success.get(), 0.0);
+}
+
+#[tokio::test]
+async fn test_failure_counted_by_stage() {
+    // Given: A speedtest command that exits cleanly but prints non-JSON output
+    let mut config = create_test_config(ScheduleMode::Interval);
+    config.speedtest.command = "echo".to_string();
+    config.speedtest.args = vec!["not json".to_string()];
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let scheduler = Scheduler::new(config, metrics.clone(), None);
+
+    // When: The run completes
+    scheduler.run_once().await;
+
+    // Then: The failure is attributed to the parse stage
+    assert_eq!(metrics.errors_total.with_label_values(&["parse"]).get(), 1);
+    assert_eq!(
+        metrics.errors_total.with_label_values(&["execute"]).get(),
+        0
+    );
+}
+
+#[tokio::test]
+async fn test_server_failover_drops_pinned_server() {
+    // Given: A pinned server that keeps failing, with a failover threshold of 2
+    let mut config = create_test_config(ScheduleMode::Interval);
+    config.speedtest.command = "sh".to_string();
+    config.speedtest.args = vec![
+        "-c".to_string(),
+        "exit 1".to_string(),
+        "--server-id=1234".to_string(),
+    ];
+    config.server_failover_threshold = 2;
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let scheduler = Scheduler::new(config, metrics, None);
+
+    // When: The first run fails
+    scheduler.run_once().await;
+
+    // Then: The server stays pinned below the threshold
+    assert!(scheduler
+        .speedtest_config()
+        .args
+        .contains(&"--server-id=1234".to_string()));
+
+    // When: A second run fails, reaching the threshold
+    scheduler.run_once().await;
+
+    // Then: The next run lets Ookla choose the server
+    let args = scheduler.speedtest_config().args;
+    assert_eq!(args, vec!["-c", "exit 1"]);
+}
+
+#[test]
+fn test_adaptive_interval_grows_when_stable() {
+    // Given: An adaptive interval starting at 1h with a 10% tolerance
+    let mut adaptive = AdaptiveInterval::new(3600, 900, 14400, 0.1);
+
+    // When: Feeding results that stay within the tolerance
+    let intervals: Vec<u64> = [100.0e6, 103.0e6, 98.0e6, 101.0e6]
+        .into_iter()
+        .map(|bps| adaptive.record(Some(bps)))
+        .collect();
+
+    // Then: The interval doubles after the baseline and is capped at the max
+    assert_eq!(intervals, vec![3600, 7200, 14400, 14400]);
+}
+
+#[test]
+fn test_adaptive_interval_shrinks_on_swing_and_failure() {
+    // Given: An adaptive interval that has grown on stable results
+    let mut adaptive = AdaptiveInterval::new(3600, 900, 14400, 0.1);
+    adaptive.record(Some(100.0e6));
+    adaptive.record(Some(100.0e6));
+    assert_eq!(adaptive.current_seconds(), 7200);
+
+    // When: A result swings well outside the tolerance
+    let after_swing = adaptive.record(Some(40.0e6));
+
+    // Then: The interval halves
+    assert_eq!(after_swing, 3600);
+
+    // When: Runs then fail repeatedly
+    adaptive.record(None);
+    adaptive.record(None);
+    let after_failures = adaptive.record(None);
+
+    // Then: The interval keeps halving down to the minimum
+    assert_eq!(after_failures, 900);
+}
+
+#[test]
+fn test_anomaly_detector_flags_outlier() {
+    // Given: A 3-sigma detector fed a stable series around 100 Mbps
+    let mut detector = AnomalyDetector::new(3.0);
+    let stable: Vec<bool> = [100.0e6, 102.0e6, 99.0e6, 101.0e6, 100.0e6, 98.0e6, 101.0e6]
+        .into_iter()
+        .map(|bps| detector.record(bps))
+        .collect();
+
+    // When: A sudden drop to 20 Mbps arrives
+    let outlier = detector.record(20.0e6);
+
+    // Then: Only the outlier is flagged
+    assert!(stable.iter().all(|anomalous| !anomalous));
+    assert!(outlier);
+}
+
+#[test]
+fn test_anomaly_detector_needs_seeding_and_can_be_disabled() {
+    // Given: A fresh detector and a disabled one, both with two stable samples
+    let mut fresh = AnomalyDetector::new(3.0);
+    let mut disabled = AnomalyDetector::new(0.0);
+    for detector in [&mut fresh, &mut disabled] {
+        detector.record(100.0e6);
+        detector.record(101.0e6);
+    }
+
+    // When: Recording an extreme value
+    // Then: Neither flags it, for lack of samples or because detection is off
+    assert!(!fresh.record(1.0e6));
+    for _ in 0..5 {
+        disabled.record(100.0e6);
+    }
+    assert!(!disabled.record(1.0e6));
+}
+
+#[tokio::test]
+async fn test_adaptive_mode_adjusts_after_runs() {
+    // Given: A scheduler in adaptive mode whose speedtest returns identical results
+    let mut config = create_test_config(ScheduleMode::Adaptive);
+    config.speedtest.command = "cat".to_string();
+    config.speedtest.args = vec!["tests/fixtures/speedtest_sample.json".to_string()];
+    let scheduler = Scheduler::new(config, Metrics::new().unwrap(), None);
+    assert_eq!(scheduler.adaptive_interval_seconds(), 3600);
+
+    // When: Running twice
+    scheduler.run_once().await;
+    scheduler.run_once().await;
+
+    // Then: The stable second result lengthens the interval
+    assert_eq!(scheduler.adaptive_interval_seconds(), 7200);
+}
+
+#[tokio::test]
+async fn test_external_ip_exposed_only_when_enabled() {
+    for expose in [false, true] {
+        // Given: A successful speedtest that reports an external IP
+        let mut config = create_test_config(ScheduleMode::Interval);
+        config.speedtest.command = "cat".to_string();
+        config.speedtest.args = vec!["tests/fixtures/speedtest_sample.json".to_string()];
+        config.expose_external_ip = expose;
+        let metrics = Metrics::new().expect("Failed to create metrics");
+        let scheduler = Scheduler::new(config, metrics.clone(), None);
+
+        // When: The run completes
+        scheduler.run_once().await;
+
+        // Then: The IP info series is present only when exposure is enabled
+        let rendered = metrics.render().unwrap();
+        assert_eq!(
+            rendered.contains(r#"netspeed_external_ip_info{ip="203.0.113.7"} 1"#),
+            expose
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_interface_exposed_only_when_enabled() {
+    for expose in [false, true] {
+        // Given: A successful speedtest that reports its interface and MAC address
+        let mut config = create_test_config(ScheduleMode::Interval);
+        config.speedtest.command = "cat".to_string();
+        config.speedtest.args = vec!["tests/fixtures/speedtest_sample.json".to_string()];
+        config.expose_interface = expose;
+        let metrics = Metrics::new().expect("Failed to create metrics");
+        let scheduler = Scheduler::new(config, metrics.clone(), None);
+
+        // When: The run completes
+        scheduler.run_once().await;
+
+        // Then: The interface name is present only when enabled, the MAC never
+        let rendered = metrics.render().unwrap();
+        assert_eq!(
+            rendered.contains(r#"netspeed_interface_info{name="eth0"} 1"#),
+            expose
+        );
+        assert!(!rendered.contains("00:11:22:33:44:55"));
+    }
+}
+
+#[test]
+fn test_clock_skew_detection() {
+    // Given: A planned one-hour sleep
+    let planned = std::time::Duration::from_secs(3600);
+
+    // When/Then: Waking a few seconds late is normal scheduling jitter
+    assert_eq!(
+        detect_clock_skew(planned, std::time::Duration::from_secs(3605)),
+        None
+    );
+
+    // When/Then: Waking after a long suspend is reported with the skew
+    let after_suspend = std::time::Duration::from_secs(8 * 3600);
+    assert_eq!(
+        detect_clock_skew(planned, after_suspend),
+        Some(std::time::Duration::from_secs(7 * 3600))
+    );
+
+    // When/Then: Waking early is never skew
+    assert_eq!(
+        detect_clock_skew(planned, std::time::Duration::from_secs(10)),
+        None
+    );
+}
+
+#[tokio::test]
+async fn test_jitter_exceeded_flag() {
+    // Given: A 2ms jitter limit and a speedtest reporting 2.1ms jitter
+    let mut config = create_test_config(ScheduleMode::Interval);
+    config.speedtest.command = "cat".to_string();
+    config.speedtest.args = vec!["tests/fixtures/speedtest_sample.json".to_string()];
+    config.max_jitter_ms = Some(2.0);
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let scheduler = Scheduler::new(config.clone(), metrics.clone(), None);
+
+    // When: The run completes
+    scheduler.run_once().await;
+
+    // Then: The jitter flag is raised
+    assert_eq!(metrics.jitter_exceeded.get(), 1.0);
+
+    // Given/When: A limit above the measured jitter
+    config.max_jitter_ms = Some(5.0);
+    let scheduler = Scheduler::new(config, metrics.clone(), None);
+    scheduler.run_once().await;
+
+    // Then: The flag is cleared
+    assert_eq!(metrics.jitter_exceeded.get(), 0.0);
+}
@@ -0,0 +1,130 @@
+use netspeed_lite::config::ScheduleMode;
+use netspeed_lite::metrics::Metrics;
+use netspeed_lite::scheduler::Scheduler;
+use netspeed_lite::supervisor::supervise;
+use prometheus::{IntCounterVec, Opts};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+mod common;
+
+#[tokio::test]
+async fn test_supervisor_restarts_panicked_task() {
+    // Given: A task that panics on its first run and exits cleanly on the second
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let attempts = Arc::new(AtomicU32::new(0));
+    let task_attempts = attempts.clone();
+
+    // When: Supervising the task
+    supervise(
+        "test_task",
+        metrics.clone(),
+        Duration::from_millis(10),
+        move || {
+            let attempts = task_attempts.clone();
+            async move {
+                if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                    panic!("injected panic");
+                }
+            }
+        },
+    )
+    .await;
+
+    // Then: The task should have been restarted once and the restart counted
+    assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    assert_eq!(
+        metrics
+            .task_restarts_total
+            .with_label_values(&["test_task"])
+            .get(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn test_supervisor_does_not_restart_clean_exit() {
+    // Given: A task that exits normally
+    let metrics = Metrics::new().expect("Failed to create metrics");
+    let attempts = Arc::new(AtomicU32::new(0));
+    let task_attempts = attempts.clone();
+
+    // When: Supervising the task
+    supervise(
+        "clean_task",
+        metrics.clone(),
+        Duration::from_millis(10),
+        move || {
+            let attempts = task_attempts.clone();
+            async move {
+                attempts.fetch_add(1, Ordering::SeqCst);
+            }
+        },
+    )
+    .await;
+
+    // Then: The task should run exactly once with no restarts
+    assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    assert_eq!(
+        metrics
+            .task_restarts_total
+            .with_label_values(&["clean_task"])
+            .get(),
+        0
+    );
+}
+
+#[tokio::test]
+async fn test_scheduler_runs_again_after_panic_inside_run() {
+    // Given: A scheduler whose first run fails, and whose failure metrics
+    // panic when updated (wrong label cardinality), so that run panics
+    // midway through
+    let marker = std::env::temp_dir().join(format!("netspeed-panic-{}", std::process::id()));
+    let _ = std::fs::remove_file(&marker);
+    let mut config = common::create_test_config(ScheduleMode::Interval);
+    config.schedule.interval_seconds = 1;
+    config.speedtest.command = "sh".to_string();
+    config.speedtest.args = vec![
+        "-c".to_string(),
+        format!(
+            "if [ -e {0} ]; then cat tests/fixtures/speedtest_sample.json; else touch {0}; exit 1; fi",
+            marker.display()
+        ),
+    ];
+    let mut metrics = Metrics::new().expect("Failed to create metrics");
+    metrics.errors_total =
+        IntCounterVec::new(Opts::new("broken_errors_total", "broken"), &["a", "b"]).unwrap();
+    let scheduler = Arc::new(Scheduler::new(config, metrics.clone(), None));
+
+    // When: The scheduler runs under the supervisor
+    let supervised = scheduler.clone();
+    let handle = tokio::spawn(supervise(
+        "scheduler",
+        metrics.clone(),
+        Duration::from_millis(10),
+        move || {
+            let scheduler = supervised.clone();
+            async move { scheduler.run().await }
+        },
+    ));
+    let succeeded = tokio::time::timeout(Duration::from_secs(10), async {
+        while metrics.runs_total.with_label_values(&["success"]).get() == 0 {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await;
+    handle.abort();
+    let _ = std::fs::remove_file(&marker);
+
+    // Then: The panicked run was restarted, and the next run still executed
+    assert!(succeeded.is_ok(), "No run executed after the panic");
+    assert_eq!(
+        metrics
+            .task_restarts_total
+            .with_label_values(&["scheduler"])
+            .get(),
+        1
+    );
+    assert!(!scheduler.is_running());
+}